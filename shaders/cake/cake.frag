@@ -14,15 +14,29 @@ layout(push_constant) uniform PushConstants {
     int end_time;
     int screen_width;
     int screen_height;
+    float border_width;
+    float rounding;
+    float brightness;
+    float saturation;
+    float opacity;
+    float rounding_min_length;
 } consts;
 
+// Keep in sync with `MIDIColor::adjusted`
+vec3 adjust_color(vec3 color) {
+    if (consts.brightness == 1.0 && consts.saturation == 1.0) {
+        return color;
+    }
+
+    float luma = dot(color, vec3(0.299, 0.587, 0.114));
+    return clamp((luma + (color - luma) * consts.saturation) * consts.brightness, 0.0, 1.0);
+}
+
 layout(set = 0, binding = 0) readonly buffer BufferArray
 {
     ivec4 BinTree[];
 } buffers[256];
 
-const float border_width = 2;
-
 ivec4 getNoteAt(int time) {
     int nextIndex = buffers[buffer_index].BinTree[0].x;
 
@@ -64,7 +78,7 @@ void main()
     if (note.z == -1) {
         discard;
     } else {
-        frag_color = vec3(((note.z >> 16) & 0xFF) / 255.0, ((note.z >> 8) & 0xFF) / 255.0, (note.z & 0xFF) / 255.0);
+        frag_color = adjust_color(vec3(((note.z >> 16) & 0xFF) / 255.0, ((note.z >> 8) & 0xFF) / 255.0, (note.z & 0xFF) / 255.0));
     }
 
     vec3 color = frag_color;
@@ -86,18 +100,36 @@ void main()
 
     float y = 1 - screen_pos.y;
     float note_top_dist = (y - note_top);
-    float note_bottom_dist = (note_bottom - y);
 
     float note_left_dist = (screen_pos.x - left_right.x);
-    float note_right_dist = (left_right.y - screen_pos.x);
 
-    float min_x_dist = min(note_left_dist, note_right_dist) * consts.screen_width;
-    float min_y_dist = min(note_top_dist, note_bottom_dist) * consts.screen_height;
-    float min_dist = min(min_x_dist, min_y_dist);
+    vec2 size_pixels = vec2(
+        (left_right.y - left_right.x) * consts.screen_width,
+        (note_bottom - note_top) * consts.screen_height
+    );
+    vec2 pos_pixels = vec2(note_left_dist * consts.screen_width, note_top_dist * consts.screen_height);
+
+    // Keep the border and the corners proportional on very small notes
+    float min_side = min(size_pixels.x, size_pixels.y);
+    float border_width = min(consts.border_width, min_side / 4);
+    float radius = min(consts.rounding, min_side / 2);
+    // Short notes would turn into circles, so they keep their square corners
+    if(size_pixels.y < consts.rounding_min_length) {
+        radius = 0;
+    }
+
+    // Signed distance to the edge of the rounded rectangle
+    vec2 q = abs(pos_pixels - size_pixels / 2) - (size_pixels / 2 - radius);
+    float dist = length(max(q, 0)) + min(max(q.x, q.y), 0) - radius;
+
+    if(dist > 0) {
+        discard;
+    }
 
-    if(min_dist < border_width) {
+    if(-dist < border_width) {
         color = frag_color * 0.034;
     }
 
-    fsout_Color = vec4(color, 1);
+    // The scene is drawn by egui, which expects premultiplied alpha
+    fsout_Color = vec4(color * consts.opacity, consts.opacity);
 }
@@ -4,11 +4,11 @@ layout(location = 0) in vec3 frag_color;
 layout(location = 1) in vec2 frag_tex_coord;
 layout(location = 2) in vec2 v_note_size;
 layout(location = 3) in vec2 win_size;
+layout(location = 4) in vec3 note_style;
+layout(location = 5) in vec2 edge_fade;
 
 layout(location = 0) out vec4 out_color;
 
-const float border = 2;
-
 void main() {
     vec2 v_uv = frag_tex_coord;
     
@@ -23,22 +23,40 @@ void main() {
     color *= desaturated;
     color += color_grad;
 
-    float horiz_width_pixels = v_note_size.x / 2 * win_size.x;
-    float vert_width_pixels = v_note_size.y / 2 * win_size.y;
+    vec2 size_pixels = v_note_size / 2 * win_size;
+    vec2 pos_pixels = v_uv * size_pixels;
 
-    float horiz_margin = 1 / horiz_width_pixels * border;
-    float vert_margin = 1 / vert_width_pixels * border;
+    // Keep the border and the corners proportional on very small notes
+    float min_side = min(size_pixels.x, size_pixels.y);
+    float border_width = min(note_style.x, min_side / 4);
+    float radius = min(note_style.y, min_side / 2);
 
-    bool border =
-        v_uv.x < horiz_margin ||
-        v_uv.x > 1 - horiz_margin ||
-        v_uv.y < vert_margin ||
-        v_uv.y > 1 - vert_margin;
+    // Signed distance to the edge of the rounded rectangle
+    vec2 q = abs(pos_pixels - size_pixels / 2) - (size_pixels / 2 - radius);
+    float dist = length(max(q, 0)) + min(max(q.x, q.y), 0) - radius;
 
-    if(border)
+    if(dist > 0)
+    {
+        discard;
+    }
+
+    if(-dist < border_width)
     {
         color = vec3(frag_color * 0.034);
     }
 
-    out_color = vec4(color, 1.0);
+    // The scene is drawn by egui, which expects premultiplied alpha
+    float opacity = note_style.z;
+
+    // Notes cut off by the side of the view fade out towards it, instead of
+    // ending in a hard edge
+    if(edge_fade.x > 0)
+    {
+        opacity *= clamp(gl_FragCoord.x / edge_fade.x, 0, 1);
+    }
+    if(edge_fade.y > 0)
+    {
+        opacity *= clamp((win_size.x - gl_FragCoord.x) / edge_fade.y, 0, 1);
+    }
+    out_color = vec4(color * opacity, opacity);
 }
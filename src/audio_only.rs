@@ -0,0 +1,233 @@
+use std::{
+    io::{self, BufRead},
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::RecvTimeoutError;
+
+use crate::{
+    audio_playback::SimpleTemporaryPlayer,
+    midi::{is_stdin_path, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion},
+    settings::{colors::ColorAssignments, MidiLoading, WasabiSettings},
+};
+
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often to check whether the MIDI has ended, for `--loop-count`
+const END_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+const HELP: &str = "Commands:
+  p          Play/pause
+  +N / -N    Seek forward/backward by N seconds
+  s X        Set the playback speed, 1.0 being normal speed
+  t          Print the current time
+  q          Quit";
+
+enum Input {
+    Line(String),
+    Interrupted,
+}
+
+/// Plays the MIDI file from the command line without opening a window,
+/// reading the playback controls from stdin
+pub fn run(settings: &WasabiSettings) {
+    let Some(midi_path) = settings.load_midi_file.as_deref() else {
+        log::error!("A MIDI file is required in audio only mode");
+        return;
+    };
+
+    let synth = Arc::new(RwLock::new(SimpleTemporaryPlayer::from_settings(settings)));
+
+    if synth.write().unwrap().take_device_error().is_some() {
+        std::process::exit(1);
+    }
+
+    // XSynth would only play silence without a soundfont
+    if let Some(err) = synth.read().unwrap().soundfont_error() {
+        log::error!("{err}, a soundfont is required in audio only mode. Pass one with --sfz-path");
+        std::process::exit(1);
+    }
+
+    let load = || {
+        // Nothing draws the notes, so the live loader would keep all of them
+        if settings.midi.midi_loading == MidiLoading::Live {
+            return LiveLoadMIDIFile::load_without_notes(
+                midi_path,
+                synth.clone(),
+                settings.midi.tempo_override,
+            )
+            .map(MIDIFileUnion::Live);
+        }
+        MIDIFileUnion::load_from_file(
+            midi_path,
+            settings.midi.midi_loading,
+            synth.clone(),
+            settings.midi.random_colors,
+            settings.midi.color_palette,
+            settings.midi.color_by,
+            &ColorAssignments::default(),
+            settings.midi.tempo_override,
+        )
+    };
+
+    let mut midi_file = match load() {
+        Ok(midi_file) => midi_file,
+        Err(err) => {
+            log::error!("Failed to load MIDI: {err}");
+            return;
+        }
+    };
+
+    let (sender, reciever) = crossbeam_channel::unbounded();
+
+    let interrupt_sender = sender.clone();
+    if let Err(err) = ctrlc::set_handler(move || {
+        interrupt_sender.send(Input::Interrupted).ok();
+    }) {
+        log::warn!("Failed to set the Ctrl+C handler: {err}");
+    }
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(Input::Line(line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    if !settings.start_paused {
+        midi_file.timer_mut().play();
+    }
+
+    println!("{HELP}");
+    print_status(&midi_file);
+
+    let mut last_status = Instant::now();
+    let mut plays = 1;
+
+    loop {
+        match reciever.recv_timeout(END_CHECK_INTERVAL) {
+            Ok(Input::Line(line)) => {
+                if !run_command(&mut midi_file, line.trim()) {
+                    break;
+                }
+            }
+            Ok(Input::Interrupted) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                if !midi_file.timer().is_paused() && last_status.elapsed() >= STATUS_INTERVAL {
+                    print_status(&midi_file);
+                    last_status = Instant::now();
+                }
+            }
+        }
+
+        let Some(loop_count) = settings.loop_count else {
+            continue;
+        };
+        if !is_finished(&midi_file) {
+            continue;
+        }
+        if loop_count != 0 && plays >= loop_count {
+            break;
+        }
+        plays += 1;
+
+        if midi_file.allows_seeking_backward() {
+            synth.write().unwrap().reset();
+            midi_file.timer_mut().seek(Duration::ZERO);
+        } else if is_stdin_path(midi_path) {
+            log::error!("A MIDI read from stdin can't be looped with live loading");
+            break;
+        } else {
+            // Live loading can't seek backwards, so load the file again instead
+            midi_file.timer_mut().pause();
+            synth.write().unwrap().reset();
+            match load() {
+                Ok(mut reloaded) => {
+                    reloaded.timer_mut().play();
+                    midi_file = reloaded;
+                }
+                Err(err) => {
+                    log::error!("Failed to load MIDI: {err}");
+                    break;
+                }
+            }
+        }
+        println!("Loop {plays}");
+    }
+
+    midi_file.timer_mut().pause();
+    synth.write().unwrap().reset();
+}
+
+/// Runs a single command, returns false if the playback should stop
+fn run_command(midi_file: &mut MIDIFileUnion, command: &str) -> bool {
+    let time = midi_file.timer().get_time().as_secs_f64();
+
+    match command {
+        "" => {}
+        "q" => return false,
+        "p" => midi_file.timer_mut().toggle_pause(),
+        "t" => {}
+        "h" => {
+            println!("{HELP}");
+            return true;
+        }
+        _ if command.starts_with('+') || command.starts_with('-') => match command.parse::<f64>() {
+            Ok(seconds) => {
+                if seconds < 0.0 && !midi_file.allows_seeking_backward() {
+                    println!("This MIDI can't be seeked backward");
+                } else {
+                    let time = (time + seconds).max(0.0);
+                    midi_file.timer_mut().seek(Duration::from_secs_f64(time));
+                }
+            }
+            Err(_) => println!("Invalid amount of seconds: {command}"),
+        },
+        _ if command.starts_with("s ") => match command[2..].trim().parse::<f64>() {
+            Ok(speed) if speed > 0.0 => midi_file.timer_mut().set_speed(speed),
+            _ => println!("Invalid speed: {}", &command[2..]),
+        },
+        _ => {
+            println!("Unknown command: {command}, type h for help");
+            return true;
+        }
+    }
+
+    print_status(midi_file);
+    true
+}
+
+fn is_finished(midi_file: &MIDIFileUnion) -> bool {
+    midi_file.midi_length().map_or(false, |length| {
+        midi_file.timer().get_time().as_secs_f64() >= length
+    })
+}
+
+fn print_status(midi_file: &MIDIFileUnion) {
+    fn format_time(seconds: f64) -> String {
+        let seconds = seconds.max(0.0) as u64;
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    let timer = midi_file.timer();
+    let time = timer.get_time().as_secs_f64();
+    let length = midi_file
+        .midi_length()
+        .map(format_time)
+        .unwrap_or_else(|| "??:??".to_string());
+    let state = if timer.is_paused() {
+        "Paused"
+    } else {
+        "Playing"
+    };
+
+    println!(
+        "[{state}] {} / {length} ({}x)",
+        format_time(time),
+        timer.speed()
+    );
+}
@@ -0,0 +1,118 @@
+use std::{collections::VecDeque, time::Instant};
+
+/// The most events that are kept in the log, the older ones are dropped
+const MAX_LOGGED_EVENTS: usize = 2000;
+
+/// The type of a channel event, taken from its status byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    NoteOn,
+    NoteOff,
+    Aftertouch,
+    Control,
+    Program,
+    ChannelPressure,
+    PitchBend,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 7] = [
+        EventKind::NoteOn,
+        EventKind::NoteOff,
+        EventKind::Aftertouch,
+        EventKind::Control,
+        EventKind::Program,
+        EventKind::ChannelPressure,
+        EventKind::PitchBend,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EventKind::NoteOn => "Note On",
+            EventKind::NoteOff => "Note Off",
+            EventKind::Aftertouch => "Aftertouch",
+            EventKind::Control => "Control",
+            EventKind::Program => "Program",
+            EventKind::ChannelPressure => "Pressure",
+            EventKind::PitchBend => "Pitch Bend",
+        }
+    }
+}
+
+/// An event that was sent to the synth, packed the same way as the synth takes it
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedEvent {
+    pub time: Instant,
+    data: u32,
+}
+
+impl LoggedEvent {
+    /// The channel, from 0 to 15
+    pub fn channel(&self) -> u8 {
+        (self.data & 0x0F) as u8
+    }
+
+    fn data1(&self) -> u32 {
+        (self.data >> 8) & 0x7F
+    }
+
+    fn data2(&self) -> u32 {
+        (self.data >> 16) & 0x7F
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self.data & 0xF0 {
+            0x90 if self.data2() > 0 => EventKind::NoteOn,
+            0x80 | 0x90 => EventKind::NoteOff,
+            0xA0 => EventKind::Aftertouch,
+            0xB0 => EventKind::Control,
+            0xC0 => EventKind::Program,
+            0xD0 => EventKind::ChannelPressure,
+            _ => EventKind::PitchBend,
+        }
+    }
+
+    /// The values of the event in a readable form
+    pub fn describe(&self) -> String {
+        match self.kind() {
+            EventKind::NoteOn | EventKind::NoteOff => {
+                format!("key {} vel {}", self.data1(), self.data2())
+            }
+            EventKind::Aftertouch => format!("key {} pressure {}", self.data1(), self.data2()),
+            EventKind::Control => format!("cc {} = {}", self.data1(), self.data2()),
+            EventKind::Program => format!("program {}", self.data1()),
+            EventKind::ChannelPressure => format!("pressure {}", self.data1()),
+            EventKind::PitchBend => {
+                let value = self.data1() | (self.data2() << 7);
+                format!("{}", value as i32 - 8192)
+            }
+        }
+    }
+}
+
+/// The last events that were sent to the synth, for finding out why a MIDI sounds wrong
+pub struct EventLog {
+    events: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::with_capacity(MAX_LOGGED_EVENTS),
+        }
+    }
+
+    pub fn push(&mut self, data: u32) {
+        if self.events.len() == MAX_LOGGED_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(LoggedEvent {
+            time: Instant::now(),
+            data,
+        });
+    }
+
+    pub fn events(&self) -> Vec<LoggedEvent> {
+        self.events.iter().copied().collect()
+    }
+}
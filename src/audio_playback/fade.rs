@@ -0,0 +1,253 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How many volume changes the fade before a reset is made of
+const FADE_STEPS: u32 = 8;
+
+/// A synth that a fade can turn down and reset from the fade thread
+pub trait FadeOutput: Send {
+    fn send(&mut self, data: u32);
+    fn reset(&mut self);
+}
+
+struct FadeJob {
+    output: Box<dyn FadeOutput>,
+    /// The channels that get turned down and their volumes before the fade
+    volumes: Vec<(u32, u8)>,
+    duration: Duration,
+}
+
+impl FadeJob {
+    fn send_volumes(&mut self, scale: f64) {
+        for &(channel, volume) in &self.volumes {
+            let volume = (volume as f64 * scale).round() as u32;
+            self.output.send(0xB0 | channel | (7 << 8) | (volume << 16));
+        }
+    }
+}
+
+#[derive(Default)]
+struct FadeState {
+    /// The fade that was asked for, until the thread picks it up
+    job: Option<FadeJob>,
+    /// Whether a fade was asked for and the synth wasn't reset yet
+    fading: bool,
+    /// The events sent during the fade, held back so that the reset doesn't cut them off
+    pending: Vec<u32>,
+    /// Set to end the running fade without resetting the synth
+    stopped: bool,
+    /// Whether the thread is running a fade and holds on to its synth
+    running: bool,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<FadeState>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, FadeState> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Turns the channel volumes down over a short time on a thread of its own, then
+/// resets the synth and turns them back up, so that the voices don't stop with a
+/// click. The events sent in the meantime are held back and sent once it's over.
+pub struct FadeWorker {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FadeWorker {
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared::default());
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || Self::run(&thread_shared));
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    fn run(shared: &Shared) {
+        let mut state = shared.lock();
+        loop {
+            state = shared
+                .changed
+                .wait_while(state, |s| s.job.is_none() && !s.closed)
+                .unwrap();
+            if state.closed {
+                return;
+            }
+            let Some(mut job) = state.job.take() else {
+                continue;
+            };
+            state.stopped = false;
+            state.running = true;
+            drop(state);
+
+            // Another fade being asked for also ends this one, it's on an old synth
+            let interrupted = |s: &FadeState| s.stopped || s.job.is_some() || s.closed;
+            let step = job.duration / FADE_STEPS;
+            for i in (0..FADE_STEPS).rev() {
+                job.send_volumes(i as f64 / FADE_STEPS as f64);
+                let (state, _) = shared
+                    .changed
+                    .wait_timeout_while(shared.lock(), step, |s| !interrupted(s))
+                    .unwrap();
+                if interrupted(&state) {
+                    break;
+                }
+            }
+
+            // Everything is sent while the state is locked, so that no new event
+            // can get in before the ones that were held back
+            state = shared.lock();
+            if !interrupted(&state) {
+                job.output.reset();
+                // Nothing is playing anymore, so the volumes can go back up right away
+                job.send_volumes(1.0);
+                for data in state.pending.drain(..) {
+                    job.output.send(data);
+                }
+            }
+            drop(job);
+            state.stopped = false;
+            state.running = false;
+            state.fading = state.job.is_some();
+            shared.changed.notify_all();
+        }
+    }
+
+    /// Starts fading out the synth, which gets reset once the fade is over
+    pub fn start(&self, output: Box<dyn FadeOutput>, volumes: Vec<(u32, u8)>, duration: Duration) {
+        let mut state = self.shared.lock();
+        state.job = Some(FadeJob {
+            output,
+            volumes,
+            duration,
+        });
+        state.fading = true;
+        state.pending.clear();
+        self.shared.changed.notify_all();
+    }
+
+    /// Holds the event back if a fade is running, returning whether it did
+    pub fn hold_back(&self, data: u32) -> bool {
+        let mut state = self.shared.lock();
+        if state.fading {
+            state.pending.push(data);
+        }
+        state.fading
+    }
+
+    /// Lets the running fade take care of a reset, as it resets the synth once it's
+    /// over anyway. The events held back so far are dropped, the reset would have
+    /// cut them off. Returns whether a fade was running.
+    pub fn take_reset(&self) -> bool {
+        let mut state = self.shared.lock();
+        if state.fading {
+            state.pending.clear();
+        }
+        state.fading
+    }
+
+    /// Ends the running fade without resetting the synth, for when the synth is
+    /// replaced. The events held back are dropped. Waits for the thread to let go
+    /// of the synth, so that it can be closed.
+    pub fn stop(&self) {
+        let mut state = self.shared.lock();
+        if state.fading {
+            state.job = None;
+            state.fading = false;
+            state.stopped = true;
+            state.pending.clear();
+            self.shared.changed.notify_all();
+        }
+        drop(
+            self.shared
+                .changed
+                .wait_while(state, |s| s.running)
+                .unwrap(),
+        );
+    }
+}
+
+impl Drop for FadeWorker {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorded(Arc<Mutex<Vec<Option<u32>>>>);
+
+    /// Records the events, with `None` for a reset
+    impl FadeOutput for Recorded {
+        fn send(&mut self, data: u32) {
+            self.0.lock().unwrap().push(Some(data));
+        }
+
+        fn reset(&mut self) {
+            self.0.lock().unwrap().push(None);
+        }
+    }
+
+    #[test]
+    fn held_back_events_are_sent_after_the_reset() {
+        let fade = FadeWorker::new();
+        let output = Recorded::default();
+        fade.start(
+            Box::new(output.clone()),
+            vec![(0, 100)],
+            Duration::from_millis(40),
+        );
+        assert!(fade.hold_back(0x403C90));
+
+        // The events go out once the fade is over, without waiting for the next one
+        let sent = || output.0.lock().unwrap().clone();
+        for _ in 0..100 {
+            if !sent().contains(&Some(0x403C90)) {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        let sent = sent();
+        let reset = sent.iter().position(|e| e.is_none()).unwrap();
+        assert_eq!(sent[reset - 1], Some(0x0007B0));
+        assert_eq!(&sent[reset + 1..], &[Some(0x6407B0), Some(0x403C90)]);
+        assert!(!fade.hold_back(0x403C80));
+    }
+
+    #[test]
+    fn stopped_fades_do_not_reset() {
+        let fade = FadeWorker::new();
+        let output = Recorded::default();
+        fade.start(
+            Box::new(output.clone()),
+            vec![(0, 100)],
+            Duration::from_secs(10),
+        );
+        fade.hold_back(0x403C90);
+        fade.stop();
+        assert!(!fade.take_reset());
+        drop(fade);
+
+        assert!(!output.0.lock().unwrap().iter().any(|e| e.is_none()));
+    }
+}
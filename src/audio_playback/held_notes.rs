@@ -0,0 +1,94 @@
+use std::collections::{BTreeSet, VecDeque};
+
+/// The notes held down in the synth, kept in the order that they get stolen in once
+/// the voice limit is reached: the quietest note first, and the oldest one of those.
+pub struct HeldNotes {
+    /// When each note held on each key of each channel started and its velocity,
+    /// oldest first, as that's the one a note off releases
+    keys: Vec<VecDeque<(u64, u8)>>,
+    /// The velocity, start, channel and key of every held note
+    steal_order: BTreeSet<(u8, u64, u8, u8)>,
+    next_start: u64,
+}
+
+impl HeldNotes {
+    pub fn new() -> Self {
+        Self {
+            keys: vec![VecDeque::new(); 16 * 128],
+            steal_order: BTreeSet::new(),
+            next_start: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steal_order.len()
+    }
+
+    pub fn note_on(&mut self, channel: usize, key: usize, velocity: u8) {
+        let start = self.next_start;
+        self.next_start += 1;
+        self.keys[channel * 128 + key].push_back((start, velocity));
+        self.steal_order
+            .insert((velocity, start, channel as u8, key as u8));
+    }
+
+    pub fn note_off(&mut self, channel: usize, key: usize) {
+        if let Some((start, velocity)) = self.keys[channel * 128 + key].pop_front() {
+            self.steal_order
+                .remove(&(velocity, start, channel as u8, key as u8));
+        }
+    }
+
+    /// Forgets the note that should be stolen next and returns its channel and key
+    pub fn steal(&mut self) -> Option<(usize, usize)> {
+        let (_, start, channel, key) = self.steal_order.pop_first()?;
+        let (channel, key) = (channel as usize, key as usize);
+        let notes = &mut self.keys[channel * 128 + key];
+        if let Some(i) = notes.iter().position(|&(s, _)| s == start) {
+            notes.remove(i);
+        }
+        Some((channel, key))
+    }
+
+    pub fn clear(&mut self) {
+        if self.steal_order.is_empty() {
+            return;
+        }
+        for notes in self.keys.iter_mut() {
+            notes.clear();
+        }
+        self.steal_order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steals_the_quietest_note_then_the_oldest() {
+        let mut notes = HeldNotes::new();
+        notes.note_on(0, 60, 100);
+        notes.note_on(1, 62, 40);
+        notes.note_on(2, 64, 40);
+
+        assert_eq!(notes.steal(), Some((1, 62)));
+        assert_eq!(notes.steal(), Some((2, 64)));
+        assert_eq!(notes.steal(), Some((0, 60)));
+        assert_eq!(notes.steal(), None);
+    }
+
+    #[test]
+    fn released_notes_are_not_stolen() {
+        let mut notes = HeldNotes::new();
+        notes.note_on(0, 60, 10);
+        notes.note_on(0, 60, 90);
+        notes.note_on(0, 61, 50);
+        notes.note_off(0, 60);
+
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes.steal(), Some((0, 61)));
+        assert_eq!(notes.steal(), Some((0, 60)));
+        assert_eq!(notes.len(), 0);
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+use crate::midi::MIDIColor;
+
+use super::{transpose_key, SimpleTemporaryPlayer};
+
+pub fn list_midi_inputs() -> Vec<String> {
+    match MidiInput::new("wasabi") {
+        Ok(input) => input
+            .ports()
+            .iter()
+            .filter_map(|port| input.port_name(port).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// A hardware MIDI input port whose events are sent straight to the synth
+pub struct LiveMidiInput {
+    _connection: MidiInputConnection<()>,
+    /// A bit for every channel that is holding each key
+    held_keys: Arc<Mutex<[u16; 128]>>,
+    colors: Vec<MIDIColor>,
+}
+
+impl LiveMidiInput {
+    /// Connects to the port with the given name, or the given index
+    /// from `--list-midi-inputs`
+    pub fn connect(port: &str, synth: Arc<RwLock<SimpleTemporaryPlayer>>) -> Result<Self, String> {
+        let mut input = MidiInput::new("wasabi").map_err(|e| e.to_string())?;
+        input.ignore(Ignore::All);
+
+        let ports = input.ports();
+        let index = port.parse::<usize>().ok().filter(|i| *i < ports.len());
+        let found = match index {
+            Some(i) => ports.get(i),
+            None => ports
+                .iter()
+                .find(|p| input.port_name(p).map_or(false, |name| name == port)),
+        };
+        let found = found.ok_or_else(|| format!("The MIDI input \"{port}\" was not found"))?;
+
+        let held_keys = Arc::new(Mutex::new([0u16; 128]));
+        let held = held_keys.clone();
+
+        let connection = input
+            .connect(
+                found,
+                "wasabi-input",
+                move |_, message, _| {
+                    let Some(&status) = message.first() else {
+                        return;
+                    };
+                    // Only channel messages are forwarded
+                    if !(0x80..0xF0).contains(&status) {
+                        return;
+                    }
+
+                    let key = message.get(1).copied().unwrap_or(0);
+                    let vel = message.get(2).copied().unwrap_or(0);
+                    let channel = status & 0x0F;
+
+                    let mut held = held.lock().unwrap();
+                    let key_bits = &mut held[(key & 0x7F) as usize];
+                    match status & 0xF0 {
+                        0x90 if vel > 0 => *key_bits |= 1 << channel,
+                        0x80 | 0x90 => *key_bits &= !(1 << channel),
+                        _ => {}
+                    }
+                    drop(held);
+
+                    let data = status as u32 | (key as u32) << 8 | (vel as u32) << 16;
+                    synth.write().unwrap().push_event(data);
+                },
+                (),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            _connection: connection,
+            held_keys,
+            colors: MIDIColor::new_vec_for_tracks(1),
+        })
+    }
+
+    /// Adds the colors of the held keys to the colors of the keyboard
+    pub fn add_key_colors(&self, key_colors: &mut [Vec<MIDIColor>], transpose: i32) {
+        let held = self.held_keys.lock().unwrap();
+        for (key, channels) in held.iter().enumerate() {
+            let Some(key) = transpose_key(key, transpose) else {
+                continue;
+            };
+            for channel in 0..16 {
+                if channels & (1 << channel) != 0 {
+                    key_colors[key].push(self.colors[channel]);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,27 @@
 use kdmapi::{KDMAPIStream, KDMAPI};
-use std::ops::RangeInclusive;
-use xsynth_core::{channel::ChannelInitOptions, soundfont::SoundfontInitOptions};
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use xsynth_core::{
+    channel::ChannelInitOptions,
+    soundfont::{SoundfontBase, SoundfontInitOptions},
+    AudioStreamParams,
+};
+
+use crate::settings::{Synth, WasabiSettings};
+
+use self::{
+    event_log::{EventLog, LoggedEvent},
+    fade::{FadeOutput, FadeWorker},
+    held_notes::HeldNotes,
+};
+
+pub mod event_log;
+mod fade;
+mod held_notes;
+pub mod midi_input;
 pub mod xsynth;
 
 #[derive(Clone)]
@@ -10,43 +31,217 @@ pub enum AudioPlayerType {
         use_threadpool: bool,
         ignore_range: RangeInclusive<u8>,
         options: ChannelInitOptions,
+        output_device: Option<String>,
+        sample_rate: Option<u32>,
     },
     Kdmapi,
 }
 
+/// The state of a MIDI channel, taken from the events sent to the synth
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChannelInfo {
+    pub program: u8,
+    /// The last channel volume (CC 7), if one was sent
+    pub volume: Option<u8>,
+    /// The last pan position (CC 10), if one was sent
+    pub pan: Option<u8>,
+    pub used: bool,
+}
+
+impl ChannelInfo {
+    /// The pan from -1.0 (left) to 1.0 (right), channels without a pan event are centered
+    pub fn pan_position(&self) -> f32 {
+        self.pan
+            .map_or(0.0, |pan| ((pan as f32 - 64.0) / 63.0).clamp(-1.0, 1.0))
+    }
+}
+
+/// Channel 10 is reserved for percussion in General MIDI
+pub const DRUM_CHANNEL: usize = 9;
+
+/// The channel volume (CC 7) that synths start with
+const DEFAULT_CHANNEL_VOLUME: u8 = 100;
+
+/// Shifts a key by the transpose amount. Keys that end up outside
+/// of the MIDI range are dropped instead of wrapping around.
+pub fn transpose_key(key: usize, transpose: i32) -> Option<usize> {
+    if transpose == 0 {
+        return Some(key);
+    }
+
+    let key = key as i32 + transpose;
+    (0..128).contains(&key).then_some(key as usize)
+}
+
+/// KDMAPI is provided by OmniMIDI, which only exists on Windows
+pub const fn kdmapi_available() -> bool {
+    cfg!(windows)
+}
+
 pub struct SimpleTemporaryPlayer {
     player_type: AudioPlayerType,
     xsynth: Option<xsynth::XSynthPlayer>,
-    kdmapi: Option<KDMAPIStream>,
+    /// Shared with the fade thread, which turns KDMAPI down and resets it
+    kdmapi: Option<Arc<Mutex<KDMAPIStream>>>,
+    pitch_bends: [f32; 16],
+    channels: [ChannelInfo; 16],
+    /// When each drum was last hit, for the drum strip
+    drum_hits: [Option<Instant>; 128],
+    /// The velocity of the note held on each transposed key of each channel, 0 if none is
+    key_velocities: [[u8; 128]; 16],
+    /// The last value of each controller on each channel, if it was sent
+    controllers: [[Option<u8>; 128]; 16],
+    /// The last value of each controller on any channel
+    last_controllers: [Option<u8>; 128],
+    transpose: i32,
+    /// How long the voices fade out for before the synth is reset
+    fade_ms: f64,
+    /// The program that replaces the program changes of every channel except the drums
+    force_program: Option<u8>,
+    /// Why XSynth has no soundfont loaded, if it doesn't
+    soundfont_error: Option<String>,
+    /// Why XSynth couldn't be opened, until it's taken to be shown
+    device_error: Option<String>,
+    /// The most voices XSynth plays at once
+    max_voices: Option<u64>,
+    /// How many note ons were dropped or stolen by the voice limit on each key of each
+    /// channel, so that their note offs get dropped too
+    dropped_notes: [[u32; 128]; 16],
+    /// The notes that the voice limit can steal, only kept while there is a limit
+    held_notes: HeldNotes,
+    /// The last events sent to the synth, only kept while the event log is open
+    event_log: Option<EventLog>,
+    /// How many times the synth was reset, so that every MIDI playing into it
+    /// can tell when its controls have to be sent again
+    resets: u64,
+    /// The thread that fades out before the synth is reset, started with the first fade
+    fade: Option<FadeWorker>,
+}
+
+impl FadeOutput for Arc<Mutex<KDMAPIStream>> {
+    fn send(&mut self, data: u32) {
+        self.lock().unwrap().send_direct_data(data);
+    }
+
+    fn reset(&mut self) {
+        self.lock().unwrap().reset();
+    }
 }
 
 impl SimpleTemporaryPlayer {
     pub fn new(player_type: AudioPlayerType) -> Self {
+        let mut device_error = None;
         let (xsynth, kdmapi) = match player_type.clone() {
             AudioPlayerType::XSynth {
                 buffer,
                 use_threadpool,
                 ignore_range,
                 options,
+                output_device,
+                sample_rate,
             } => {
-                let xsynth =
-                    xsynth::XSynthPlayer::new(buffer, use_threadpool, ignore_range, options);
-                (Some(xsynth), None)
+                let xsynth = xsynth::XSynthPlayer::new(
+                    buffer,
+                    use_threadpool,
+                    ignore_range,
+                    options,
+                    output_device.as_deref(),
+                    sample_rate,
+                );
+                match xsynth {
+                    Ok(xsynth) => (Some(xsynth), None),
+                    Err(err) => {
+                        log::error!("{err}");
+                        device_error = Some(err);
+                        (None, None)
+                    }
+                }
             }
             AudioPlayerType::Kdmapi => {
-                let kdmapi = KDMAPI.open_stream();
+                log::info!("Playing through KDMAPI");
+                let kdmapi = Arc::new(Mutex::new(KDMAPI.open_stream()));
                 (None, Some(kdmapi))
             }
         };
+        Self::with_synth(player_type, xsynth, kdmapi, device_error)
+    }
+
+    /// A player that doesn't play anything, for loading MIDIs in the tests
+    #[cfg(test)]
+    pub fn without_synth() -> Self {
+        Self::with_synth(AudioPlayerType::Kdmapi, None, None, None)
+    }
+
+    fn with_synth(
+        player_type: AudioPlayerType,
+        xsynth: Option<xsynth::XSynthPlayer>,
+        kdmapi: Option<Arc<Mutex<KDMAPIStream>>>,
+        device_error: Option<String>,
+    ) -> Self {
+        // XSynth is opened without a soundfont, it gets set afterwards
+        let soundfont_error = xsynth.is_some().then(|| xsynth::NO_SOUNDFONT.to_string());
         Self {
             player_type,
             xsynth,
             kdmapi,
+            pitch_bends: [0.0; 16],
+            channels: Default::default(),
+            drum_hits: [None; 128],
+            key_velocities: [[0; 128]; 16],
+            controllers: [[None; 128]; 16],
+            last_controllers: [None; 128],
+            transpose: 0,
+            fade_ms: 0.0,
+            force_program: None,
+            soundfont_error,
+            device_error,
+            max_voices: None,
+            dropped_notes: [[0; 128]; 16],
+            held_notes: HeldNotes::new(),
+            event_log: None,
+            resets: 0,
+            fade: None,
         }
     }
 
+    /// Creates the synth chosen in the settings, with the soundfont already loaded
+    pub fn from_settings(settings: &WasabiSettings) -> Self {
+        let mut player = match settings.synth.synth {
+            Synth::Kdmapi => Self::new(AudioPlayerType::Kdmapi),
+            Synth::XSynth => {
+                let mut player = Self::new(AudioPlayerType::XSynth {
+                    buffer: settings.synth.buffer_ms,
+                    use_threadpool: settings.synth.use_threadpool,
+                    ignore_range: settings.synth.vel_ignore.clone(),
+                    options: xsynth::convert_to_channel_init(settings),
+                    output_device: settings.synth.output_device.clone(),
+                    sample_rate: settings.synth.sample_rate,
+                });
+                player.preload_soundfonts(settings);
+                player.set_soundfont(
+                    &settings.synth.sfz_path,
+                    xsynth::convert_to_sf_init(settings),
+                );
+                player.set_layer_count(match settings.synth.layer_count {
+                    0 => None,
+                    _ => Some(settings.synth.layer_count),
+                });
+                player
+            }
+        };
+        player.transpose = settings.midi.transpose;
+        player.fade_ms = settings.synth.fade_ms;
+        player.max_voices = settings.synth.max_voices;
+        player.set_force_program(settings.synth.force_program);
+        player
+    }
+
     pub fn switch_player(&mut self, player_type: AudioPlayerType) {
         self.reset();
+        // The fade is on the old synth, the new one has nothing to wait for
+        if let Some(fade) = self.fade.as_ref() {
+            fade.stop();
+        }
         self.xsynth = None;
         self.kdmapi = None;
         let new_player = Self::new(player_type);
@@ -54,6 +249,27 @@ impl SimpleTemporaryPlayer {
         self.player_type = new_player.player_type;
         self.xsynth = new_player.xsynth;
         self.kdmapi = new_player.kdmapi;
+        self.soundfont_error = new_player.soundfont_error;
+        self.device_error = new_player.device_error;
+
+        self.restore_channels();
+        self.send_forced_program();
+    }
+
+    /// Sends the programs and volumes of the channels to the synth again,
+    /// so that a newly opened synth sounds the same as the old one
+    fn restore_channels(&mut self) {
+        for (channel, info) in self.channels.into_iter().enumerate() {
+            if !info.used {
+                continue;
+            }
+
+            let channel = channel as u32;
+            self.push_event(0xC0 | channel | (info.program as u32) << 8);
+            if let Some(volume) = info.volume {
+                self.push_event(0xB0 | channel | 7 << 8 | (volume as u32) << 16);
+            }
+        }
     }
 
     pub fn get_voice_count(&self) -> u64 {
@@ -69,13 +285,269 @@ impl SimpleTemporaryPlayer {
         }
     }
 
+    pub fn get_output_device(&self) -> Option<&str> {
+        match self.player_type {
+            AudioPlayerType::XSynth { .. } => self
+                .xsynth
+                .as_ref()
+                .map(|xsynth| xsynth.output_device.as_str()),
+            AudioPlayerType::Kdmapi => None,
+        }
+    }
+
+    /// The latest pitch bend of each channel, from -1.0 to 1.0
+    pub fn pitch_bends(&self) -> [f32; 16] {
+        self.pitch_bends
+    }
+
+    /// The last value of a controller on the channel, or on any channel when
+    /// there is none. `None` until the controller is sent.
+    pub fn controller(&self, controller: u8, channel: Option<u8>) -> Option<u8> {
+        let controller = (controller & 0x7F) as usize;
+        match channel {
+            Some(channel) => self.controllers[(channel & 0x0F) as usize][controller],
+            None => self.last_controllers[controller],
+        }
+    }
+
+    /// The state of each channel, with the forced program if there is one
+    pub fn channels(&self) -> [ChannelInfo; 16] {
+        let mut channels = self.channels;
+        if let Some(program) = self.force_program {
+            for (i, channel) in channels.iter_mut().enumerate() {
+                if i != DRUM_CHANNEL {
+                    channel.program = program;
+                }
+            }
+        }
+        channels
+    }
+
+    pub fn drum_hits(&self) -> [Option<Instant>; 128] {
+        self.drum_hits
+    }
+
+    pub fn is_logging_events(&self) -> bool {
+        self.event_log.is_some()
+    }
+
+    /// Starts or stops keeping the last events sent to the synth.
+    /// Stopping clears the log, so it costs nothing while it isn't shown.
+    pub fn set_event_logging(&mut self, enabled: bool) {
+        if enabled != self.event_log.is_some() {
+            self.event_log = enabled.then(EventLog::new);
+        }
+    }
+
+    pub fn logged_events(&self) -> Vec<LoggedEvent> {
+        self.event_log
+            .as_ref()
+            .map(|log| log.events())
+            .unwrap_or_default()
+    }
+
+    /// The velocity of the loudest note held on each key, 0 for the keys with no notes
+    pub fn key_velocities(&self) -> [u8; 128] {
+        let mut velocities = [0; 128];
+        for channel in self.key_velocities.iter() {
+            for (velocity, &channel_velocity) in velocities.iter_mut().zip(channel.iter()) {
+                *velocity = (*velocity).max(channel_velocity);
+            }
+        }
+        velocities
+    }
+
+    /// Which channels have at least one note held
+    pub fn active_channels(&self) -> [bool; 16] {
+        self.key_velocities
+            .map(|keys| keys.iter().any(|&velocity| velocity > 0))
+    }
+
+    /// Forgets the programs and used channels, for when the playback starts over
+    pub fn clear_channels(&mut self) {
+        self.channels = Default::default();
+    }
+
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
         for e in data {
             self.push_event(e);
         }
     }
 
-    pub fn push_event(&mut self, data: u32) {
+    /// Shifts all the played notes by a number of semitones
+    pub fn set_transpose(&mut self, transpose: i32) {
+        if self.transpose != transpose {
+            // The note offs wouldn't match the held notes anymore
+            self.panic();
+            self.transpose = transpose;
+        }
+    }
+
+    pub fn max_voices(&self) -> Option<u64> {
+        self.max_voices
+    }
+
+    /// Limits how many voices XSynth plays at once. XSynth has no voice limit of its
+    /// own, so the new notes steal the held ones while the limit is reached. The notes
+    /// that are already releasing can't be stolen, so their tails can still go over it.
+    pub fn set_max_voices(&mut self, max_voices: Option<u64>) {
+        if max_voices.is_none() {
+            self.held_notes.clear();
+        }
+        self.max_voices = max_voices;
+    }
+
+    pub fn set_fade_ms(&mut self, fade_ms: f64) {
+        self.fade_ms = fade_ms.max(0.0);
+    }
+
+    /// Plays every channel except the drums with a single program, or
+    /// goes back to the programs from the MIDI
+    pub fn set_force_program(&mut self, program: Option<u8>) {
+        self.force_program = program.map(|p| p & 0x7F);
+        match self.force_program {
+            Some(_) => self.send_forced_program(),
+            None => {
+                for (channel, info) in self.channels.into_iter().enumerate() {
+                    if channel != DRUM_CHANNEL {
+                        self.send_to_synth(0xC0 | channel as u32 | (info.program as u32) << 8);
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_forced_program(&mut self) {
+        if let Some(program) = self.force_program {
+            for channel in (0..16).filter(|&c| c != DRUM_CHANNEL) {
+                self.send_to_synth(0xC0 | channel as u32 | (program as u32) << 8);
+            }
+        }
+    }
+
+    pub fn push_event(&mut self, mut data: u32) {
+        let channel = &mut self.channels[(data & 0x0F) as usize];
+        // System messages have no channel, the low bits are part of the status
+        if (0x80..=0xEF).contains(&(data & 0xFF)) {
+            channel.used = true;
+        }
+        if data & 0xF0 == 0xC0 {
+            channel.program = ((data >> 8) & 0x7F) as u8;
+            if let Some(program) = self.force_program {
+                if (data & 0x0F) as usize != DRUM_CHANNEL {
+                    data = (data & !0xFF00) | (program as u32) << 8;
+                }
+            }
+        }
+        if data & 0xF0 == 0xB0 && (data >> 8) & 0x7F == 7 {
+            channel.volume = Some(((data >> 16) & 0x7F) as u8);
+        }
+        if data & 0xF0 == 0xB0 && (data >> 8) & 0x7F == 10 {
+            channel.pan = Some(((data >> 16) & 0x7F) as u8);
+        }
+        if data & 0xF0 == 0xB0 {
+            let controller = ((data >> 8) & 0x7F) as usize;
+            let value = Some(((data >> 16) & 0x7F) as u8);
+            self.controllers[(data & 0x0F) as usize][controller] = value;
+            self.last_controllers[controller] = value;
+        }
+
+        let is_note_on = data & 0xF0 == 0x90 && (data >> 16) & 0x7F > 0;
+        if is_note_on && (data & 0x0F) as usize == DRUM_CHANNEL {
+            self.drum_hits[((data >> 8) & 0x7F) as usize] = Some(Instant::now());
+        }
+
+        if matches!(data & 0xF0, 0x80 | 0x90 | 0xA0) {
+            let key = ((data >> 8) & 0x7F) as usize;
+            match transpose_key(key, self.transpose) {
+                Some(key) => data = (data & !0xFF00) | (key as u32) << 8,
+                None => return,
+            }
+
+            if data & 0xF0 != 0xA0 {
+                let velocity = if is_note_on {
+                    ((data >> 16) & 0x7F) as u8
+                } else {
+                    0
+                };
+                self.key_velocities[(data & 0x0F) as usize][((data >> 8) & 0x7F) as usize] =
+                    velocity;
+            }
+        }
+
+        if data & 0xF0 == 0xE0 {
+            let value = ((data >> 8) & 0x7F) | (((data >> 16) & 0x7F) << 7);
+            self.pitch_bends[(data & 0x0F) as usize] = (value as f32 - 8192.0) / 8192.0;
+        }
+
+        if self.over_voice_limit(data) {
+            return;
+        }
+
+        if let Some(event_log) = self.event_log.as_mut() {
+            event_log.push(data);
+        }
+        self.send_to_synth(data);
+    }
+
+    /// Keeps XSynth under the voice limit. A note on that comes while the limit is
+    /// reached steals a held note by releasing it early, the quietest one and the
+    /// oldest of those. Returns whether the event gets dropped instead: a note on when
+    /// there's no held note left to steal, or the note off of a dropped or stolen note.
+    fn over_voice_limit(&mut self, data: u32) -> bool {
+        let channel = (data & 0x0F) as usize;
+        let key = ((data >> 8) & 0x7F) as usize;
+        let velocity = ((data >> 16) & 0x7F) as u8;
+        match data & 0xF0 {
+            0x90 if velocity > 0 => {
+                let Some(max) = self.max_voices else {
+                    return false;
+                };
+                if !matches!(self.player_type, AudioPlayerType::XSynth { .. }) {
+                    return false;
+                }
+
+                // The voice count XSynth reports lags behind, so the notes that
+                // started since are counted too
+                let voices = self.get_voice_count().max(self.held_notes.len() as u64);
+                if voices >= max {
+                    let Some((stolen_channel, stolen_key)) = self.held_notes.steal() else {
+                        self.dropped_notes[channel][key] += 1;
+                        return true;
+                    };
+                    self.dropped_notes[stolen_channel][stolen_key] += 1;
+                    let note_off = 0x80 | stolen_channel as u32 | (stolen_key as u32) << 8;
+                    if let Some(event_log) = self.event_log.as_mut() {
+                        event_log.push(note_off);
+                    }
+                    self.send_to_synth(note_off);
+                }
+                self.held_notes.note_on(channel, key, velocity);
+                false
+            }
+            0x80 | 0x90 if self.dropped_notes[channel][key] > 0 => {
+                self.dropped_notes[channel][key] -= 1;
+                true
+            }
+            0x80 | 0x90 => {
+                self.held_notes.note_off(channel, key);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Sends an event without keeping track of it
+    fn send_to_synth(&mut self, data: u32) {
+        if let Some(fade) = self.fade.as_ref() {
+            if fade.hold_back(data) {
+                return;
+            }
+        }
+        self.send_direct(data);
+    }
+
+    fn send_direct(&mut self, data: u32) {
         match self.player_type {
             AudioPlayerType::XSynth { .. } => {
                 if let Some(xsynth) = self.xsynth.as_mut() {
@@ -83,24 +555,88 @@ impl SimpleTemporaryPlayer {
                 }
             }
             AudioPlayerType::Kdmapi => {
-                if let Some(kdmapi) = self.kdmapi.as_mut() {
-                    kdmapi.send_direct_data(data);
+                if let Some(kdmapi) = self.kdmapi.as_ref() {
+                    kdmapi.lock().unwrap().send_direct_data(data);
                 }
             }
         }
     }
 
+    /// Starts turning the channel volumes down before the synth gets reset, so the
+    /// playing voices don't stop with a click. Returns whether the fade resets it.
+    /// KDMAPI doesn't report its voices, so it's only faded while notes are held.
+    fn start_fade(&mut self) -> bool {
+        if self.fade_ms <= 0.0 {
+            return false;
+        }
+        let output: Box<dyn FadeOutput> = match (self.xsynth.as_ref(), self.kdmapi.as_ref()) {
+            (Some(xsynth), _) if xsynth.get_voice_count() > 0 => xsynth.fade_output(),
+            (None, Some(kdmapi)) if self.key_velocities.iter().flatten().any(|&v| v > 0) => {
+                Box::new(kdmapi.clone())
+            }
+            _ => return false,
+        };
+
+        let volumes = self
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, channel)| channel.used)
+            .map(|(i, channel)| (i as u32, channel.volume.unwrap_or(DEFAULT_CHANNEL_VOLUME)))
+            .collect();
+        self.fade.get_or_insert_with(FadeWorker::new).start(
+            output,
+            volumes,
+            Duration::from_secs_f64(self.fade_ms / 1000.0),
+        );
+        true
+    }
+
+    /// How many times the synth was reset so far
+    pub fn reset_count(&self) -> u64 {
+        self.resets
+    }
+
     pub fn reset(&mut self) {
-        match self.player_type {
-            AudioPlayerType::XSynth { .. } => {
-                if let Some(xsynth) = self.xsynth.as_mut() {
-                    xsynth.reset();
+        self.resets += 1;
+        // A fade that's still running resets the synth once it's over
+        let fading = self.fade.as_ref().is_some_and(|fade| fade.take_reset()) || self.start_fade();
+        self.pitch_bends = [0.0; 16];
+        self.key_velocities = [[0; 128]; 16];
+        self.controllers = [[None; 128]; 16];
+        self.last_controllers = [None; 128];
+        self.dropped_notes = [[0; 128]; 16];
+        self.held_notes.clear();
+
+        if !fading {
+            match self.player_type {
+                AudioPlayerType::XSynth { .. } => {
+                    if let Some(xsynth) = self.xsynth.as_mut() {
+                        xsynth.reset();
+                    }
+                }
+                AudioPlayerType::Kdmapi => {
+                    if let Some(kdmapi) = self.kdmapi.as_ref() {
+                        kdmapi.lock().unwrap().reset();
+                    }
                 }
             }
-            AudioPlayerType::Kdmapi => {
-                if let Some(kdmapi) = self.kdmapi.as_mut() {
-                    kdmapi.reset();
+        }
+        self.send_forced_program();
+    }
+
+    /// Resets the synth and sends "all sound off" and "all notes off"
+    /// on every channel, to get rid of any stuck notes
+    pub fn panic(&mut self) {
+        self.reset();
+        // Sent past the channel tracking, so the channels don't show up as used
+        for channel in 0..16 {
+            for controller in [120, 123] {
+                let data = 0xB0 | channel | (controller << 8);
+                if let Some(event_log) = self.event_log.as_mut() {
+                    event_log.push(data);
                 }
+                self.send_to_synth(data);
             }
         }
     }
@@ -116,8 +652,62 @@ impl SimpleTemporaryPlayer {
     pub fn set_soundfont(&mut self, path: &str, options: SoundfontInitOptions) {
         if let AudioPlayerType::XSynth { .. } = self.player_type {
             if let Some(xsynth) = self.xsynth.as_mut() {
-                xsynth.set_soundfont(path, options);
+                let result = xsynth.set_soundfont(path, options);
+                if let Err(err) = &result {
+                    log::error!("{err}");
+                }
+                self.soundfont_error = result.err();
             }
         }
     }
+
+    /// The audio format that soundfonts have to be loaded for, while XSynth is playing
+    pub fn soundfont_stream_params(&self) -> Option<AudioStreamParams> {
+        self.xsynth.as_ref().map(|xsynth| xsynth.stream_params())
+    }
+
+    /// Switches to a soundfont that was loaded again on another thread with
+    /// [`xsynth::load_soundfont`]. If it failed to load, the old one keeps playing.
+    pub fn use_reloaded_soundfont(
+        &mut self,
+        path: &str,
+        soundfont: Result<Arc<dyn SoundfontBase>, String>,
+    ) -> Result<(), String> {
+        let Some(xsynth) = self.xsynth.as_mut() else {
+            return Err("The soundfont can only be reloaded while XSynth is playing".to_string());
+        };
+        let soundfont = soundfont.map_err(|err| {
+            log::error!("{err}");
+            err
+        })?;
+        xsynth.use_reloaded_soundfont(path, soundfont);
+        self.soundfont_error = None;
+        Ok(())
+    }
+
+    /// Loads the soundfonts of the cycle list ahead of time, and unloads
+    /// the ones that were taken out of it
+    pub fn preload_soundfonts(&mut self, settings: &WasabiSettings) {
+        if let Some(xsynth) = self.xsynth.as_mut() {
+            let paths = &settings.synth.soundfont_cycle;
+            xsynth.retain_preloaded(paths);
+            for path in paths {
+                if let Err(err) =
+                    xsynth.preload_soundfont(path, xsynth::convert_to_sf_init(settings))
+                {
+                    log::error!("{err}");
+                }
+            }
+        }
+    }
+
+    /// Why XSynth is silent because it has no soundfont, if it is
+    pub fn soundfont_error(&self) -> Option<&str> {
+        self.soundfont_error.as_deref()
+    }
+
+    /// Why XSynth couldn't be opened the last time it was, returned only once
+    pub fn take_device_error(&mut self) -> Option<String> {
+        self.device_error.take()
+    }
 }
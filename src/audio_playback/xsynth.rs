@@ -1,11 +1,15 @@
 use std::{
+    env,
     ops::{Deref, DerefMut, RangeInclusive},
-    path::Path,
-    sync::Arc,
+    sync::{Arc, OnceLock},
+    thread,
 };
 
 use crate::WasabiSettings;
 
+use super::fade::FadeOutput;
+
+use cpal::traits::{DeviceTrait, HostTrait};
 use xsynth_core::{
     channel::{ChannelConfigEvent, ChannelInitOptions},
     soundfont::{SampleSoundfont, SoundfontBase, SoundfontInitOptions},
@@ -15,6 +19,38 @@ use xsynth_realtime::{
     config::XSynthRealtimeConfig, RealtimeEventSender, RealtimeSynth, RealtimeSynthStatsReader,
 };
 
+pub const NO_SOUNDFONT: &str = "No soundfont is set";
+
+const RAYON_THREADS_VAR: &str = "RAYON_NUM_THREADS";
+
+/// The render thread count that XSynth was set up with when Wasabi started
+static RENDER_THREADS: OnceLock<Option<usize>> = OnceLock::new();
+
+/// Sets how many threads XSynth's threadpool renders with. XSynth builds its threadpool
+/// with rayon's defaults, which only take a thread count from the environment, so this
+/// has to be called before any other thread exists. Rayon's global pool is built first
+/// so that the rest of Wasabi keeps one thread per core. Only the first call counts.
+pub fn init_render_threads(threads: Option<usize>) {
+    if RENDER_THREADS.set(threads).is_err() {
+        return;
+    }
+    if let Some(threads) = threads {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        if let Err(err) = rayon::ThreadPoolBuilder::new()
+            .num_threads(cores)
+            .build_global()
+        {
+            log::warn!("Failed to set up the rayon thread pool: {err}");
+        }
+        env::set_var(RAYON_THREADS_VAR, threads.to_string());
+    }
+}
+
+/// The render thread count that the synth runs with, one per CPU core if not set
+pub fn render_threads() -> Option<usize> {
+    RENDER_THREADS.get().copied().flatten()
+}
+
 #[repr(transparent)]
 struct FuckYouImSend<T>(T);
 
@@ -35,20 +71,36 @@ impl<T> DerefMut for FuckYouImSend<T> {
     }
 }
 
+impl FadeOutput for RealtimeEventSender {
+    fn send(&mut self, data: u32) {
+        self.send_event_u32(data);
+    }
+
+    fn reset(&mut self) {
+        self.reset_synth();
+    }
+}
+
 pub struct XSynthPlayer {
     sender: RealtimeEventSender,
     pub stats: RealtimeSynthStatsReader,
+    pub output_device: String,
     stream_params: AudioStreamParams,
+    /// Soundfonts that were loaded ahead of time, so they can be switched to right away
+    preloaded: Vec<(String, Arc<dyn SoundfontBase>)>,
     _synth: FuckYouImSend<RealtimeSynth>,
 }
 
 impl XSynthPlayer {
+    /// Opens XSynth on the audio device, failing if there's no device to play through
     pub fn new(
         buffer: f64,
         use_threadpool: bool,
         ignore_range: RangeInclusive<u8>,
         options: ChannelInitOptions,
-    ) -> Self {
+        output_device: Option<&str>,
+        sample_rate: Option<u32>,
+    ) -> Result<Self, String> {
         let config = XSynthRealtimeConfig {
             render_window_ms: buffer,
             use_threadpool,
@@ -57,17 +109,68 @@ impl XSynthPlayer {
             ..Default::default()
         };
 
-        let synth = FuckYouImSend(RealtimeSynth::open_with_default_output(config));
+        let host = cpal::default_host();
+        let device = output_device
+            .and_then(|name| {
+                let device = host
+                    .output_devices()
+                    .ok()?
+                    .find(|device| device.name().map(|n| n == name).unwrap_or(false));
+                if device.is_none() {
+                    log::warn!("Audio device {name} not found, using the default output instead");
+                }
+                device
+            })
+            .or_else(|| host.default_output_device())
+            .ok_or("No audio output device was found")?;
+        let default_config = device.default_output_config().map_err(|err| {
+            let name = device.name().unwrap_or_default();
+            format!("Failed to open the audio device {name}: {err}")
+        })?;
+        // XSynth renders at the rate the stream is opened at and can't resample, so
+        // a rate that the device doesn't take falls back to the device default
+        let stream_config = sample_rate
+            .and_then(|rate| {
+                let config = device
+                    .supported_output_configs()
+                    .ok()
+                    .and_then(|mut configs| {
+                        configs
+                            .filter(|c| c.channels() == default_config.channels())
+                            .filter(|c| c.sample_format() == default_config.sample_format())
+                            .find(|c| {
+                                (c.min_sample_rate().0..=c.max_sample_rate().0).contains(&rate)
+                            })
+                    });
+                if config.is_none() {
+                    log::warn!(
+                        "Sample rate {rate} Hz is not supported by the audio device, \
+                        using its default of {} Hz instead",
+                        default_config.sample_rate().0
+                    );
+                }
+                config.map(|c| c.with_sample_rate(cpal::SampleRate(rate)))
+            })
+            .unwrap_or(default_config);
+        let output_device = device.name().unwrap_or_default();
+        log::info!(
+            "Playing through {output_device} at {} Hz",
+            stream_config.sample_rate().0
+        );
+
+        let synth = FuckYouImSend(RealtimeSynth::open(config, &device, stream_config));
         let sender = synth.get_senders();
         let stream_params = synth.stream_params();
         let stats = synth.get_stats();
 
-        XSynthPlayer {
+        Ok(XSynthPlayer {
             sender,
             stats,
+            output_device,
             stream_params,
+            preloaded: Vec::new(),
             _synth: synth,
-        }
+        })
     }
 
     pub fn get_voice_count(&self) -> u64 {
@@ -82,20 +185,96 @@ impl XSynthPlayer {
         self.sender.reset_synth();
     }
 
+    /// Something for the fade thread to turn the synth down and reset it with
+    pub fn fade_output(&self) -> Box<dyn FadeOutput> {
+        Box::new(self.sender.clone())
+    }
+
     pub fn set_layer_count(&mut self, layers: Option<usize>) {
         self.sender
             .send_config(ChannelConfigEvent::SetLayerCount(layers));
     }
 
-    pub fn set_soundfont(&mut self, path: &str, options: SoundfontInitOptions) {
-        if !path.is_empty() && Path::new(path).exists() {
-            let samplesf = SampleSoundfont::new(path, self.stream_params, options);
-            if let Ok(sf) = samplesf {
-                let soundfont: Arc<dyn SoundfontBase> = Arc::new(sf);
-                self.sender
-                    .send_config(ChannelConfigEvent::SetSoundfonts(vec![soundfont]));
+    /// Loads the soundfont, or returns why it couldn't be loaded
+    pub fn set_soundfont(
+        &mut self,
+        path: &str,
+        options: SoundfontInitOptions,
+    ) -> Result<(), String> {
+        let preloaded = self.preloaded.iter().find(|(p, _)| p == path);
+        let soundfont = match preloaded {
+            Some((_, soundfont)) => soundfont.clone(),
+            None => load_soundfont(path, self.stream_params, options)?,
+        };
+        self.sender
+            .send_config(ChannelConfigEvent::SetSoundfonts(vec![soundfont]));
+        Ok(())
+    }
+
+    /// Loads the soundfont without using it yet, so that it can be switched to instantly
+    pub fn preload_soundfont(
+        &mut self,
+        path: &str,
+        options: SoundfontInitOptions,
+    ) -> Result<(), String> {
+        if self.preloaded.iter().any(|(p, _)| p == path) {
+            return Ok(());
+        }
+
+        let soundfont = load_soundfont(path, self.stream_params, options)?;
+        self.preloaded.push((path.to_owned(), soundfont));
+        Ok(())
+    }
+
+    /// Switches to a soundfont that was loaded from its file again, replacing the
+    /// preloaded copy of it. The voices of the old soundfont are cut off.
+    pub fn use_reloaded_soundfont(&mut self, path: &str, soundfont: Arc<dyn SoundfontBase>) {
+        for (preloaded_path, preloaded) in self.preloaded.iter_mut() {
+            if preloaded_path == path {
+                *preloaded = soundfont.clone();
             }
         }
+        self.sender
+            .send_config(ChannelConfigEvent::SetSoundfonts(vec![soundfont]));
+        self.reset();
+    }
+
+    /// The audio format that the soundfonts have to be loaded for
+    pub fn stream_params(&self) -> AudioStreamParams {
+        self.stream_params
+    }
+
+    /// Unloads the preloaded soundfonts that aren't in the list
+    pub fn retain_preloaded(&mut self, paths: &[String]) {
+        self.preloaded.retain(|(path, _)| paths.contains(path));
+    }
+}
+
+/// Loads the soundfont, or returns why it couldn't be loaded. This doesn't need
+/// the synth, so big soundfonts can be loaded on another thread.
+pub fn load_soundfont(
+    path: &str,
+    stream_params: AudioStreamParams,
+    options: SoundfontInitOptions,
+) -> Result<Arc<dyn SoundfontBase>, String> {
+    if path.is_empty() {
+        return Err(NO_SOUNDFONT.to_string());
+    }
+    let resolved = WasabiSettings::resolve_path(path, WasabiSettings::config_dir());
+    if !resolved.exists() {
+        return Err(format!("The soundfont {path} doesn't exist"));
+    }
+
+    let soundfont = SampleSoundfont::new(resolved, stream_params, options)
+        .map_err(|err| format!("Failed to load the soundfont {path}: {err:?}"))?;
+    Ok(Arc::new(soundfont))
+}
+
+pub fn list_output_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(_) => Vec::new(),
     }
 }
 
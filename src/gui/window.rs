@@ -1,80 +1,254 @@
+mod autosave;
+mod background;
+mod cc_effects;
+mod channel_leds;
+mod channels;
+mod download;
+mod drums;
+mod event_log;
 mod fps;
 mod keyboard;
 mod keyboard_layout;
+mod note_hover;
+mod peaks;
+mod playlist;
 mod scene;
+pub mod shortcuts;
+mod sleep_inhibit;
 mod stats;
+mod toast;
+mod tracks;
 
 mod settings_window;
 mod top_panel;
 mod xsynth_settings;
 
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
-    time::Duration,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use egui::{style::Margin, Frame, Visuals};
+use directories::UserDirs;
+
+use egui::{panel::TopBottomSide, style::Margin, Frame, Visuals};
+use vulkano::image::ImageViewAbstract;
+use winit::window::Theme;
 
 use crate::{
     audio_playback::{
-        xsynth::{convert_to_channel_init, convert_to_sf_init},
-        AudioPlayerType, SimpleTemporaryPlayer,
+        midi_input::LiveMidiInput, xsynth::convert_to_sf_init, SimpleTemporaryPlayer,
+    },
+    gui::window::{
+        background::GuiBackground,
+        keyboard::GuiKeyboard,
+        scene::{GuiRenderScene, NoteStyle},
+        toast::{GuiToasts, ToastLevel},
+    },
+    midi::{export_midi_summary, is_stdin_path, is_url, MIDIColor, MIDIFileBase, MIDIFileUnion},
+    renderer::screenshot::CapturedFrame,
+    settings::{
+        colors::ColorAssignments, keybindings::ShortcutAction, recent, ColorPalette, MidiSettings,
+        Synth, UiTheme, WasabiSettings,
     },
-    gui::window::{keyboard::GuiKeyboard, scene::GuiRenderScene},
-    midi::{CakeMIDIFile, InRamMIDIFile, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion},
-    settings::{MidiLoading, Synth, WasabiSettings},
-    state::WasabiState,
+    state::{HiddenWindows, WasabiState},
     GuiRenderer, GuiState,
 };
 
+/// How many seconds before the last note off jumping to the end seeks to
+const END_JUMP_LEAD: f64 = 3.0;
+
+/// Shown when seeking backwards while the MIDI is loaded live, which only reads forward
+const LIVE_SEEK_MESSAGE: &str =
+    "Live loading can't seek backwards, switch to RAM loading to rewind";
+
+/// Shown when looping a MIDI that was live loaded from stdin
+const STDIN_LOOP_MESSAGE: &str =
+    "A MIDI read from stdin can't be looped with live loading, use RAM loading instead";
+
+/// How often holding fast forward or rewind seeks, each seek resets the synth
+const SCRUB_SEEK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many keys the notes of a fully panned channel are moved sideways by
+const PAN_NOTE_SHIFT: f32 = 0.3;
+
+/// The extra keys shown on each side of the active keys, when following them
+const FOLLOW_PADDING_KEYS: f32 = 4.0;
+
+/// The fewest keys shown when following the active keys
+const MIN_FOLLOWED_KEYS: f32 = 24.0;
+
+/// How quickly the followed key range catches up with the active keys
+const FOLLOW_SPEED: f32 = 3.0;
+
+/// Hides all the panels and windows for a clean recording, or brings back
+/// the ones that were open before they were hidden
+fn toggle_compact_mode(state: &mut WasabiState) {
+    state.compact_mode = !state.compact_mode;
+
+    if state.compact_mode {
+        state.hidden_windows = HiddenWindows {
+            settings: std::mem::take(&mut state.settings_visible),
+            xsynth_settings: std::mem::take(&mut state.xsynth_settings_visible),
+            tracks: std::mem::take(&mut state.tracks_visible),
+            playlist: std::mem::take(&mut state.playlist_visible),
+            event_log: std::mem::take(&mut state.event_log_visible),
+        };
+    } else {
+        let hidden = state.hidden_windows;
+        state.settings_visible = hidden.settings;
+        state.xsynth_settings_visible = hidden.xsynth_settings;
+        state.tracks_visible = hidden.tracks;
+        state.playlist_visible = hidden.playlist;
+        state.event_log_visible = hidden.event_log;
+    }
+}
+
+/// The part of the note panel that the notes are drawn in, and the seconds it shows.
+/// A lookahead shorter than the note speed cuts the waterfall off before the top of
+/// the panel, keeping the notes as long as they would be over the whole panel.
+fn lookahead_rect(
+    notes_rect: egui::Rect,
+    note_speed: f64,
+    lookahead: Option<f64>,
+    notes_fall_down: bool,
+) -> (egui::Rect, f64) {
+    let Some(lookahead) = lookahead.filter(|&l| l < note_speed) else {
+        return (notes_rect, note_speed);
+    };
+
+    let height = notes_rect.height() * (lookahead / note_speed) as f32;
+    let rect = if notes_fall_down {
+        egui::Rect::from_min_max(
+            egui::pos2(notes_rect.min.x, notes_rect.max.y - height),
+            notes_rect.max,
+        )
+    } else {
+        egui::Rect::from_min_size(notes_rect.min, egui::vec2(notes_rect.width(), height))
+    };
+    (rect, lookahead)
+}
+
+/// The palette of the MIDI layer at the index, so its notes stand out from the
+/// main MIDI and the other layers. Only repeats after every other palette was used.
+fn layer_palette(palette: ColorPalette, index: usize) -> ColorPalette {
+    let palettes = [
+        ColorPalette::Grayscale,
+        ColorPalette::Pastel,
+        ColorPalette::Colorblind,
+        ColorPalette::Default,
+    ]
+    .into_iter()
+    .filter(|p| *p != palette)
+    .collect::<Vec<_>>();
+    palettes[index % palettes.len()]
+}
+
 pub struct GuiWasabiWindow {
     render_scene: GuiRenderScene,
+    background: GuiBackground,
     keyboard_layout: keyboard_layout::KeyboardLayout,
     keyboard: GuiKeyboard,
     midi_file: Option<MIDIFileUnion>,
+    /// MIDIs that play on top of the main one, following its timer
+    layers: Vec<MIDIFileUnion>,
+    layer_scenes: Vec<GuiRenderScene>,
+    /// Draws the frames of `--frames-out`, kept apart so its image size doesn't
+    /// take turns with the window's
+    offscreen_scene: Option<GuiRenderScene>,
     synth: Arc<RwLock<SimpleTemporaryPlayer>>,
     fps: fps::Fps,
+    peaks: peaks::GuiPeaks,
+    /// The eased key range, while following the active keys
+    followed_keys: Option<(f32, f32)>,
+    toasts: GuiToasts,
+    playlist: Vec<String>,
+    playlist_index: Option<usize>,
+    /// Whether the end of the MIDI was already handled, so the playlist only advances
+    /// once when it ends, even if there's no next MIDI to go to
+    end_handled: bool,
+    midi_input: Option<LiveMidiInput>,
+    sleep_inhibitor: sleep_inhibit::SleepInhibitor,
+    channel_leds: channel_leds::ChannelLeds,
+    autosave: autosave::SettingsAutosave,
+    validation: Option<tracks::MIDIValidationState>,
+    /// The MIDI being downloaded, which gets loaded once it's done
+    download: Option<download::MIDIDownload>,
+    /// Ends once the soundfont being reloaded in the background is done
+    soundfont_reload: Option<crossbeam_channel::Receiver<()>>,
+    /// The colors from the color file, given to the main MIDI on top of the palette
+    color_assignments: ColorAssignments,
+    /// Where the seek bar is being dragged to, when seeking waits for it to be let go
+    seek_preview: Option<f64>,
+    /// When fast forward or rewind last seeked, while one of them is held
+    last_scrub_seek: Option<Instant>,
+    /// How many times the MIDI has restarted, for `--loop-count`. Starts over
+    /// when another MIDI is loaded or looping is turned back on.
+    loops_played: u32,
+    /// The note last found under the cursor, for the note tooltips
+    note_hover: note_hover::NoteHover,
 }
 
 impl GuiWasabiWindow {
     pub fn new(renderer: &mut GuiRenderer, settings: &mut WasabiSettings) -> GuiWasabiWindow {
-        let synth = match settings.synth.synth {
-            Synth::Kdmapi => Arc::new(RwLock::new(SimpleTemporaryPlayer::new(
-                AudioPlayerType::Kdmapi,
-            ))),
-            Synth::XSynth => {
-                let synth = Arc::new(RwLock::new(SimpleTemporaryPlayer::new(
-                    AudioPlayerType::XSynth {
-                        buffer: settings.synth.buffer_ms,
-                        use_threadpool: settings.synth.use_threadpool,
-                        ignore_range: settings.synth.vel_ignore.clone(),
-                        options: convert_to_channel_init(settings),
-                    },
-                )));
-                synth
-                    .write()
-                    .unwrap()
-                    .set_soundfont(&settings.synth.sfz_path, convert_to_sf_init(settings));
-                synth
-                    .write()
-                    .unwrap()
-                    .set_layer_count(match settings.synth.layer_count {
-                        0 => None,
-                        _ => Some(settings.synth.layer_count),
-                    });
-                synth
-            }
+        let synth = Arc::new(RwLock::new(SimpleTemporaryPlayer::from_settings(settings)));
+
+        let midi_input = settings.midi_input.as_ref().and_then(|port| {
+            LiveMidiInput::connect(port, synth.clone())
+                .map_err(|err| log::error!("Failed to open the MIDI input: {err}"))
+                .ok()
+        });
+
+        let color_assignments = match settings.midi.color_file.as_ref() {
+            Some(path) => ColorAssignments::load(Path::new(path)).unwrap_or_else(|err| {
+                log::error!("Failed to load the colors from {path}: {err}");
+                Default::default()
+            }),
+            None => Default::default(),
         };
 
-        GuiWasabiWindow {
+        let mut window = GuiWasabiWindow {
             render_scene: GuiRenderScene::new(renderer),
+            background: GuiBackground::new(),
             keyboard_layout: keyboard_layout::KeyboardLayout::new(&Default::default()),
             keyboard: GuiKeyboard::new(),
             midi_file: None,
+            layers: Vec::new(),
+            layer_scenes: Vec::new(),
+            offscreen_scene: None,
             synth,
             fps: fps::Fps::new(),
+            peaks: peaks::GuiPeaks::new(),
+            followed_keys: None,
+            toasts: GuiToasts::new(),
+            playlist: settings.midi.playlist.clone(),
+            playlist_index: None,
+            end_handled: false,
+            midi_input,
+            sleep_inhibitor: sleep_inhibit::SleepInhibitor::new(),
+            channel_leds: channel_leds::ChannelLeds::new(),
+            autosave: autosave::SettingsAutosave::new(),
+            validation: None,
+            download: None,
+            soundfont_reload: None,
+            color_assignments,
+            seek_preview: None,
+            last_scrub_seek: None,
+            loops_played: 0,
+            note_hover: Default::default(),
+        };
+
+        if let Some(err) = window.synth.write().unwrap().take_device_error() {
+            window.push_toast(err, ToastLevel::Error);
+        }
+
+        if let Some(midi_path) = settings.load_midi_file.clone() {
+            window.load_midi(settings, PathBuf::from(midi_path));
         }
+        settings.start_paused = false;
+
+        window
     }
 
     /// Defines the layout of our UI
@@ -86,7 +260,55 @@ impl GuiWasabiWindow {
     ) {
         let ctx = state.renderer.gui.context();
         self.fps.update();
-        ctx.set_visuals(Visuals::dark());
+
+        if let Some(result) = self.download.as_mut().and_then(|download| download.poll()) {
+            let start_paused = self.download.take().map_or(false, |d| d.start_paused());
+            match result {
+                Ok(path) => self.open_midi(settings, &path.to_string_lossy(), start_paused),
+                Err(err) => {
+                    let message = format!("Failed to download MIDI: {err}");
+                    log::error!("{message}");
+                    self.push_toast(message, ToastLevel::Error);
+                }
+            }
+        }
+
+        let finished = self.is_finished();
+        if wasabi_state.loop_playback {
+            self.loop_if_finished(settings, wasabi_state);
+        } else if self.playlist_index.is_some() && finished && !self.end_handled {
+            self.end_handled = true;
+            self.play_next(settings);
+        }
+        if !finished {
+            self.end_handled = false;
+        }
+        let dark = match settings.visual.theme {
+            UiTheme::Dark => true,
+            UiTheme::Light => false,
+            UiTheme::System => wasabi_state.system_theme != Some(Theme::Light),
+        };
+        ctx.set_visuals(if dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        });
+
+        // While the shortcut list is open, the next key press only closes it
+        let actions = if wasabi_state.shortcuts_visible {
+            if shortcuts::any_key_pressed(&ctx) {
+                wasabi_state.shortcuts_visible = false;
+            }
+            Vec::new()
+        } else if wasabi_state.settings_visible && wasabi_state.rebinding_shortcut.is_some() {
+            // The key press goes to the keybinding being edited in the settings
+            Vec::new()
+        } else {
+            shortcuts::pressed_actions(&ctx, &settings.keybindings)
+        };
+        for action in actions {
+            self.handle_shortcut(action, settings, wasabi_state);
+        }
 
         if wasabi_state.settings_visible {
             settings_window::draw_settings(self, settings, wasabi_state, &ctx);
@@ -94,9 +316,36 @@ impl GuiWasabiWindow {
         if wasabi_state.xsynth_settings_visible {
             xsynth_settings::draw_xsynth_settings(self, settings, wasabi_state, &ctx);
         }
+        if wasabi_state.tracks_visible {
+            tracks::draw_tracks(self, wasabi_state, &ctx);
+        }
+        if wasabi_state.playlist_visible {
+            playlist::draw_playlist(self, settings, wasabi_state, &ctx);
+        }
+        if wasabi_state.event_log_visible {
+            event_log::draw_event_log(self, wasabi_state, &ctx);
+        }
+
+        // The synth only keeps the events while they can be seen
+        if self.synth.read().unwrap().is_logging_events() != wasabi_state.event_log_visible {
+            self.synth
+                .write()
+                .unwrap()
+                .set_event_logging(wasabi_state.event_log_visible);
+        }
+
+        // The timer keeps running after the end, but nothing is playing anymore
+        let playing = self
+            .midi_file
+            .as_ref()
+            .map_or(false, |midi_file| !midi_file.timer().is_paused())
+            && !finished;
+        self.sleep_inhibitor
+            .update(settings.visual.inhibit_sleep && playing);
+        self.autosave.update(settings);
 
         let height_prev = ctx.available_rect().height();
-        if settings.visual.show_top_pannel {
+        if settings.visual.show_top_pannel && !wasabi_state.compact_mode {
             top_panel::draw_panel(self, settings, wasabi_state, &ctx);
         }
 
@@ -106,129 +355,339 @@ impl GuiWasabiWindow {
         let available = ctx.available_rect();
         let height = available.height();
         let panel_height = height_prev - height;
-        let keyboard_height =
-            (11.6 / settings.midi.key_range.len() as f32 * available.width()).min(height / 2.0);
+        let key_width = available.width() / settings.midi.key_range.len() as f32;
+        let keyboard_height = (settings.visual.keyboard_height_ratio * key_width).min(height / 2.0);
+        let keyboard_height = settings
+            .visual
+            .max_keyboard_height
+            .map_or(keyboard_height, |max| keyboard_height.min(max));
         let notes_height = height - keyboard_height;
 
-        let key_view = self.keyboard_layout.get_view_for_keys(
-            *settings.midi.key_range.start() as usize,
-            *settings.midi.key_range.end() as usize,
-        );
+        let dt = ctx.input(|input| input.stable_dt);
+        let (first_key, last_key) = self.visible_key_range(settings, dt);
+        let key_view = self.keyboard_layout.get_view_for_keys(first_key, last_key);
+
+        let cc_effects =
+            cc_effects::CCEffects::new(&self.synth.read().unwrap(), &settings.visual.cc_mappings);
 
         let no_frame = Frame::default()
             .inner_margin(Margin::same(0.0))
-            .fill(settings.visual.bg_color);
+            .fill(cc_effects.background(settings.visual.bg_color));
 
         let mut stats = stats::GuiMidiStats::empty();
 
         let mut render_result_data = None;
 
+        self.background.update(&ctx, &settings.visual.bg_image);
+
+        // When the notes rise, they go below the keyboard and the rendered image is flipped
+        let notes_side = if settings.visual.notes_fall_down {
+            TopBottomSide::Top
+        } else {
+            TopBottomSide::Bottom
+        };
+
         // Render the notes
-        egui::TopBottomPanel::top("Note panel")
+        egui::TopBottomPanel::new(notes_side, "Note panel")
             .height_range(notes_height..=notes_height)
             .frame(no_frame)
             .show_separator_line(false)
             .show(&ctx, |ui| {
+                if let Some(bottom) = settings.visual.bg_color_bottom {
+                    self.background.draw_gradient(
+                        ui,
+                        cc_effects.background(settings.visual.bg_color),
+                        cc_effects.background(bottom),
+                    );
+                }
+                self.background.draw(ui, cc_effects.background_tint());
+
                 if let Some(midi_file) = self.midi_file.as_mut() {
-                    let one_sec = Duration::from_secs(1);
                     let time = midi_file.timer().get_time();
+                    let (scene_rect, view_range) = lookahead_rect(
+                        ui.available_rect_before_wrap(),
+                        settings.midi.note_speed,
+                        settings.midi.lookahead_seconds,
+                        settings.visual.notes_fall_down,
+                    );
+
+                    let mut pitch_bends = if settings.visual.visualize_pitch_bend {
+                        let range = settings.visual.pitch_bend_range as f32;
+                        self.synth
+                            .read()
+                            .unwrap()
+                            .pitch_bends()
+                            .map(|bend| bend * range)
+                    } else {
+                        [0.0; 16]
+                    };
 
-                    ui.input(|events| {
-                        for event in &events.events {
-                            if let egui::Event::Key { key, pressed, .. } = event {
-                                if pressed == &true {
-                                    match key {
-                                        egui::Key::ArrowRight => {
-                                            midi_file.timer_mut().seek(time + one_sec)
-                                        }
-                                        egui::Key::ArrowLeft => {
-                                            if midi_file.allows_seeking_backward() {
-                                                midi_file.timer_mut().seek(if time <= one_sec {
-                                                    Duration::from_secs(0)
-                                                } else {
-                                                    time - one_sec
-                                                })
-                                            }
-                                        }
-                                        egui::Key::ArrowUp => {
-                                            settings.midi.note_speed += 0.05;
-                                        }
-                                        egui::Key::ArrowDown => {
-                                            settings.midi.note_speed -= 0.05;
-                                        }
-                                        egui::Key::Space => midi_file.timer_mut().toggle_pause(),
-                                        _ => {}
-                                    }
-                                }
-                            }
+                    // Panned channels are nudged sideways, reusing the pitch bend offset
+                    if settings.visual.pan_notes {
+                        let channels = self.synth.read().unwrap().channels();
+                        for (bend, channel) in pitch_bends.iter_mut().zip(channels.iter()) {
+                            *bend += channel.pan_position() * PAN_NOTE_SHIFT;
                         }
-                    });
+                    }
 
-                    let result = self.render_scene.draw(
-                        state,
-                        ui,
-                        &key_view,
-                        midi_file,
-                        settings.midi.note_speed,
-                    );
-                    stats.set_rendered_note_count(result.notes_rendered);
+                    // Hold Page Up/Page Down (by default) to fast forward/rewind. Every seek resets
+                    // the synth, so no voices are left hanging while skipping through. The seeks
+                    // are spaced out, so the synth isn't reset on every frame.
+                    let bindings = &settings.keybindings;
+                    let forward = shortcuts::is_held(&ctx, ShortcutAction::FastForward, bindings);
+                    let backward = shortcuts::is_held(&ctx, ShortcutAction::Rewind, bindings);
+                    if backward && !midi_file.allows_seeking_backward() {
+                        self.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+                    }
+                    let backward = backward && midi_file.allows_seeking_backward();
+                    if forward != backward {
+                        let now = Instant::now();
+                        let last_seek = *self.last_scrub_seek.get_or_insert(now);
+                        let elapsed = now - last_seek;
+                        if elapsed >= SCRUB_SEEK_INTERVAL {
+                            let step = elapsed.as_secs_f64() * settings.midi.fast_forward_speed;
+                            let time = time.as_secs_f64();
+                            let new_time = if forward { time + step } else { time - step };
+                            midi_file
+                                .timer_mut()
+                                .seek(Duration::from_secs_f64(new_time.max(0.0)));
+                            self.last_scrub_seek = Some(now);
+                        }
+                    } else {
+                        self.last_scrub_seek = None;
+                    }
+
+                    let mut result = ui
+                        .allocate_ui_at_rect(scene_rect, |ui| {
+                            self.render_scene.draw(
+                                state,
+                                ui,
+                                &key_view,
+                                midi_file,
+                                view_range,
+                                cc_effects.note_style(&settings.visual),
+                                pitch_bends,
+                                settings.visual.max_rendered_notes,
+                                settings.midi.transpose,
+                                !settings.visual.notes_fall_down,
+                            )
+                        })
+                        .inner;
+
+                    // The layers are rendered separately and drawn over the main MIDI
+                    for (i, layer) in self.layers.iter_mut().enumerate() {
+                        layer.timer_mut().sync_to(midi_file.timer());
+
+                        if self.layer_scenes.len() <= i {
+                            self.layer_scenes.push(GuiRenderScene::new(state.renderer));
+                        }
+                        let layer_result = ui
+                            .allocate_ui_at_rect(scene_rect, |ui| {
+                                self.layer_scenes[i].draw(
+                                    state,
+                                    ui,
+                                    &key_view,
+                                    layer,
+                                    view_range,
+                                    cc_effects.note_style(&settings.visual),
+                                    pitch_bends,
+                                    settings.visual.max_rendered_notes,
+                                    settings.midi.transpose,
+                                    !settings.visual.notes_fall_down,
+                                )
+                            })
+                            .inner;
+
+                        result.notes_rendered += layer_result.notes_rendered;
+                        result.notes_capped |= layer_result.notes_capped;
+                        for (colors, layer_colors) in
+                            result.key_colors.iter_mut().zip(layer_result.key_colors)
+                        {
+                            colors.extend(layer_colors);
+                        }
+                    }
+
+                    if settings.visual.note_tooltips {
+                        self.note_hover.draw_tooltip(
+                            ui,
+                            midi_file,
+                            &key_view,
+                            scene_rect,
+                            view_range,
+                            settings.midi.transpose,
+                            settings.visual.notes_fall_down,
+                        );
+                    }
+
+                    stats.set_rendered_note_count(result.notes_rendered, result.notes_capped);
                     render_result_data = Some(result);
                 }
             });
 
+        let beat_flash = match &self.midi_file {
+            Some(midi_file) if settings.visual.beat_flash => {
+                let time = midi_file.timer().get_time().as_secs_f64();
+                keyboard::beat_flash(midi_file.beat_at(time))
+            }
+            _ => 0.0,
+        };
+
         // Render the keyboard
         egui::TopBottomPanel::top("Keyboard panel")
             .height_range(keyboard_height..=keyboard_height)
             .frame(no_frame)
             .show_separator_line(false)
             .show(&ctx, |ui| {
-                ui.input(|events| {
-                    for event in &events.events {
-                        if let egui::Event::Key {
-                            key,
-                            pressed,
-                            modifiers,
-                            ..
-                        } = event
-                        {
-                            if *pressed && modifiers.ctrl {
-                                match key {
-                                    egui::Key::F => {
-                                        settings.visual.show_top_pannel =
-                                            !settings.visual.show_top_pannel
-                                    }
-                                    egui::Key::G => {
-                                        settings.visual.show_statistics =
-                                            !settings.visual.show_statistics
-                                    }
-                                    //egui::Key::O => self.open_midi_dialog(wasabi_state),
-                                    _ => {}
-                                }
-                            }
-                            if *pressed && modifiers.alt && key == &egui::Key::Enter {
-                                wasabi_state.fullscreen = !wasabi_state.fullscreen
-                            }
-                        }
+                self.keyboard
+                    .handle_scroll(ui, &mut settings.midi.key_range);
+
+                let mut key_colors = render_result_data.map(|data| data.key_colors);
+                if let Some(midi_input) = &self.midi_input {
+                    let key_colors = key_colors.get_or_insert_with(|| vec![Vec::new(); 256]);
+                    midi_input.add_key_colors(key_colors, settings.midi.transpose);
+                }
+
+                if settings.visual.velocity_key_colors {
+                    if let Some(key_colors) = key_colors.as_mut() {
+                        let velocities = self.synth.read().unwrap().key_velocities();
+                        keyboard::scale_by_velocity(key_colors, &velocities);
                     }
-                });
+                }
 
-                let colors = if let Some(data) = render_result_data {
-                    data.key_colors
+                if let Some(key_colors) = key_colors {
+                    self.keyboard
+                        .draw(ui, &key_view, &key_colors, &settings.visual, beat_flash);
                 } else {
-                    vec![None; 256]
-                };
-
-                self.keyboard
-                    .draw(ui, &key_view, &colors, &settings.visual.bar_color);
+                    self.keyboard.draw_empty(ui, &key_view, &settings.visual);
+                }
             });
 
-        // Render the stats
-        if settings.visual.show_statistics {
-            let voice_count = self.synth.read().unwrap().get_voice_count();
-            stats.set_voice_count(voice_count);
+        if let Some(midi_file) = self.midi_file.as_ref() {
+            let time = midi_file.timer().get_time().as_secs_f64();
+            let voices = self.synth.read().unwrap().get_voice_count();
+            self.peaks
+                .update(time, midi_file.stats().passed_notes, voices);
+        }
+
+        xsynth_settings::draw_soundfont_warning(self, wasabi_state, &ctx, panel_height);
+
+        let screen = ctx.screen_rect();
+        let notes_top = if settings.visual.notes_fall_down {
+            panel_height
+        } else {
+            panel_height + keyboard_height
+        };
+        let notes_rect = egui::Rect::from_min_size(
+            egui::Pos2::new(screen.left(), notes_top),
+            egui::Vec2::new(screen.width(), notes_height),
+        );
+
+        // Render the stats, in a corner of the note area
+        if settings.visual.show_statistics && !wasabi_state.compact_mode {
+            let synth = self.synth.read().unwrap();
+            stats.set_voice_count(synth.get_voice_count(), synth.max_voices());
+            stats.set_output_device(synth.get_output_device().map(|d| d.to_owned()));
+            drop(synth);
+
+            stats::draw_stats(
+                self,
+                wasabi_state,
+                &ctx,
+                notes_rect,
+                stats,
+                &settings.visual,
+            );
+        }
+
+        // Render the drum strip, next to the keyboard
+        if settings.visual.separate_drums && self.midi_file.is_some() {
+            let hits = self.synth.read().unwrap().drum_hits();
+            drums::draw_drums(&ctx, notes_rect, settings.visual.notes_fall_down, &hits);
+        }
+
+        // Render the FPS counter, inside of the note area
+        if settings.visual.show_fps && !wasabi_state.compact_mode {
+            stats::draw_fps(&ctx, notes_rect, &self.fps, settings.visual.fps_corner);
+        }
+
+        // Render the channel list
+        if settings.visual.show_channels && !wasabi_state.compact_mode {
+            let channels = self.synth.read().unwrap().channels();
+            let colors = self.channel_colors();
+            channels::draw_channels(&ctx, panel_height, &channels, &colors);
+        }
+
+        // Render the channel activity lights
+        if settings.visual.show_channel_leds && !wasabi_state.compact_mode {
+            let active = self.synth.read().unwrap().active_channels();
+            let colors = self.channel_colors();
+            self.channel_leds.draw(&ctx, panel_height, active, &colors);
+        }
+
+        if wasabi_state.shortcuts_visible {
+            shortcuts::draw_shortcuts(&ctx, &settings.keybindings);
+        }
+
+        if let Some(download) = self.download.as_ref() {
+            download.draw(&ctx);
+        }
+
+        self.toasts.draw(&ctx);
+    }
 
-            let pos = egui::Pos2::new(10.0, panel_height + 10.0);
-            stats::draw_stats(self, &ctx, pos, stats);
+    fn handle_shortcut(
+        &mut self,
+        action: ShortcutAction,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        let one_sec = Duration::from_secs(1);
+
+        match action {
+            ShortcutAction::TogglePause => {
+                if let Some(midi_file) = self.midi_file.as_mut() {
+                    midi_file.timer_mut().toggle_pause();
+                }
+            }
+            ShortcutAction::Stop => self.stop(),
+            ShortcutAction::SkipForward => {
+                if let Some(midi_file) = self.midi_file.as_mut() {
+                    let time = midi_file.timer().get_time();
+                    midi_file.timer_mut().seek(time + one_sec);
+                }
+            }
+            ShortcutAction::SkipBackward => {
+                if let Some(midi_file) = self.midi_file.as_mut() {
+                    if midi_file.allows_seeking_backward() {
+                        let time = midi_file.timer().get_time();
+                        midi_file.timer_mut().seek(time.saturating_sub(one_sec));
+                    } else {
+                        self.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+                    }
+                }
+            }
+            ShortcutAction::JumpToStart => self.jump_to_start(),
+            ShortcutAction::JumpToEnd => self.jump_to_end(),
+            ShortcutAction::SlowerNotes => settings.midi.note_speed += 0.05,
+            ShortcutAction::FasterNotes => settings.midi.note_speed -= 0.05,
+            ShortcutAction::ToggleFullscreen => state.fullscreen = !state.fullscreen,
+            ShortcutAction::ToggleTopPanel => {
+                settings.visual.show_top_pannel = !settings.visual.show_top_pannel
+            }
+            ShortcutAction::ToggleStatistics => {
+                settings.visual.show_statistics = !settings.visual.show_statistics
+            }
+            ShortcutAction::ToggleFps => settings.visual.show_fps = !settings.visual.show_fps,
+            ShortcutAction::ToggleChannels => {
+                settings.visual.show_channels = !settings.visual.show_channels
+            }
+            ShortcutAction::ToggleCompactMode => toggle_compact_mode(state),
+            ShortcutAction::Panic => self.synth.write().unwrap().panic(),
+            ShortcutAction::Screenshot => state.take_screenshot = true,
+            ShortcutAction::CycleSoundfont => self.cycle_soundfont(settings),
+            ShortcutAction::ToggleShortcuts => state.shortcuts_visible = !state.shortcuts_visible,
+            // These are checked every frame while the key is held down
+            ShortcutAction::FastForward | ShortcutAction::Rewind => {}
         }
     }
 
@@ -236,7 +695,7 @@ impl GuiWasabiWindow {
     pub fn open_midi_dialog(&mut self, settings: &mut WasabiSettings, state: &mut WasabiState) {
         // If windows, just use the native dialog
         let midi_path = rfd::FileDialog::new()
-            .add_filter("mid", &["mid"])
+            .add_filter("midi", &["mid", "midi", "rmi", "kar"])
             .pick_file();
 
         if let Some(midi_path) = midi_path {
@@ -245,43 +704,482 @@ impl GuiWasabiWindow {
         }
     }
 
+    /// The keys to show, which narrow down to the keys that have notes on
+    /// screen while following the active keys
+    fn visible_key_range(&mut self, settings: &WasabiSettings, dt: f32) -> (usize, usize) {
+        let full = (
+            *settings.midi.key_range.start() as f32,
+            *settings.midi.key_range.end() as f32,
+        );
+        if !settings.midi.follow_active_keys {
+            self.followed_keys = None;
+            return (full.0 as usize, full.1 as usize);
+        }
+
+        let current = *self.followed_keys.get_or_insert(full);
+        let active = self.midi_file.as_ref().and_then(|f| f.active_key_range());
+        let target = active
+            .map(|range| {
+                let transpose = settings.midi.transpose as f32;
+                let first = *range.start() as f32 + transpose - FOLLOW_PADDING_KEYS;
+                let last = *range.end() as f32 + transpose + FOLLOW_PADDING_KEYS;
+
+                // Narrow ranges get widened around their center
+                let missing = (MIN_FOLLOWED_KEYS - (last - first)).max(0.0) / 2.0;
+                ((first - missing).max(full.0), (last + missing).min(full.1))
+            })
+            .filter(|(first, last)| first < last)
+            .unwrap_or(current);
+
+        // Ease towards the active keys, so the view doesn't jump around with every note
+        let t = 1.0 - (-dt * FOLLOW_SPEED).exp();
+        let first = current.0 + (target.0 - current.0) * t;
+        let last = current.1 + (target.1 - current.1) * t;
+        self.followed_keys = Some((first, last));
+
+        (first.round() as usize, last.round() as usize)
+    }
+
     pub fn load_midi(&mut self, settings: &mut WasabiSettings, midi_path: PathBuf) {
         if let Some(midi_file) = self.midi_file.as_mut() {
             midi_file.timer_mut().pause();
         }
-        self.synth.write().unwrap().reset();
+        let mut synth = self.synth.write().unwrap();
+        synth.reset();
+        synth.clear_channels();
+        drop(synth);
         self.midi_file = None;
+        self.layers.clear();
+        self.peaks.reset();
+        self.playlist_index = None;
+        self.validation = None;
+        self.download = None;
 
         if let Some(midi_path) = midi_path.to_str() {
-            match settings.midi.midi_loading {
-                MidiLoading::Ram => {
-                    let mut midi_file = MIDIFileUnion::InRam(InRamMIDIFile::load_from_file(
-                        midi_path,
-                        self.synth.clone(),
-                        settings.midi.random_colors,
-                    ));
-                    midi_file.timer_mut().play();
-                    self.midi_file = Some(midi_file);
+            if is_url(midi_path) {
+                let download =
+                    download::MIDIDownload::start(midi_path.to_owned(), settings.start_paused);
+                self.download = Some(download);
+            } else {
+                self.open_midi(settings, midi_path, settings.start_paused);
+            }
+        }
+    }
+
+    /// Whether a MIDI is being downloaded before it gets loaded
+    pub fn is_downloading(&self) -> bool {
+        self.download.is_some()
+    }
+
+    fn open_midi(&mut self, settings: &WasabiSettings, midi_path: &str, start_paused: bool) {
+        log::info!("Loading {midi_path}");
+        let midi_file = MIDIFileUnion::load_from_file(
+            midi_path,
+            settings.midi.midi_loading,
+            self.synth.clone(),
+            settings.midi.random_colors,
+            settings.midi.color_palette,
+            settings.midi.color_by,
+            &self.color_assignments,
+            settings.midi.tempo_override,
+        );
+
+        match midi_file {
+            Ok(mut midi_file) => {
+                // Stdin can't be read again, so it's no use in the recent MIDIs
+                if !is_stdin_path(midi_path) {
+                    recent::add_recent_midi(midi_path);
                 }
-                MidiLoading::Live => {
-                    let mut midi_file = MIDIFileUnion::Live(LiveLoadMIDIFile::load_from_file(
-                        midi_path,
-                        self.synth.clone(),
-                        settings.midi.random_colors,
-                    ));
-                    midi_file.timer_mut().play();
-                    self.midi_file = Some(midi_file);
+                if let Some(warning) = midi_file.format().warning() {
+                    log::warn!("{warning}");
+                    self.push_toast(warning, ToastLevel::Warning);
                 }
-                MidiLoading::Cake => {
-                    let mut midi_file = MIDIFileUnion::Cake(CakeMIDIFile::load_from_file(
-                        midi_path,
-                        self.synth.clone(),
-                        settings.midi.random_colors,
-                    ));
+                if !start_paused {
                     midi_file.timer_mut().play();
-                    self.midi_file = Some(midi_file);
                 }
+                self.midi_file = Some(midi_file);
+                self.loops_played = 0;
+                self.note_hover = Default::default();
+            }
+            Err(err) => {
+                let message = format!("Failed to load MIDI: {err}");
+                log::error!("{message}");
+                self.push_toast(message, ToastLevel::Error);
+            }
+        }
+    }
+
+    pub fn add_layer_dialog(&mut self, settings: &mut WasabiSettings) {
+        let midi_path = rfd::FileDialog::new()
+            .add_filter("midi", &["mid", "midi", "rmi", "kar"])
+            .pick_file();
+
+        if let Some(midi_path) = midi_path {
+            self.load_layer(settings, midi_path);
+        }
+    }
+
+    /// Loads a MIDI to play at the same time as the main one. Without a main
+    /// MIDI, it just gets loaded normally.
+    pub fn load_layer(&mut self, settings: &mut WasabiSettings, midi_path: PathBuf) {
+        let Some(midi_file) = self.midi_file.as_ref() else {
+            self.load_midi(settings, midi_path);
+            return;
+        };
+        let Some(path) = midi_path.to_str() else {
+            return;
+        };
+
+        // Every layer gets its own channels, so the layers don't change each other's instruments.
+        // The drum channel stays shared, so there are 15 channels to move around in
+        let offset = settings.midi.layer_channel_offset as usize * (self.layers.len() + 1);
+        let layer = MIDIFileUnion::load_layer_from_file(
+            path,
+            self.synth.clone(),
+            layer_palette(settings.midi.color_palette, self.layers.len()),
+            settings.midi.color_by,
+            settings.midi.tempo_override,
+            (offset % 15) as u8,
+        );
+
+        match layer {
+            Ok(mut layer) => {
+                layer.timer_mut().sync_to(midi_file.timer());
+                self.layers.push(layer);
+            }
+            Err(err) => {
+                let message = format!("Failed to load the MIDI layer: {err}");
+                self.push_toast(message, ToastLevel::Error);
+            }
+        }
+    }
+
+    pub fn clear_layers(&mut self) {
+        self.layers.clear();
+        self.synth.write().unwrap().reset();
+    }
+
+    pub fn jump_to_start(&mut self) {
+        if let Some(midi_file) = self.midi_file.as_mut() {
+            if midi_file.allows_seeking_backward() {
+                self.synth.write().unwrap().reset();
+                midi_file.timer_mut().seek(Duration::ZERO);
+            } else {
+                self.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+            }
+        }
+    }
+
+    /// Pauses and goes back to the start, unlike pausing which keeps the position
+    pub fn stop(&mut self) {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return;
+        };
+        let timer = midi_file.timer_mut();
+        if timer.is_paused() && timer.get_time().is_zero() {
+            return;
+        }
+
+        timer.pause();
+        self.synth.write().unwrap().reset();
+        if midi_file.allows_seeking_backward() {
+            midi_file.timer_mut().seek(Duration::ZERO);
+        } else {
+            self.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+        }
+    }
+
+    /// Shows a message at the bottom of the window for a few seconds
+    pub fn push_toast(&self, message: impl Into<String>, level: ToastLevel) {
+        self.toasts.push(message, level);
+    }
+
+    /// Switches XSynth to the next soundfont of the cycle list, wrapping around
+    pub fn cycle_soundfont(&mut self, settings: &mut WasabiSettings) {
+        let soundfonts = &settings.synth.soundfont_cycle;
+        let (message, level) = if settings.synth.synth != Synth::XSynth {
+            (
+                "Soundfonts can only be switched with XSynth".to_string(),
+                ToastLevel::Warning,
+            )
+        } else if soundfonts.is_empty() {
+            (
+                "Add soundfonts to cycle through in the XSynth settings".to_string(),
+                ToastLevel::Warning,
+            )
+        } else {
+            let next = soundfonts
+                .iter()
+                .position(|path| *path == settings.synth.sfz_path)
+                .map_or(0, |i| (i + 1) % soundfonts.len());
+            settings.synth.sfz_path = soundfonts[next].clone();
+
+            // The voices of the old soundfont would be cut off anyway
+            let mut synth = self.synth.write().unwrap();
+            synth.reset();
+            synth.set_soundfont(&settings.synth.sfz_path, convert_to_sf_init(settings));
+            match synth.soundfont_error() {
+                Some(err) => (err.to_owned(), ToastLevel::Error),
+                None => {
+                    let name = Path::new(&settings.synth.sfz_path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    (format!("Soundfont: {name}"), ToastLevel::Info)
+                }
+            }
+        };
+        self.push_toast(message, level);
+    }
+
+    /// Moves the paused MIDI to the time of the next frame of `--frames-out` without
+    /// seeking, so nothing gets reset or caught up on between the frames. Returns
+    /// false if there is no MIDI or it has ended
+    pub fn step_to(&mut self, time: Duration) -> bool {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return false;
+        };
+        if let Some(length) = midi_file.midi_length() {
+            if time.as_secs_f64() > length {
+                return false;
+            }
+        }
+
+        midi_file.timer_mut().set_time(time);
+        true
+    }
+
+    /// Draws the notes of the main MIDI into the image for `--frames-out`, over the
+    /// whole key range. The keyboard, the layers and the rest of the UI are left out.
+    pub fn draw_offscreen(
+        &mut self,
+        renderer: &GuiRenderer,
+        settings: &WasabiSettings,
+        image: Arc<dyn ImageViewAbstract + 'static>,
+    ) {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return;
+        };
+
+        let key_view = self.keyboard_layout.get_view_for_keys(
+            *settings.midi.key_range.start() as usize,
+            *settings.midi.key_range.end() as usize,
+        );
+        let scene = self
+            .offscreen_scene
+            .get_or_insert_with(|| GuiRenderScene::new(renderer));
+        scene.draw_to_image(
+            renderer,
+            image,
+            &key_view,
+            midi_file,
+            settings.midi.note_speed,
+            NoteStyle::new(&settings.visual),
+            [0.0; 16],
+            settings.visual.max_rendered_notes,
+            settings.midi.transpose,
+        );
+    }
+
+    /// Seeks to a little before the MIDI ends, so the ending can still be heard
+    pub fn jump_to_end(&mut self) {
+        if let Some(midi_file) = self.midi_file.as_mut() {
+            // Live loading keeps no summary, and only knows the length once the whole
+            // file is parsed, so it goes by the length instead of the last note off
+            let end = match midi_file.summary() {
+                Some(summary) => summary.last_note_off.unwrap_or(0.0),
+                None => match midi_file.midi_length() {
+                    Some(length) => length,
+                    None => return,
+                },
+            };
+
+            let time = Duration::from_secs_f64((end - END_JUMP_LEAD).max(0.0));
+            if time < midi_file.timer().get_time() && !midi_file.allows_seeking_backward() {
+                self.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+                return;
+            }
+
+            self.synth.write().unwrap().reset();
+            midi_file.timer_mut().seek(time);
+        }
+    }
+
+    /// Restarts the MIDI from the beginning once it has finished playing
+    fn loop_if_finished(&mut self, settings: &mut WasabiSettings, state: &mut WasabiState) {
+        if !self.is_finished() {
+            return;
+        }
+
+        // Stop looping once the MIDI has played as many times as `--loop-count` asks for
+        if let Some(count) = settings.loop_count.filter(|count| *count > 0) {
+            if self.loops_played + 1 >= count {
+                state.loop_playback = false;
+                return;
+            }
+        }
+        self.loops_played += 1;
+
+        if let Some(midi_file) = self.midi_file.as_mut() {
+            if midi_file.allows_seeking_backward() {
+                self.synth.write().unwrap().reset();
+                midi_file.timer_mut().seek(Duration::ZERO);
+            } else if is_stdin_path(&midi_file.signature().filepath) {
+                // Stdin was already read to the end, so there's nothing to load again
+                state.loop_playback = false;
+                self.toasts.push(STDIN_LOOP_MESSAGE, ToastLevel::Info);
+            } else {
+                // Live loading can't seek backwards, so load the file again instead
+                let midi_path = PathBuf::from(&midi_file.signature().filepath);
+                let playlist_index = self.playlist_index;
+                self.load_midi(settings, midi_path);
+                self.playlist_index = playlist_index;
+            }
+        }
+    }
+
+    /// The note colors of each channel in the first track that has notes, or the
+    /// colors the MIDI input uses when there's no MIDI
+    fn channel_colors(&self) -> Vec<MIDIColor> {
+        let Some(midi_file) = self.midi_file.as_ref() else {
+            return MIDIColor::new_vec_for_tracks(1);
+        };
+        let colors = midi_file.track_colors();
+        let track = midi_file
+            .track_note_counts()
+            .and_then(|counts| counts.iter().position(|&count| count > 0))
+            .unwrap_or(0);
+        match colors.get(track * 16..track * 16 + 16) {
+            Some(colors) => colors.to_vec(),
+            None => MIDIColor::new_vec_for_tracks(1),
+        }
+    }
+
+    /// Gives the loaded MIDIs the colors of the current palette and color file.
+    /// MIDIs loaded with random colors keep them, only grouped differently.
+    pub fn recolor(&mut self, midi: &MidiSettings) {
+        if let Some(midi_file) = self.midi_file.as_mut() {
+            midi_file.recolor(midi.color_palette, midi.color_by, &self.color_assignments);
+        }
+        // The layers keep their own palette, so they still stand out
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            let palette = layer_palette(midi.color_palette, i);
+            layer.recolor(palette, midi.color_by, &ColorAssignments::default());
+        }
+    }
+
+    /// Asks for a color file and gives its colors to the loaded MIDI right away
+    pub fn import_colors_dialog(&mut self, settings: &mut WasabiSettings) {
+        let path = rfd::FileDialog::new()
+            .add_filter("colors", &["toml", "json"])
+            .pick_file();
+        let Some(path) = path else {
+            return;
+        };
+
+        match ColorAssignments::load(&path) {
+            Ok(assignments) => {
+                self.color_assignments = assignments;
+                settings.midi.color_file = Some(path.to_string_lossy().into_owned());
+                self.recolor(&settings.midi);
             }
+            Err(err) => self.push_toast(
+                format!("Failed to load the colors from {}: {err}", path.display()),
+                ToastLevel::Error,
+            ),
         }
     }
+
+    /// Goes back to only the palette colors
+    pub fn clear_colors(&mut self, settings: &mut WasabiSettings) {
+        self.color_assignments = ColorAssignments::default();
+        settings.midi.color_file = None;
+        self.recolor(&settings.midi);
+    }
+
+    /// Asks where to save the statistics of the loaded MIDI. They're kept from loading
+    /// when the whole MIDI was parsed ahead of time, otherwise the file gets read again
+    /// in the background to collect them.
+    pub fn export_stats_dialog(&mut self) {
+        let Some(midi_file) = self.midi_file.as_ref() else {
+            return;
+        };
+        let summary = midi_file.summary().cloned();
+        let midi_path = midi_file.signature().filepath.clone();
+        let midi_name = Path::new(&midi_path)
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "midi".to_string());
+
+        let path = rfd::FileDialog::new()
+            .add_filter("csv", &["csv"])
+            .add_filter("json", &["json"])
+            .set_file_name(&format!("{midi_name}_stats.csv"))
+            .save_file();
+
+        if let Some(path) = path {
+            let toasts = self.toasts.sender();
+            thread::spawn(move || {
+                let result = match summary {
+                    Some(summary) => summary.save(&path),
+                    None => export_midi_summary(&midi_path, &path),
+                };
+                match result {
+                    Ok(()) => toasts.send(
+                        format!("Saved the MIDI statistics to {}", path.display()),
+                        ToastLevel::Info,
+                    ),
+                    Err(err) => toasts.send(
+                        format!("Failed to export the MIDI statistics: {err}"),
+                        ToastLevel::Error,
+                    ),
+                }
+            });
+        }
+    }
+
+    /// Encodes and saves the frame as a PNG in the background
+    pub fn screenshot_failed(&self, err: &str) {
+        let message = format!("Failed to capture the screenshot: {err}");
+        log::error!("{message}");
+        self.push_toast(message, ToastLevel::Error);
+    }
+
+    pub fn save_screenshot(&mut self, frame: CapturedFrame) {
+        let dir = UserDirs::new()
+            .and_then(|dirs| dirs.picture_dir().map(|dir| dir.to_path_buf()))
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let midi_name = self.midi_file.as_ref().and_then(|midi_file| {
+            Path::new(&midi_file.signature().filepath)
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+        let file_name = match midi_name {
+            Some(name) => format!("wasabi_{name}_{timestamp}.png"),
+            None => format!("wasabi_{timestamp}.png"),
+        };
+        let path = dir.join(file_name);
+
+        let toasts = self.toasts.sender();
+        thread::spawn(move || {
+            let result = frame
+                .into_rgba()
+                .and_then(|image| image.save(&path).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => toasts.send(
+                    format!("Saved screenshot to {}", path.display()),
+                    ToastLevel::Info,
+                ),
+                Err(err) => toasts.send(
+                    format!("Failed to save screenshot: {err}"),
+                    ToastLevel::Error,
+                ),
+            }
+        });
+    }
 }
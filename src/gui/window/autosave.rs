@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use crate::settings::WasabiSettings;
+
+/// How often the settings are checked for changes
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the settings must stay the same before they are saved, so
+/// dragging a slider doesn't write the config on every step
+const IDLE_TIME: Duration = Duration::from_secs(2);
+
+/// Saves the settings shortly after they stop changing.
+///
+/// Changes are found by comparing the serialized settings, which is only
+/// done once every [`CHECK_INTERVAL`] rather than on every frame.
+pub struct SettingsAutosave {
+    saved: Option<String>,
+    /// The changed settings and when they were last seen changing
+    pending: Option<(String, Instant)>,
+    last_check: Instant,
+}
+
+impl SettingsAutosave {
+    pub fn new() -> Self {
+        Self {
+            saved: None,
+            pending: None,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, settings: &WasabiSettings) {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let Ok(current) = toml::to_string(settings) else {
+            return;
+        };
+
+        // The settings at the time autosaving is turned on count as saved
+        let Some(saved) = self.saved.as_ref().filter(|_| settings.autosave) else {
+            self.saved = Some(current);
+            self.pending = None;
+            return;
+        };
+        if *saved == current {
+            self.pending = None;
+            return;
+        }
+
+        match self.pending.as_ref() {
+            Some((pending, since)) if *pending == current => {
+                if since.elapsed() >= IDLE_TIME {
+                    settings.save_to_file();
+                    self.saved = Some(current);
+                    self.pending = None;
+                }
+            }
+            _ => self.pending = Some((current, Instant::now())),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use egui::{Color32, ColorImage, Context, Mesh, Pos2, Rect, TextureHandle, TextureOptions, Ui};
+
+/// An optional image painted behind the notes
+pub struct GuiBackground {
+    texture: Option<TextureHandle>,
+    loaded_path: Option<String>,
+}
+
+impl GuiBackground {
+    pub fn new() -> Self {
+        Self {
+            texture: None,
+            loaded_path: None,
+        }
+    }
+
+    /// Loads the image if the path has changed since the last call
+    pub fn update(&mut self, ctx: &Context, path: &Option<String>) {
+        if &self.loaded_path == path {
+            return;
+        }
+        self.loaded_path = path.clone();
+
+        self.texture = path.as_ref().and_then(|path| match image::open(path) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                let image = ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+                Some(ctx.load_texture("background_image", image, TextureOptions::LINEAR))
+            }
+            Err(err) => {
+                log::warn!("Failed to load background image {path}: {err}");
+                None
+            }
+        });
+    }
+
+    /// Paints a vertical gradient over the whole panel
+    pub fn draw_gradient(&self, ui: &Ui, top: Color32, bottom: Color32) {
+        let rect = ui.max_rect();
+        let mut mesh = Mesh::default();
+        mesh.colored_vertex(rect.left_top(), top);
+        mesh.colored_vertex(rect.right_top(), top);
+        mesh.colored_vertex(rect.left_bottom(), bottom);
+        mesh.colored_vertex(rect.right_bottom(), bottom);
+        mesh.add_triangle(0, 1, 2);
+        mesh.add_triangle(2, 1, 3);
+        ui.painter().add(mesh);
+    }
+
+    /// Paints the image over the whole panel, cropping it to keep the aspect ratio
+    pub fn draw(&self, ui: &Ui, tint: Color32) {
+        if let Some(texture) = &self.texture {
+            let rect = ui.max_rect();
+            let [width, height] = texture.size_vec2().into();
+            if width <= 0.0 || height <= 0.0 || rect.height() <= 0.0 {
+                return;
+            }
+
+            let image_aspect = width / height;
+            let rect_aspect = rect.width() / rect.height();
+            let uv = if image_aspect > rect_aspect {
+                let crop = (1.0 - rect_aspect / image_aspect) / 2.0;
+                Rect::from_min_max(Pos2::new(crop, 0.0), Pos2::new(1.0 - crop, 1.0))
+            } else {
+                let crop = (1.0 - image_aspect / rect_aspect) / 2.0;
+                Rect::from_min_max(Pos2::new(0.0, crop), Pos2::new(1.0, 1.0 - crop))
+            };
+
+            ui.painter().image(texture.id(), rect, uv, tint);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use egui::Color32;
+
+use crate::{
+    audio_playback::SimpleTemporaryPlayer,
+    settings::{CCMapping, CCTarget, VisualSettings},
+};
+
+use super::scene::NoteStyle;
+
+/// How much each visual setting is scaled by the MIDI controllers mapped to it,
+/// 1.0 leaving it unchanged
+pub struct CCEffects {
+    background_brightness: f32,
+    note_brightness: f32,
+    note_saturation: f32,
+    note_opacity: f32,
+}
+
+impl CCEffects {
+    pub fn new(synth: &SimpleTemporaryPlayer, mappings: &[CCMapping]) -> Self {
+        let mut effects = CCEffects {
+            background_brightness: 1.0,
+            note_brightness: 1.0,
+            note_saturation: 1.0,
+            note_opacity: 1.0,
+        };
+
+        for mapping in mappings {
+            let Some(value) = synth.controller(mapping.controller, mapping.channel) else {
+                continue;
+            };
+            let scale = value as f32 / 127.0;
+            match mapping.target {
+                CCTarget::BackgroundBrightness => effects.background_brightness *= scale,
+                CCTarget::NoteBrightness => effects.note_brightness *= scale,
+                CCTarget::NoteSaturation => effects.note_saturation *= scale,
+                CCTarget::NoteOpacity => effects.note_opacity *= scale,
+            }
+        }
+
+        effects
+    }
+
+    pub fn note_style(&self, settings: &VisualSettings) -> NoteStyle {
+        let mut style = NoteStyle::new(settings);
+        style.brightness *= self.note_brightness;
+        style.saturation *= self.note_saturation;
+        style.opacity *= self.note_opacity;
+        style
+    }
+
+    /// Darkens a background color, keeping its alpha
+    pub fn background(&self, color: Color32) -> Color32 {
+        if self.background_brightness == 1.0 {
+            return color;
+        }
+        let [r, g, b, a] = color.to_array();
+        let scale = |c: u8| (c as f32 * self.background_brightness) as u8;
+        Color32::from_rgba_premultiplied(scale(r), scale(g), scale(b), a)
+    }
+
+    /// The tint that darkens the background image
+    pub fn background_tint(&self) -> Color32 {
+        self.background(Color32::WHITE)
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use egui::{Align2, Color32, Context, Frame, Sense, Vec2};
+
+use crate::midi::MIDIColor;
+
+/// How long a channel takes to fade out after its last note ends
+const LED_FADE: Duration = Duration::from_millis(400);
+
+const LED_SIZE: f32 = 10.0;
+
+/// A row of 16 lights, one per channel, that are lit while the channel has notes playing
+pub struct ChannelLeds {
+    last_active: [Option<Instant>; 16],
+}
+
+impl ChannelLeds {
+    pub fn new() -> Self {
+        Self {
+            last_active: [None; 16],
+        }
+    }
+
+    /// The colors are the note colors of each channel, which the lights take when lit
+    pub fn draw(&mut self, ctx: &Context, top: f32, active: [bool; 16], colors: &[MIDIColor]) {
+        let now = Instant::now();
+        for (last_active, active) in self.last_active.iter_mut().zip(active) {
+            if active {
+                *last_active = Some(now);
+            }
+        }
+
+        let leds_frame = Frame::default()
+            .inner_margin(egui::style::Margin::same(6.0))
+            .fill(Color32::from_rgba_unmultiplied(7, 7, 7, 200))
+            .rounding(egui::Rounding::same(6.0));
+
+        egui::Area::new("channel_leds")
+            .anchor(Align2::CENTER_TOP, [0.0, top + 10.0])
+            .show(ctx, |ui| {
+                leds_frame.show(ui, |ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    ui.horizontal(|ui| {
+                        for (i, last_active) in self.last_active.iter().enumerate() {
+                            let brightness = last_active.map_or(0.0, |time| {
+                                1.0 - (time.elapsed().as_secs_f32() / LED_FADE.as_secs_f32())
+                                    .min(1.0)
+                            });
+
+                            let color = colors[i];
+                            let lit = Color32::from_rgb(color.red(), color.green(), color.blue());
+                            let unlit = Color32::from_gray(40);
+                            let fill = Color32::from_rgb(
+                                mix(unlit.r(), lit.r(), brightness),
+                                mix(unlit.g(), lit.g(), brightness),
+                                mix(unlit.b(), lit.b(), brightness),
+                            );
+
+                            let (rect, response) =
+                                ui.allocate_exact_size(Vec2::splat(LED_SIZE), Sense::hover());
+                            ui.painter().rect_filled(rect, 2.0, fill);
+                            response.on_hover_text(format!("Channel {}", i + 1));
+                        }
+                    });
+                });
+            });
+    }
+}
+
+fn mix(from: u8, to: u8, amount: f32) -> u8 {
+    (from as f32 + (to as f32 - from as f32) * amount) as u8
+}
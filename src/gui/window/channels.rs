@@ -0,0 +1,218 @@
+use egui::{Align2, Color32, Context, Frame, Sense, Vec2};
+
+use crate::{
+    audio_playback::{ChannelInfo, DRUM_CHANNEL},
+    midi::MIDIColor,
+};
+
+/// The General MIDI instrument names, indexed by program number
+pub const GM_INSTRUMENTS: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// The colors are the note colors of each channel, shown next to the channel number
+pub fn draw_channels(ctx: &Context, top: f32, channels: &[ChannelInfo; 16], colors: &[MIDIColor]) {
+    let onepx = ctx.pixels_per_point();
+
+    let channels_frame = Frame::default()
+        .inner_margin(egui::style::Margin::same(7.0))
+        .fill(egui::Color32::from_rgba_unmultiplied(7, 7, 7, 200))
+        .stroke(egui::Stroke::new(
+            onepx,
+            egui::Color32::from_rgb(50, 50, 50),
+        ))
+        .rounding(egui::Rounding::same(6.0));
+
+    egui::Window::new("Channels")
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(false)
+        .scroll2([false, false])
+        .frame(channels_frame)
+        .anchor(Align2::RIGHT_TOP, [-10.0, top + 10.0])
+        .min_width(220.0)
+        .show(ctx, |ui| {
+            for (i, channel) in channels.iter().enumerate() {
+                let text_color = if channel.used {
+                    ui.visuals().text_color()
+                } else {
+                    Color32::from_gray(90)
+                };
+
+                ui.horizontal(|ui| {
+                    let color = colors[i];
+                    let swatch = Color32::from_rgb(color.red(), color.green(), color.blue());
+                    let (rect, _) = ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
+                    ui.painter().rect_filled(rect, 2.0, swatch);
+
+                    ui.monospace(egui::RichText::new(format!("{:>2}", i + 1)).color(text_color));
+                    draw_pan_meter(ui, channel.pan_position(), text_color);
+
+                    let name = if i == DRUM_CHANNEL {
+                        "Drums"
+                    } else {
+                        GM_INSTRUMENTS[channel.program as usize & 0x7F]
+                    };
+                    ui.monospace(egui::RichText::new(name).color(text_color));
+                });
+            }
+        });
+}
+
+/// A small horizontal bar with a marker at the pan position of the channel
+fn draw_pan_meter(ui: &mut egui::Ui, pan: f32, color: Color32) {
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(30.0, 10.0), Sense::hover());
+    let painter = ui.painter();
+
+    painter.line_segment(
+        [rect.left_center(), rect.right_center()],
+        egui::Stroke::new(1.0, Color32::from_gray(70)),
+    );
+    painter.line_segment(
+        [rect.center_top(), rect.center_bottom()],
+        egui::Stroke::new(1.0, Color32::from_gray(70)),
+    );
+
+    let x = rect.center().x + pan * rect.width() / 2.0;
+    painter.circle_filled(egui::Pos2::new(x, rect.center().y), 3.0, color);
+
+    response.on_hover_text(format!("Pan: {}", pan_name(pan)));
+}
+
+fn pan_name(pan: f32) -> String {
+    let percent = (pan.abs() * 100.0).round();
+    if percent == 0.0 {
+        "Center".to_string()
+    } else if pan < 0.0 {
+        format!("{percent}% left")
+    } else {
+        format!("{percent}% right")
+    }
+}
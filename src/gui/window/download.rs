@@ -0,0 +1,117 @@
+use std::{fs, path::PathBuf, thread};
+
+use crossbeam_channel::{Receiver, TryRecvError};
+use egui::{Align2, Context, Frame};
+
+use crate::midi::download_midi;
+
+enum DownloadUpdate {
+    Progress { downloaded: u64, total: Option<u64> },
+    Done(Result<PathBuf, String>),
+}
+
+/// A MIDI being downloaded in the background, so the window keeps drawing meanwhile.
+/// It's saved into the temp folder once downloaded, then loaded like any other file.
+pub struct MIDIDownload {
+    url: String,
+    updates: Receiver<DownloadUpdate>,
+    downloaded: u64,
+    total: Option<u64>,
+    start_paused: bool,
+}
+
+impl MIDIDownload {
+    pub fn start(url: String, start_paused: bool) -> Self {
+        let (snd, rcv) = crossbeam_channel::unbounded();
+        let thread_url = url.clone();
+        thread::spawn(move || {
+            let bytes = download_midi(&thread_url, |downloaded, total| {
+                snd.send(DownloadUpdate::Progress { downloaded, total })
+                    .ok();
+            });
+            let result = bytes.map_err(|e| e.to_string()).and_then(|bytes| {
+                let path = download_path(&thread_url);
+                fs::write(&path, bytes).map_err(|e| e.to_string())?;
+                Ok(path)
+            });
+            snd.send(DownloadUpdate::Done(result)).ok();
+        });
+
+        log::info!("Downloading {url}");
+        Self {
+            url,
+            updates: rcv,
+            downloaded: 0,
+            total: None,
+            start_paused,
+        }
+    }
+
+    /// Whether the MIDI should stay paused once it's loaded
+    pub fn start_paused(&self) -> bool {
+        self.start_paused
+    }
+
+    /// Returns where the MIDI was saved once the download is done
+    pub fn poll(&mut self) -> Option<Result<PathBuf, String>> {
+        loop {
+            match self.updates.try_recv() {
+                Ok(DownloadUpdate::Progress { downloaded, total }) => {
+                    self.downloaded = downloaded;
+                    self.total = total;
+                }
+                Ok(DownloadUpdate::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err("The download stopped unexpectedly".to_string()))
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &Context) {
+        let progress = match self.total {
+            Some(total) if total > 0 => format!(
+                "{} of {} KiB ({}%)",
+                self.downloaded / 1024,
+                total / 1024,
+                self.downloaded * 100 / total
+            ),
+            _ => format!("{} KiB", self.downloaded / 1024),
+        };
+
+        egui::Window::new("Downloading")
+            .resizable(false)
+            .collapsible(false)
+            .title_bar(false)
+            .frame(Frame::popup(&ctx.style()))
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Downloading {}", self.url));
+                });
+                ui.label(progress);
+            });
+    }
+}
+
+/// Where the downloaded MIDI is kept, named after the last part of the URL. Only
+/// letters, digits, `-`, `_` and `.` are kept from it, so it can't leave the temp folder.
+fn download_path(url: &str) -> PathBuf {
+    let name: String = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|url| url.rsplit('/').next())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    let name = name.trim_start_matches('.');
+    let name = if name.is_empty() {
+        "download.mid"
+    } else {
+        name
+    };
+    std::env::temp_dir().join(format!("wasabi-{name}"))
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+use egui::{Align2, Color32, Context, Frame, Rect};
+
+/// How long a drum stays lit up after it was hit
+const DRUM_HIT_DURATION: Duration = Duration::from_millis(300);
+
+/// The first key of the General MIDI percussion map
+const FIRST_DRUM_KEY: usize = 35;
+
+/// The General MIDI percussion names, starting at key 35
+const GM_DRUMS: [&str; 47] = [
+    "Acoustic Bass Drum",
+    "Bass Drum",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+];
+
+/// The General MIDI name of the drum, or just "Percussion" outside of the map
+fn drum_name(key: usize) -> &'static str {
+    key.checked_sub(FIRST_DRUM_KEY)
+        .and_then(|i| GM_DRUMS.get(i))
+        .copied()
+        .unwrap_or("Percussion")
+}
+
+/// Draws the names of the recently hit drums in a strip along the side of the
+/// notes that faces the keyboard, for when the drums aren't drawn as notes
+pub fn draw_drums(
+    ctx: &Context,
+    notes_rect: Rect,
+    notes_fall_down: bool,
+    hits: &[Option<Instant>],
+) {
+    let (anchor, offset) = if notes_fall_down {
+        (
+            Align2::CENTER_BOTTOM,
+            [0.0, notes_rect.bottom() - ctx.screen_rect().bottom() - 10.0],
+        )
+    } else {
+        (Align2::CENTER_TOP, [0.0, notes_rect.top() + 10.0])
+    };
+
+    let drum_frame = Frame::default()
+        .inner_margin(egui::style::Margin::same(5.0))
+        .rounding(egui::Rounding::same(4.0));
+
+    egui::Area::new("drum_strip")
+        .anchor(anchor, offset)
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (key, hit) in hits.iter().enumerate() {
+                    let Some(elapsed) = hit.map(|hit| hit.elapsed()) else {
+                        continue;
+                    };
+                    if elapsed > DRUM_HIT_DURATION {
+                        continue;
+                    }
+
+                    let fade = 1.0 - elapsed.as_secs_f32() / DRUM_HIT_DURATION.as_secs_f32();
+                    let alpha = (fade * 220.0) as u8;
+                    drum_frame
+                        .fill(Color32::from_rgba_unmultiplied(230, 140, 30, alpha))
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(drum_name(key)).color(Color32::WHITE));
+                        });
+                }
+            });
+        });
+}
@@ -0,0 +1,89 @@
+use std::time::Instant;
+
+use egui::Context;
+
+use crate::{
+    audio_playback::event_log::EventKind, gui::window::GuiWasabiWindow, state::WasabiState,
+};
+
+pub fn draw_event_log(win: &mut GuiWasabiWindow, state: &mut WasabiState, ctx: &Context) {
+    let events = win.synth.read().unwrap().logged_events();
+    let now = Instant::now();
+
+    egui::Window::new("Event Log")
+        .resizable(true)
+        .collapsible(true)
+        .title_bar(true)
+        .enabled(true)
+        .open(&mut state.event_log_visible)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Channel: ");
+                egui::ComboBox::from_id_source("event_log_channel_select")
+                    .selected_text(match state.event_log_channel {
+                        Some(channel) => format!("{}", channel + 1),
+                        None => "All".to_string(),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut state.event_log_channel, None, "All");
+                        for channel in 0..16 {
+                            ui.selectable_value(
+                                &mut state.event_log_channel,
+                                Some(channel),
+                                format!("{}", channel + 1),
+                            );
+                        }
+                    });
+            });
+
+            ui.horizontal_wrapped(|ui| {
+                for kind in EventKind::ALL {
+                    let hidden = &mut state.event_log_hidden_kinds;
+                    let mut shown = !hidden.contains(&kind);
+                    if ui.checkbox(&mut shown, kind.as_str()).changed() {
+                        if shown {
+                            hidden.retain(|k| *k != kind);
+                        } else {
+                            hidden.push(kind);
+                        }
+                    }
+                }
+            });
+            ui.separator();
+
+            let events: Vec<_> = events
+                .iter()
+                .filter(|e| state.event_log_channel.map_or(true, |c| e.channel() == c))
+                .filter(|e| !state.event_log_hidden_kinds.contains(&e.kind()))
+                .collect();
+            if events.is_empty() {
+                ui.label("No events have been sent to the synth yet");
+                return;
+            }
+
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("event_log_grid")
+                        .num_columns(4)
+                        .spacing([20.0, 2.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Time");
+                            ui.strong("Channel");
+                            ui.strong("Type");
+                            ui.strong("Data");
+                            ui.end_row();
+
+                            for event in events {
+                                let age = now.saturating_duration_since(event.time);
+                                ui.monospace(format!("-{:.3}s", age.as_secs_f64()));
+                                ui.monospace(format!("{}", event.channel() + 1));
+                                ui.monospace(event.kind().as_str());
+                                ui.monospace(event.describe());
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+}
@@ -1,8 +1,13 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 pub struct Fps(VecDeque<Instant>);
 
 const FPS_WINDOW: f64 = 0.5;
+/// How long the frames are kept for the frame time graph
+const HISTORY_WINDOW: f64 = 3.0;
 
 impl Fps {
     pub fn new() -> Self {
@@ -12,7 +17,7 @@ impl Fps {
     pub fn update(&mut self) {
         self.0.push_back(Instant::now());
         while let Some(front) = self.0.front() {
-            if front.elapsed().as_secs_f64() > FPS_WINDOW {
+            if front.elapsed().as_secs_f64() > HISTORY_WINDOW {
                 self.0.pop_front();
             } else {
                 break;
@@ -21,10 +26,22 @@ impl Fps {
     }
 
     pub fn get_fps(&self) -> f64 {
-        if self.0.is_empty() {
-            0.0
-        } else {
-            self.0.len() as f64 / self.0.front().unwrap().elapsed().as_secs_f64()
+        let recent = self
+            .0
+            .iter()
+            .rev()
+            .take_while(|time| time.elapsed().as_secs_f64() <= FPS_WINDOW);
+        match recent.clone().last() {
+            Some(first) => recent.count() as f64 / first.elapsed().as_secs_f64(),
+            None => 0.0,
         }
     }
+
+    /// How long each of the recent frames took, oldest first
+    pub fn frame_times(&self) -> impl '_ + Iterator<Item = Duration> {
+        self.0
+            .iter()
+            .zip(self.0.iter().skip(1))
+            .map(|(prev, next)| *next - *prev)
+    }
 }
@@ -1,23 +1,119 @@
+use std::ops::RangeInclusive;
+
 use egui::{Color32, Mesh, Pos2, Rect, Sense, Ui};
 
-use crate::midi::MIDIColor;
+use crate::{
+    midi::{Beat, MIDIColor},
+    settings::{KeyColorBlend, VisualSettings},
+};
 
 use super::keyboard_layout::KeyboardView;
 
-pub struct GuiKeyboard {}
+/// The amount of scroll points egui-winit emits for a single mouse wheel line
+const SCROLL_LINE: f32 = 50.0;
+/// The smallest key range that scrolling is allowed to zoom into
+const MIN_KEY_SPAN: i32 = 12;
+const MAX_KEY: i32 = 127;
+/// The time it takes for the beat flash to fade to about a third of its strength
+const BEAT_FLASH_DECAY: f64 = 0.12;
+/// How long a key takes to come back up once its notes stop
+const KEY_RELEASE_TIME: f32 = 0.08;
+/// How bright the key color of the quietest note is, relative to the loudest
+const MIN_VELOCITY_BRIGHTNESS: f32 = 0.35;
+
+pub struct GuiKeyboard {
+    zoom_scroll: f32,
+    pan_scroll: f32,
+    /// How far down each key is pressed, from 0.0 to 1.0, and the color it was last pressed with
+    key_presses: Vec<(f32, Option<Color32>)>,
+}
 
 impl GuiKeyboard {
     pub fn new() -> GuiKeyboard {
-        GuiKeyboard {}
+        GuiKeyboard {
+            zoom_scroll: 0.0,
+            pan_scroll: 0.0,
+            key_presses: vec![(0.0, None); 256],
+        }
+    }
+
+    /// Presses the keys that have colors and lets the others rise back up. The keys
+    /// keep their color while rising, so they don't flicker between short notes.
+    fn animate_presses(&mut self, colors: &mut [Option<Color32>], dt: f32) -> Vec<f32> {
+        let release = dt / KEY_RELEASE_TIME;
+        for (color, (press, last_color)) in colors.iter_mut().zip(self.key_presses.iter_mut()) {
+            if color.is_some() {
+                *press = 1.0;
+                *last_color = *color;
+            } else {
+                *press = (*press - release).max(0.0);
+                if *press > 0.0 {
+                    *color = *last_color;
+                }
+            }
+        }
+        self.key_presses.iter().map(|(press, _)| *press).collect()
+    }
+
+    /// Zooms the key range with the mouse wheel while hovering the keyboard,
+    /// or pans it when Ctrl is held.
+    pub fn handle_scroll(&mut self, ui: &Ui, key_range: &mut RangeInclusive<u8>) {
+        if !ui.ui_contains_pointer() {
+            self.zoom_scroll = 0.0;
+            self.pan_scroll = 0.0;
+            return;
+        }
+
+        // egui-winit turns Ctrl+scroll into a zoom event, so undo that to get the pan delta
+        let (scroll, pan) = ui.input(|i| (i.scroll_delta.y, i.zoom_delta().ln() * 200.0));
+
+        let zoom_steps = take_scroll_steps(&mut self.zoom_scroll, scroll);
+        let pan_steps = take_scroll_steps(&mut self.pan_scroll, pan);
+        if zoom_steps == 0 && pan_steps == 0 {
+            return;
+        }
+
+        let mut start = *key_range.start() as i32;
+        let mut end = (*key_range.end() as i32).min(MAX_KEY);
+
+        if zoom_steps > 0 {
+            // Narrow the range, but never below the minimum span
+            let span = end - start;
+            let shrink = zoom_steps.min(((span - MIN_KEY_SPAN) / 2).max(0));
+            start += shrink;
+            end -= shrink;
+        } else if zoom_steps < 0 {
+            start = (start + zoom_steps).max(0);
+            end = (end - zoom_steps).min(MAX_KEY);
+        }
+
+        if pan_steps != 0 {
+            let span = end - start;
+            start = (start + pan_steps).clamp(0, (MAX_KEY - span).max(0));
+            end = (start + span).min(MAX_KEY);
+        }
+
+        *key_range = start as u8..=end as u8;
+    }
+
+    /// Draws the keyboard with no keys pressed
+    pub fn draw_empty(&mut self, ui: &mut Ui, key_view: &KeyboardView, settings: &VisualSettings) {
+        let colors = vec![Vec::new(); 256];
+        self.draw(ui, key_view, &colors, settings, 0.0);
     }
 
     pub fn draw(
         &mut self,
         ui: &mut Ui,
         key_view: &KeyboardView,
-        colors: &[Option<MIDIColor>],
-        bar_color: &Color32,
+        colors: &[Vec<MIDIColor>],
+        settings: &VisualSettings,
+        beat_flash: f32,
     ) {
+        let bar_color = &flash_color(settings.bar_color, beat_flash);
+        // The keyboard is mirrored when the notes rise up from below it
+        let flipped = !settings.notes_fall_down;
+
         let (rect, _) = ui.allocate_exact_size(ui.available_size(), Sense::click());
         let mut mesh = Mesh::default();
         let key_density =
@@ -28,17 +124,47 @@ impl GuiKeyboard {
         let bar = rect.height() * 0.06;
 
         let black_key_overlap = bar / 2.35;
-        let top = rect.top() + bar;
+        // The coloured bar takes the lower part of the space above the keys, so
+        // a custom height only moves the keys and leaves the progress bar as is
+        let bar_height = settings
+            .bar_height_px
+            .map(|height| height.max(0.0))
+            .unwrap_or(black_key_overlap);
+        let top = rect.top() + bar - black_key_overlap + bar_height;
         let bottom = rect.bottom();
         let black_bottom = rect.bottom() - rect.height() * 0.34;
         let map_x = |num: f32| rect.left() + num * rect.width();
-        fn map_color(col: MIDIColor) -> Color32 {
-            Color32::from_rgb(col.red(), col.green(), col.blue())
-        }
+        let opacity = if settings.keyboard_note_opacity {
+            settings.note_opacity.clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let mut colors: Vec<_> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, colors)| {
+                blend_colors(colors, settings.key_color_blend).map(|color| {
+                    let color = color.adjusted(settings.brightness, settings.saturation);
+                    let color = Color32::from_rgb(color.red(), color.green(), color.blue());
+                    if opacity < 1.0 {
+                        let idle = if key_view.key(i).black { 20 } else { 255 };
+                        fade_key_color(color, idle, opacity)
+                    } else {
+                        color
+                    }
+                })
+            })
+            .collect();
+        let presses = if settings.key_press_animation {
+            let dt = ui.input(|i| i.stable_dt);
+            self.animate_presses(&mut colors, dt)
+        } else {
+            vec![0.0; colors.len()]
+        };
 
         for (i, key) in key_view.iter_visible_keys() {
             if !key.black {
-                if let Some(color) = colors[i].map(map_color) {
+                if let Some(color) = colors[i] {
                     // Pressed
                     let darkened = Color32::from_rgb(
                         (color.r() as f32 * 0.6) as u8,
@@ -63,6 +189,16 @@ impl GuiKeyboard {
                         darkened,
                     );
 
+                    // A pressed key sinks in, leaving a shadow below it
+                    let key_bottom = bottom - presses[i] * md_height;
+                    if key_bottom < bottom {
+                        let shadow = Rect::from_min_max(
+                            Pos2::new(map_x(key.left), key_bottom),
+                            Pos2::new(map_x(key.right), bottom),
+                        );
+                        mesh.add_colored_rect(shadow, Color32::from_rgb(20, 20, 20));
+                    }
+
                     add_rect_triangles(&mut mesh);
                     mesh.colored_vertex(
                         Pos2::new(map_x(key.left), top + black_key_overlap),
@@ -72,20 +208,20 @@ impl GuiKeyboard {
                         Pos2::new(map_x(key.right), top + black_key_overlap),
                         darkened,
                     );
-                    mesh.colored_vertex(Pos2::new(map_x(key.left), bottom), color);
-                    mesh.colored_vertex(Pos2::new(map_x(key.right), bottom), color);
+                    mesh.colored_vertex(Pos2::new(map_x(key.left), key_bottom), color);
+                    mesh.colored_vertex(Pos2::new(map_x(key.right), key_bottom), color);
 
                     add_rect_triangles(&mut mesh);
                     mesh.colored_vertex(
-                        Pos2::new(map_x(key.left), bottom - key_density * 2.0),
+                        Pos2::new(map_x(key.left), key_bottom - key_density * 2.0),
                         darkened2,
                     );
                     mesh.colored_vertex(
-                        Pos2::new(map_x(key.right), bottom - key_density * 2.0),
+                        Pos2::new(map_x(key.right), key_bottom - key_density * 2.0),
                         darkened2,
                     );
-                    mesh.colored_vertex(Pos2::new(map_x(key.left), bottom), darkened);
-                    mesh.colored_vertex(Pos2::new(map_x(key.right), bottom), darkened);
+                    mesh.colored_vertex(Pos2::new(map_x(key.left), key_bottom), darkened);
+                    mesh.colored_vertex(Pos2::new(map_x(key.right), key_bottom), darkened);
                 } else {
                     // Not pressed
                     add_rect_triangles(&mut mesh);
@@ -170,23 +306,40 @@ impl GuiKeyboard {
         }
 
         // Coloured bar
-        let bar_color_dark = Color32::from_rgb(
-            (bar_color.r() as f32 * 0.3) as u8,
-            (bar_color.g() as f32 * 0.3) as u8,
-            (bar_color.b() as f32 * 0.3) as u8,
-        );
+        let bar_color_dark = if settings.bar_gradient {
+            Color32::from_rgb(
+                (bar_color.r() as f32 * 0.3) as u8,
+                (bar_color.g() as f32 * 0.3) as u8,
+                (bar_color.b() as f32 * 0.3) as u8,
+            )
+        } else {
+            *bar_color
+        };
         add_rect_triangles(&mut mesh);
-        mesh.colored_vertex(
-            Pos2::new(rect.left(), top - black_key_overlap),
-            bar_color_dark,
-        );
-        mesh.colored_vertex(
-            Pos2::new(rect.right(), top - black_key_overlap),
-            bar_color_dark,
-        );
+        mesh.colored_vertex(Pos2::new(rect.left(), top - bar_height), bar_color_dark);
+        mesh.colored_vertex(Pos2::new(rect.right(), top - bar_height), bar_color_dark);
         mesh.colored_vertex(Pos2::new(rect.left(), top), *bar_color);
         mesh.colored_vertex(Pos2::new(rect.right(), top), *bar_color);
 
+        if settings.bar_border && bar_height > 0.0 {
+            let border = onepx.min(bar_height / 2.0);
+            let border_color = Color32::from_gray(10);
+            mesh.add_colored_rect(
+                Rect::from_min_max(
+                    Pos2::new(rect.left(), top - bar_height),
+                    Pos2::new(rect.right(), top - bar_height + border),
+                ),
+                border_color,
+            );
+            mesh.add_colored_rect(
+                Rect::from_min_max(
+                    Pos2::new(rect.left(), top - border),
+                    Pos2::new(rect.right(), top),
+                ),
+                border_color,
+            );
+        }
+
         // Progress bar
         add_rect_triangles(&mut mesh);
         mesh.colored_vertex(
@@ -198,17 +351,17 @@ impl GuiKeyboard {
             Color32::from_rgb(90, 90, 90),
         );
         mesh.colored_vertex(
-            Pos2::new(rect.left(), top - black_key_overlap),
+            Pos2::new(rect.left(), top - bar_height),
             Color32::from_rgb(40, 40, 40),
         );
         mesh.colored_vertex(
-            Pos2::new(rect.right(), top - black_key_overlap),
+            Pos2::new(rect.right(), top - bar_height),
             Color32::from_rgb(40, 40, 40),
         );
 
         for (i, key) in key_view.iter_visible_keys() {
             if key.black {
-                if let Some(color) = colors[i].map(map_color) {
+                if let Some(color) = colors[i] {
                     // Pressed
                     let darkened = Color32::from_rgb(
                         (color.r() as f32 * 0.76) as u8,
@@ -222,6 +375,7 @@ impl GuiKeyboard {
                         (color.b() as f32 * 1.3) as u8,
                     );
 
+                    let black_bottom = black_bottom - presses[i] * md_height / 2.0;
                     let md_height = md_height / 2.0;
                     let black_key_overlap = black_key_overlap / 2.2;
 
@@ -391,10 +545,99 @@ impl GuiKeyboard {
             }
         }
 
+        if flipped {
+            // Mirror the keyboard so that the bar faces the notes above it
+            for vertex in mesh.vertices.iter_mut() {
+                vertex.pos.y = rect.top() + rect.bottom() - vertex.pos.y;
+            }
+        }
+
         ui.painter().add(mesh);
     }
 }
 
+/// Combines the colors of all the notes playing on a key into one
+fn blend_colors(colors: &[MIDIColor], blend: KeyColorBlend) -> Option<MIDIColor> {
+    let first = colors.first()?;
+    let color = match blend {
+        KeyColorBlend::Off => *first,
+        KeyColorBlend::Average => {
+            let count = colors.len() as u32;
+            let sum = |channel: fn(&MIDIColor) -> u8| {
+                (colors.iter().map(|c| channel(c) as u32).sum::<u32>() / count) as u8
+            };
+            MIDIColor::new(
+                sum(MIDIColor::red),
+                sum(MIDIColor::green),
+                sum(MIDIColor::blue),
+            )
+        }
+        KeyColorBlend::Additive => {
+            let sum = |channel: fn(&MIDIColor) -> u8| {
+                colors
+                    .iter()
+                    .map(|c| channel(c) as u32)
+                    .sum::<u32>()
+                    .min(255) as u8
+            };
+            MIDIColor::new(
+                sum(MIDIColor::red),
+                sum(MIDIColor::green),
+                sum(MIDIColor::blue),
+            )
+        }
+    };
+    Some(color)
+}
+
+/// Darkens the key colors of the quiet notes, so the hard hits stand out. Keys that
+/// the synth has no velocity for keep their colors.
+pub fn scale_by_velocity(colors: &mut [Vec<MIDIColor>], velocities: &[u8; 128]) {
+    for (colors, &velocity) in colors.iter_mut().zip(velocities.iter()) {
+        if velocity == 0 {
+            continue;
+        }
+
+        let brightness =
+            MIN_VELOCITY_BRIGHTNESS + (1.0 - MIN_VELOCITY_BRIGHTNESS) * velocity as f32 / 127.0;
+        let scale = |c: u8| (c as f32 * brightness) as u8;
+        for color in colors.iter_mut() {
+            *color = MIDIColor::new(
+                scale(color.red()),
+                scale(color.green()),
+                scale(color.blue()),
+            );
+        }
+    }
+}
+
+/// The strength of the keyboard bar flash. It only depends on the time since
+/// the beat started, so it fades out the same way at any frame rate.
+pub fn beat_flash(beat: Beat) -> f32 {
+    let strength = if beat.downbeat { 1.0 } else { 0.5 };
+    (strength * (-beat.elapsed / BEAT_FLASH_DECAY).exp()) as f32
+}
+
+/// Mixes the color of a pressed key with the gray of the key when it isn't pressed,
+/// so it looks like the note color is drawn over the key with the given opacity
+fn fade_key_color(color: Color32, idle: u8, opacity: f32) -> Color32 {
+    let mix = |c: u8| (idle as f32 + (c as f32 - idle as f32) * opacity) as u8;
+    Color32::from_rgb(mix(color.r()), mix(color.g()), mix(color.b()))
+}
+
+/// Brightens the color towards white by the flash strength
+fn flash_color(color: Color32, flash: f32) -> Color32 {
+    let mix = |c: u8| (c as f32 + (255.0 - c as f32) * flash * 0.7) as u8;
+    Color32::from_rgb(mix(color.r()), mix(color.g()), mix(color.b()))
+}
+
+fn take_scroll_steps(accumulated: &mut f32, delta: f32) -> i32 {
+    *accumulated += delta / SCROLL_LINE;
+    let steps = accumulated.trunc();
+    *accumulated -= steps;
+    steps as i32
+}
+
 fn add_rect_triangles(mesh: &mut Mesh) {
     let idx = mesh.vertices.len() as u32;
     mesh.add_triangle(idx, idx + 1, idx + 2);
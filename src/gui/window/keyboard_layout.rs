@@ -166,6 +166,14 @@ impl KeyboardLayout {
     }
 
     pub fn get_view_for_keys(&self, first_key: usize, last_key: usize) -> KeyboardView {
+        if first_key > last_key {
+            return KeyboardView {
+                layout: self,
+                range: KeyboardRange::new(0.0, 1.0),
+                visible_range: first_key..first_key,
+            };
+        }
+
         let range = self.get_range_for_keys(first_key, last_key);
 
         let mut left_key = first_key;
@@ -0,0 +1,171 @@
+use egui::{Id, Rect, Ui};
+
+use crate::{
+    audio_playback::transpose_key,
+    midi::{MIDIFile, MIDIFileBase, MIDIFileUnion, MIDINoteColumnView, MIDINoteViews},
+};
+
+use super::keyboard_layout::KeyboardView;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// A note under the mouse cursor, with its times in seconds from the start of the MIDI
+#[derive(Clone)]
+struct HoveredNote {
+    key: usize,
+    channel: u8,
+    velocity: u8,
+    start: f64,
+    length: f64,
+}
+
+/// Where the notes were last searched, the key and note offset being in view units
+#[derive(PartialEq)]
+struct HoverQuery {
+    x: f32,
+    offset: f32,
+    time: f64,
+    view_range: f64,
+    transpose: i32,
+}
+
+/// Remembers the last note that was found under the cursor, so the notes are only
+/// searched again once the cursor or the MIDI moves
+#[derive(Default)]
+pub struct NoteHover {
+    last: Option<(HoverQuery, Option<HoveredNote>)>,
+}
+
+/// The name and octave of a key, where key 60 is C4
+fn note_name(key: usize) -> String {
+    format!("{}{}", NOTE_NAMES[key % 12], key as i32 / 12 - 1)
+}
+
+fn format_time(seconds: f64) -> String {
+    format!("{}:{:06.3}", seconds as u64 / 60, seconds % 60.0)
+}
+
+/// Finds the note drawn at the given key and time, `offset` being the seconds
+/// from the current time
+fn find_note(
+    midi_file: &mut impl MIDIFile,
+    key_view: &KeyboardView,
+    x: f32,
+    offset: f32,
+    view_range: f64,
+    transpose: i32,
+) -> Option<HoveredNote> {
+    let time = midi_file.timer().get_time().as_secs_f64();
+    let views = midi_file.get_current_column_views(view_range, false);
+
+    // The black keys are drawn over the white keys, so they are checked first
+    let mut keys: Vec<_> = (0..256)
+        .filter_map(|column| Some((column, transpose_key(column, transpose)?)))
+        .filter(|&(_, key)| {
+            let position = key_view.note(key);
+            position.left <= x && x <= position.right
+        })
+        .collect();
+    keys.sort_by_key(|&(_, key)| !key_view.key(key).black);
+
+    keys.into_iter().find_map(|(column, key)| {
+        let note = views
+            .get_column(column)
+            .iterate_displaced_notes()
+            .find(|note| note.start <= offset && offset <= note.start + note.len)?;
+        Some(HoveredNote {
+            key,
+            channel: note.channel,
+            velocity: note.velocity,
+            start: time + note.start as f64,
+            length: note.len as f64,
+        })
+    })
+}
+
+impl NoteHover {
+    /// Shows the details of the note under the cursor in a tooltip. The notes only get
+    /// checked while the MIDI is paused, since searching through them takes a while
+    /// in dense MIDIs. The Cake loading mode doesn't keep the details of each note.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_tooltip(
+        &mut self,
+        ui: &Ui,
+        midi_file: &mut MIDIFileUnion,
+        key_view: &KeyboardView,
+        scene_rect: Rect,
+        view_range: f64,
+        transpose: i32,
+        notes_fall_down: bool,
+    ) {
+        if !midi_file.timer().is_paused() || !ui.rect_contains_pointer(scene_rect) {
+            return;
+        }
+        let Some(pointer) = ui.input(|i| i.pointer.hover_pos()) else {
+            return;
+        };
+
+        let x = (pointer.x - scene_rect.min.x) / scene_rect.width();
+        let y = (pointer.y - scene_rect.min.y) / scene_rect.height();
+
+        // The bottom of the scene is the current time when the notes fall down
+        let offset = (if notes_fall_down { 1.0 - y } else { y }) * view_range as f32;
+
+        let query = HoverQuery {
+            x,
+            offset,
+            time: midi_file.timer().get_time().as_secs_f64(),
+            view_range,
+            transpose,
+        };
+        let note = match &self.last {
+            Some((last, note)) if *last == query => note.clone(),
+            _ => {
+                let note = match midi_file {
+                    MIDIFileUnion::InRam(file) => {
+                        find_note(file, key_view, x, offset, view_range, transpose)
+                    }
+                    MIDIFileUnion::Live(file) => {
+                        find_note(file, key_view, x, offset, view_range, transpose)
+                    }
+                    MIDIFileUnion::Cake(_) => None,
+                };
+                self.last = Some((query, note.clone()));
+                note
+            }
+        };
+        if let Some(note) = note {
+            draw_note(ui, &note);
+        }
+    }
+}
+
+fn draw_note(ui: &Ui, note: &HoveredNote) {
+    egui::show_tooltip_at_pointer(ui.ctx(), Id::new("note_tooltip"), |ui| {
+        egui::Grid::new("note_tooltip_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Key: ");
+                ui.label(format!("{} ({})", note_name(note.key), note.key));
+                ui.end_row();
+
+                ui.label("Channel: ");
+                ui.label(format!("{}", note.channel + 1));
+                ui.end_row();
+
+                ui.label("Velocity: ");
+                ui.label(format!("{}", note.velocity));
+                ui.end_row();
+
+                ui.label("Start: ");
+                ui.label(format_time(note.start));
+                ui.end_row();
+
+                ui.label("Length: ");
+                ui.label(format!("{:.3}s", note.length));
+                ui.end_row();
+            });
+    });
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+/// A bigger jump between two frames is a seek, so the notes skipped over don't count
+const MAX_SAMPLE_GAP: f64 = 0.25;
+
+/// The highest value that a statistic has reached, and where in the MIDI it happened
+#[derive(Debug, Clone, Copy)]
+pub struct Peak {
+    pub value: u64,
+    pub time: f64,
+}
+
+/// Keeps track of the highest NPS and voice count since the MIDI was loaded
+pub struct GuiPeaks {
+    /// The passed note count at each frame of the last second of playback
+    samples: VecDeque<(f64, u64)>,
+    nps: Option<Peak>,
+    voices: Option<Peak>,
+}
+
+impl GuiPeaks {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            nps: None,
+            voices: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn nps(&self) -> Option<Peak> {
+        self.nps
+    }
+
+    pub fn voices(&self) -> Option<Peak> {
+        self.voices
+    }
+
+    pub fn update(&mut self, time: f64, passed_notes: Option<u64>, voice_count: u64) {
+        if self.voices.map_or(true, |peak| voice_count > peak.value) {
+            self.voices = Some(Peak {
+                value: voice_count,
+                time,
+            });
+        }
+
+        let Some(passed_notes) = passed_notes else {
+            return;
+        };
+
+        // Seeking makes the old samples meaningless
+        if let Some(&(last_time, last_notes)) = self.samples.back() {
+            if time < last_time || time - last_time > MAX_SAMPLE_GAP || passed_notes < last_notes {
+                self.samples.clear();
+            } else if time == last_time {
+                return;
+            }
+        }
+
+        self.samples.push_back((time, passed_notes));
+        while let Some(&(sample_time, _)) = self.samples.front() {
+            if time - sample_time <= 1.0 {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        // The peak points at the start of the second that had the most notes
+        let (start_time, start_notes) = self.samples[0];
+        let nps = passed_notes - start_notes;
+        if nps > 0 && self.nps.map_or(true, |peak| nps > peak.value) {
+            self.nps = Some(Peak {
+                value: nps,
+                time: start_time,
+            });
+        }
+    }
+}
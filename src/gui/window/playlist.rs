@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use egui::Context;
+
+use crate::{
+    gui::window::GuiWasabiWindow, midi::MIDIFileBase, settings::WasabiSettings, state::WasabiState,
+};
+
+enum PlaylistAction {
+    Play(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Remove(usize),
+}
+
+pub fn draw_playlist(
+    win: &mut GuiWasabiWindow,
+    settings: &mut WasabiSettings,
+    state: &mut WasabiState,
+    ctx: &Context,
+) {
+    let mut action = None;
+
+    egui::Window::new("Playlist")
+        .resizable(true)
+        .collapsible(true)
+        .title_bar(true)
+        .scroll2([false, true])
+        .enabled(true)
+        .open(&mut state.playlist_visible)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Add").clicked() {
+                    let midi_paths = rfd::FileDialog::new()
+                        .add_filter("midi", &["mid", "midi", "rmi", "kar"])
+                        .pick_files();
+
+                    for path in midi_paths.into_iter().flatten() {
+                        if let Some(path) = path.to_str() {
+                            win.playlist.push(path.to_owned());
+                        }
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    win.playlist.clear();
+                    win.playlist_index = None;
+                }
+
+                ui.add_space(10.0);
+
+                if ui.button("Previous").clicked() {
+                    win.play_previous(settings);
+                }
+                if ui.button("Next").clicked() {
+                    win.play_next(settings);
+                }
+
+                ui.add_space(10.0);
+
+                ui.checkbox(&mut settings.midi.remember_playlist, "Remember")
+                    .on_hover_text(
+                        "Keep the playlist in the config file when the settings are saved",
+                    );
+            });
+            ui.separator();
+
+            if win.playlist.is_empty() {
+                ui.label("Add some MIDIs to play them one after another");
+                return;
+            }
+
+            egui::Grid::new("playlist_grid")
+                .num_columns(3)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for (i, path) in win.playlist.iter().enumerate() {
+                        let name = Path::new(path)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| path.clone());
+
+                        ui.label(format!("{}", i + 1));
+                        if ui
+                            .selectable_label(win.playlist_index == Some(i), name)
+                            .on_hover_text(path)
+                            .clicked()
+                        {
+                            action = Some(PlaylistAction::Play(i));
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.small_button("⏶").clicked() {
+                                action = Some(PlaylistAction::MoveUp(i));
+                            }
+                            if ui.small_button("⏷").clicked() {
+                                action = Some(PlaylistAction::MoveDown(i));
+                            }
+                            if ui.small_button("✖").clicked() {
+                                action = Some(PlaylistAction::Remove(i));
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+    match action {
+        Some(PlaylistAction::Play(i)) => win.play_playlist_entry(settings, i),
+        Some(PlaylistAction::MoveUp(i)) if i > 0 => swap_entries(win, i, i - 1),
+        Some(PlaylistAction::MoveDown(i)) if i + 1 < win.playlist.len() => {
+            swap_entries(win, i, i + 1)
+        }
+        Some(PlaylistAction::Remove(i)) => {
+            win.playlist.remove(i);
+            win.playlist_index = match win.playlist_index {
+                Some(current) if current == i => None,
+                Some(current) if current > i => Some(current - 1),
+                index => index,
+            };
+        }
+        _ => {}
+    }
+
+    if settings.midi.remember_playlist {
+        settings.midi.playlist.clone_from(&win.playlist);
+    } else {
+        settings.midi.playlist.clear();
+    }
+}
+
+/// Swaps two entries, keeping track of the one that is playing
+fn swap_entries(win: &mut GuiWasabiWindow, a: usize, b: usize) {
+    win.playlist.swap(a, b);
+    win.playlist_index = win.playlist_index.map(|current| match current {
+        i if i == a => b,
+        i if i == b => a,
+        i => i,
+    });
+}
+
+impl GuiWasabiWindow {
+    /// Loads a MIDI of the playlist. It only becomes the current entry if it loads,
+    /// or starts downloading.
+    pub fn play_playlist_entry(&mut self, settings: &mut WasabiSettings, index: usize) {
+        if let Some(path) = self.playlist.get(index) {
+            let path = WasabiSettings::resolve_path(path, WasabiSettings::config_dir());
+            self.load_midi(settings, path);
+            if self.midi_file.is_some() || self.is_downloading() {
+                self.playlist_index = Some(index);
+            }
+        }
+    }
+
+    pub fn play_next(&mut self, settings: &mut WasabiSettings) {
+        let next = self.playlist_index.map(|i| i + 1).unwrap_or(0);
+        self.play_playlist_entry(settings, next);
+    }
+
+    pub fn play_previous(&mut self, settings: &mut WasabiSettings) {
+        if let Some(previous) = self.playlist_index.and_then(|i| i.checked_sub(1)) {
+            self.play_playlist_entry(settings, previous);
+        }
+    }
+
+    /// Whether the loaded MIDI has played until the end
+    pub fn is_finished(&self) -> bool {
+        self.midi_file.as_ref().map_or(false, |midi_file| {
+            midi_file.midi_length().map_or(false, |length| {
+                midi_file.timer().get_time().as_secs_f64() >= length
+            })
+        })
+    }
+}
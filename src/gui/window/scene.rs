@@ -1,11 +1,15 @@
 mod cake_system;
 mod note_list_system;
 
-use egui::Ui;
+use std::sync::Arc;
+
+use egui::{Pos2, Rect, Ui};
+use vulkano::image::ImageViewAbstract;
 
 use crate::{
     midi::{MIDIColor, MIDIFileUnion},
     scenes::SceneSwapchain,
+    settings::VisualSettings,
 };
 
 use self::{cake_system::CakeRenderer, note_list_system::NoteRenderer};
@@ -48,6 +52,68 @@ impl CurrentRenderer {
     }
 }
 
+/// The most note colors that are collected per key for blending
+pub const MAX_KEY_COLORS: usize = 16;
+
+/// The width of the note outline in pixels, before it's scaled down for small notes
+const NOTE_BORDER_WIDTH: f32 = 2.0;
+
+/// How many seconds the attack glow takes to fade out
+const ATTACK_GLOW_SECONDS: f32 = 0.15;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NoteStyle {
+    pub border_width: f32,
+    pub rounding: f32,
+    /// Notes shorter than this many pixels aren't rounded
+    pub rounding_min_length: f32,
+    pub brightness: f32,
+    pub saturation: f32,
+    pub min_note_height: f32,
+    /// How much darker the bottom of each note is than its top
+    pub gradient: f32,
+    pub opacity: f32,
+    /// How much the notes light up as they reach the keyboard, 0 being off.
+    /// The Cake loading mode doesn't know when each note starts, so it has no glow.
+    pub attack_glow: f32,
+    /// The MIDI time that the attack glow lasts for
+    pub glow_time: f32,
+    /// The width in pixels that notes cut off by the sides of the view fade out over
+    pub edge_fade: f32,
+    /// Draw a tail on the notes held by the sustain pedal
+    pub show_sustain: bool,
+    /// Leave out the percussion channel, which gets its own strip instead
+    pub separate_drums: bool,
+}
+
+impl NoteStyle {
+    pub fn new(settings: &VisualSettings) -> Self {
+        Self {
+            border_width: if settings.note_outline {
+                NOTE_BORDER_WIDTH
+            } else {
+                0.0
+            },
+            rounding: settings.note_rounding,
+            rounding_min_length: settings.rounding_min_length_px,
+            brightness: settings.brightness,
+            saturation: settings.saturation,
+            min_note_height: settings.min_note_height_px,
+            gradient: if settings.note_gradient {
+                settings.gradient_strength
+            } else {
+                0.0
+            },
+            opacity: settings.note_opacity.clamp(0.0, 1.0),
+            attack_glow: settings.attack_glow.max(0.0),
+            glow_time: ATTACK_GLOW_SECONDS,
+            edge_fade: settings.edge_fade_px.max(0.0),
+            show_sustain: settings.show_sustain,
+            separate_drums: settings.separate_drums,
+        }
+    }
+}
+
 pub struct GuiRenderScene {
     swap_chain: SceneSwapchain,
     draw_system: CurrentRenderer,
@@ -55,7 +121,10 @@ pub struct GuiRenderScene {
 
 pub struct RenderResultData {
     pub notes_rendered: u64,
-    pub key_colors: Vec<Option<MIDIColor>>,
+    /// Whether some notes were left out because of the rendered note limit
+    pub notes_capped: bool,
+    /// The colors of the notes currently playing on each key
+    pub key_colors: Vec<Vec<MIDIColor>>,
 }
 
 impl GuiRenderScene {
@@ -66,6 +135,7 @@ impl GuiRenderScene {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &mut self,
         state: &mut GuiState,
@@ -73,32 +143,90 @@ impl GuiRenderScene {
         key_view: &KeyboardView,
         midi_file: &mut MIDIFileUnion,
         view_range: f64,
+        note_style: NoteStyle,
+        pitch_bends: [f32; 16],
+        max_notes: Option<usize>,
+        transpose: i32,
+        flipped: bool,
     ) -> RenderResultData {
-        let size = ui.available_size();
-        let size = [size.x as u32, size.y as u32];
+        // Render at the full pixel resolution, so the UI scale doesn't affect the notes
+        let available = ui.available_size();
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let size = [
+            (available.x * pixels_per_point) as u32,
+            (available.y * pixels_per_point) as u32,
+        ];
 
         let scene_image = self.swap_chain.get_next_image(state, size);
         let frame = scene_image.image.clone();
+        let id = scene_image.id;
+
+        let result = self.draw_to_image(
+            state.renderer,
+            frame,
+            key_view,
+            midi_file,
+            view_range,
+            note_style,
+            pitch_bends,
+            max_notes,
+            transpose,
+        );
+
+        let uv = if flipped {
+            Rect::from_min_max(Pos2::new(0.0, 1.0), Pos2::new(1.0, 0.0))
+        } else {
+            Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0))
+        };
+        ui.add(egui::Image::new(id, available).uv(uv));
 
-        let result = match midi_file {
-            MIDIFileUnion::InRam(file) => self
-                .draw_system
-                .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
-
-            MIDIFileUnion::Live(file) => self
-                .draw_system
-                .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
+        result
+    }
 
+    /// Draws the notes straight into the image, without egui. The notes always fall
+    /// down in the image, and are flipped when it's shown if they rise.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_to_image(
+        &mut self,
+        renderer: &GuiRenderer,
+        frame: Arc<dyn ImageViewAbstract + 'static>,
+        key_view: &KeyboardView,
+        midi_file: &mut MIDIFileUnion,
+        view_range: f64,
+        note_style: NoteStyle,
+        pitch_bends: [f32; 16],
+        max_notes: Option<usize>,
+        transpose: i32,
+    ) -> RenderResultData {
+        match midi_file {
+            MIDIFileUnion::InRam(file) => self.draw_system.get_note_renderer(renderer).draw(
+                key_view,
+                frame,
+                file,
+                view_range,
+                note_style,
+                pitch_bends,
+                max_notes,
+                transpose,
+            ),
+
+            MIDIFileUnion::Live(file) => self.draw_system.get_note_renderer(renderer).draw(
+                key_view,
+                frame,
+                file,
+                view_range,
+                note_style,
+                pitch_bends,
+                max_notes,
+                transpose,
+            ),
+
+            // Cake files don't keep the channel of each note, so they can't be bent.
+            // They also draw every key in a single pass, so there is no note limit
             MIDIFileUnion::Cake(file) => self
                 .draw_system
-                .get_cake_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
-        };
-
-        ui.image(scene_image.id, [size[0] as f32, size[1] as f32]);
-
-        result
+                .get_cake_renderer(renderer)
+                .draw(key_view, frame, file, view_range, note_style, transpose),
+        }
     }
 }
@@ -28,6 +28,7 @@ use vulkano::{
 };
 
 use crate::{
+    audio_playback::transpose_key,
     gui::{
         window::keyboard_layout::{KeyPosition, KeyboardView},
         GuiRenderer,
@@ -35,7 +36,7 @@ use crate::{
     midi::{CakeBlock, CakeMIDIFile, CakeSignature, IntVector4},
 };
 
-use super::RenderResultData;
+use super::{NoteStyle, RenderResultData};
 
 const BUFFER_ARRAY_LEN: u64 = 256;
 
@@ -209,6 +210,8 @@ impl CakeRenderer {
         final_image: Arc<dyn ImageViewAbstract + 'static>,
         midi_file: &mut CakeMIDIFile,
         view_range: f64,
+        note_style: NoteStyle,
+        transpose: i32,
     ) -> RenderResultData {
         let img_dims = final_image.image().dimensions().width_height();
         if self.depth_buffer.image().dimensions().width_height() != img_dims {
@@ -242,13 +245,22 @@ impl CakeRenderer {
             end_time: screen_end,
             screen_width: img_dims[0] as i32,
             screen_height: img_dims[1] as i32,
+            border_width: note_style.border_width,
+            rounding: note_style.rounding,
+            brightness: note_style.brightness,
+            saturation: note_style.saturation,
+            opacity: note_style.opacity,
+            rounding_min_length: note_style.rounding_min_length,
         };
 
         let mut buffer_instances = self.buffers_init.write().unwrap();
         // Black keys first
         let mut written_instances = 0;
         for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
+            let Some(note) = transpose_key(i, transpose) else {
+                continue;
+            };
+            let key = key_view.note(note);
             if key.black {
                 buffer_instances[written_instances] = CakeNoteColumn {
                     buffer_index: i as i32,
@@ -262,7 +274,10 @@ impl CakeRenderer {
         }
         // Then white keys
         for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
+            let Some(note) = transpose_key(i, transpose) else {
+                continue;
+            };
+            let key = key_view.note(note);
             if !key.black {
                 buffer_instances[written_instances] = CakeNoteColumn {
                     buffer_index: i as i32,
@@ -353,12 +368,18 @@ impl CakeRenderer {
             .boxed();
 
         // Calculate the metadata before awaiting the future
-        // to keep this more efficient
-        let colors = midi_file
-            .key_blocks()
-            .iter()
-            .map(|block| block.get_note_at(screen_start as u32).map(|n| n.color))
-            .collect();
+        // to keep this more efficient. The cake trees only store the top note
+        // at any point in time, so there is at most 1 color per key here.
+        let mut colors = vec![Vec::new(); 256];
+        for (i, block) in midi_file.key_blocks().iter().enumerate() {
+            if let Some(key) = transpose_key(i, transpose) {
+                colors[key] = block
+                    .get_note_at(screen_start as u32)
+                    .map(|n| n.color)
+                    .into_iter()
+                    .collect();
+            }
+        }
         let rendered_notes = midi_file
             .key_blocks()
             .iter()
@@ -382,6 +403,7 @@ impl CakeRenderer {
 
         RenderResultData {
             notes_rendered: rendered_notes,
+            notes_capped: false,
             key_colors: colors,
         }
     }
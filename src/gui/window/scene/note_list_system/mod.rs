@@ -1,18 +1,19 @@
 mod notes_render_pass;
 
-use std::{cell::UnsafeCell, sync::Arc};
+use std::{cell::UnsafeCell, sync::Arc, thread};
 
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use vulkano::image::ImageViewAbstract;
 
 use crate::{
+    audio_playback::{transpose_key, DRUM_CHANNEL},
     gui::{window::keyboard_layout::KeyboardView, GuiRenderer},
     midi::{DisplacedMIDINote, MIDIColor, MIDIFile, MIDINoteColumnView, MIDINoteViews},
 };
 
 use self::notes_render_pass::{NotePassStatus, NoteRenderPass, NoteVertex};
 
-use super::RenderResultData;
+use super::{NoteStyle, RenderResultData, MAX_KEY_COLORS};
 
 pub struct NoteRenderer {
     render_pass: NoteRenderPass,
@@ -39,44 +40,87 @@ impl NoteRenderer {
     pub fn new(renderer: &GuiRenderer) -> NoteRenderer {
         NoteRenderer {
             render_pass: NoteRenderPass::new(renderer),
-            thrad_pool: rayon::ThreadPoolBuilder::new().build().unwrap(),
+            // Sized here, since RAYON_NUM_THREADS may hold XSynth's thread count
+            thrad_pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(thread::available_parallelism().map_or(1, |n| n.get()))
+                .build()
+                .unwrap(),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &mut self,
         key_view: &KeyboardView,
         final_image: Arc<dyn ImageViewAbstract + 'static>,
         midi_file: &mut impl MIDIFile,
         view_range: f64,
+        mut note_style: NoteStyle,
+        pitch_bends: [f32; 16],
+        max_notes: Option<usize>,
+        transpose: i32,
     ) -> RenderResultData {
-        let note_views = midi_file.get_current_column_views(view_range);
+        // The glow fades by wall time, so it covers more of the MIDI at higher speeds
+        note_style.glow_time *= midi_file.timer().speed() as f32;
 
-        struct ColumnViewInfo<Iter: ExactSizeIterator<Item = DisplacedMIDINote> + Send> {
+        let note_views = midi_file.get_current_column_views(view_range, note_style.show_sustain);
+
+        struct ColumnViewInfo<Iter: Iterator<Item = DisplacedMIDINote> + Send> {
             offset: usize,
             iter: Iter,
             key: u8,
             remaining: usize,
-            color: Option<MIDIColor>,
+            skip: usize,
+            colors: Vec<MIDIColor>,
         }
 
         let mut total_notes = 0;
 
         let columns: Vec<_> = (0..256).map(|i| note_views.get_column(i)).collect();
 
+        // The drums drawn in their own strip are left out before the notes are counted,
+        // so they don't take up any room in the buffer or count towards the note limit
+        let separate_drums = note_style.separate_drums;
+        let is_shown = move |note: &DisplacedMIDINote| {
+            !(separate_drums && note.channel as usize == DRUM_CHANNEL)
+        };
+
         let mut columns_view_info = Vec::new();
 
+        // Keys outside of the view are left out, unless a pitch bend could move
+        // their notes far enough to be seen
+        let reach = pitch_bends
+            .iter()
+            .fold(0.0f32, |reach, bend| reach.max(bend.abs()))
+            .ceil() as usize;
+        let is_visible = |key: usize| {
+            (key.saturating_sub(reach)..=(key + reach).min(255)).any(|key| {
+                let note = key_view.note(key);
+                note.right > 0.0 && note.left < 1.0
+            })
+        };
+
         // Add black keys first
         for (i, column) in columns.iter().enumerate() {
-            if key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
-                let length = iter.len();
+            let Some(key) = transpose_key(i, transpose).filter(|&key| is_visible(key)) else {
+                continue;
+            };
+            if key_view.key(key).black {
+                let notes = column.iterate_displaced_notes();
+                let length = if separate_drums {
+                    // Finding the drums takes another pass over the notes
+                    column.iterate_displaced_notes().filter(is_shown).count()
+                } else {
+                    notes.len()
+                };
+                let iter = notes.filter(is_shown);
                 columns_view_info.push(ColumnViewInfo {
                     offset: total_notes,
                     iter,
-                    key: i as u8,
+                    key: key as u8,
                     remaining: length,
-                    color: None,
+                    skip: 0,
+                    colors: Vec::new(),
                 });
                 total_notes += length;
             }
@@ -84,20 +128,46 @@ impl NoteRenderer {
 
         // Then white keys after
         for (i, column) in columns.iter().enumerate() {
-            if !key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
-                let length = iter.len();
+            let Some(key) = transpose_key(i, transpose).filter(|&key| is_visible(key)) else {
+                continue;
+            };
+            if !key_view.key(key).black {
+                let notes = column.iterate_displaced_notes();
+                let length = if separate_drums {
+                    // Finding the drums takes another pass over the notes
+                    column.iterate_displaced_notes().filter(is_shown).count()
+                } else {
+                    notes.len()
+                };
+                let iter = notes.filter(is_shown);
                 columns_view_info.push(ColumnViewInfo {
                     offset: total_notes,
                     iter,
-                    key: i as u8,
+                    key: key as u8,
                     remaining: length,
-                    color: None,
+                    skip: 0,
+                    colors: Vec::new(),
                 });
                 total_notes += length;
             }
         }
 
+        // The columns are iterated from the top of the screen, so when there are too
+        // many notes, the ones furthest from the keyboard are skipped
+        let capped = max_notes.map_or(false, |max_notes| total_notes > max_notes);
+        if let (true, Some(max_notes)) = (capped, max_notes) {
+            let lengths: Vec<_> = columns_view_info.iter().map(|c| c.remaining).collect();
+            let allowed = distribute_note_limit(&lengths, max_notes);
+
+            total_notes = 0;
+            for (column, allowed) in columns_view_info.iter_mut().zip(allowed) {
+                column.offset = total_notes;
+                column.skip = column.remaining - allowed;
+                column.remaining = allowed;
+                total_notes += allowed;
+            }
+        }
+
         let mut notes_pushed = 0;
 
         let mut cycle = 0;
@@ -105,7 +175,7 @@ impl NoteRenderer {
         let view_range = note_views.range().length() as f32;
 
         self.render_pass
-            .draw(final_image, key_view, view_range, |buffer| {
+            .draw(final_image, key_view, view_range, note_style, |buffer| {
                 let buffer_length = buffer.len() as usize;
 
                 let buffer_writer = UnsafeSyncCell::new(buffer.write().unwrap());
@@ -133,6 +203,11 @@ impl NoteRenderer {
                             iter_length
                         };
 
+                        if column.skip > 0 {
+                            column.iter.nth(column.skip - 1);
+                            column.skip = 0;
+                        }
+
                         unsafe {
                             let buffer = buffer_writer.get_mut();
 
@@ -144,13 +219,15 @@ impl NoteRenderer {
                                         note.len,
                                         column.key,
                                         note.color.as_u32(),
+                                        pitch_bends[note.channel as usize],
+                                        note.sustain,
                                     );
 
                                     if note.start <= 0.0
-                                        && column.color.is_none()
+                                        && column.colors.len() < MAX_KEY_COLORS
                                         && note.start + note.len > 0.0
                                     {
-                                        column.color = Some(note.color);
+                                        column.colors.push(note.color);
                                     }
                                 } else {
                                     panic!("Invalid iterator length");
@@ -179,15 +256,32 @@ impl NoteRenderer {
                 }
             });
 
-        // Sort for output metrics
-        columns_view_info.sort_unstable_by_key(|k| k.key);
+        // Transposed notes can leave some keys without a column
+        let mut key_colors = vec![Vec::new(); 256];
+        for column in columns_view_info {
+            key_colors[column.key as usize] = column.colors;
+        }
 
         RenderResultData {
             notes_rendered: notes_pushed as u64,
-            key_colors: columns_view_info
-                .iter()
-                .map(|column| column.color)
-                .collect(),
+            notes_capped: capped,
+            key_colors,
         }
     }
 }
+
+/// Splits the note limit between the columns. Columns with few notes keep all
+/// of them, and the rest of the limit is shared evenly between the others.
+fn distribute_note_limit(lengths: &[usize], limit: usize) -> Vec<usize> {
+    let mut order: Vec<_> = (0..lengths.len()).collect();
+    order.sort_unstable_by_key(|&i| lengths[i]);
+
+    let mut allowed = vec![0; lengths.len()];
+    let mut remaining = limit;
+    for (n, &i) in order.iter().enumerate() {
+        let share = remaining / (lengths.len() - n);
+        allowed[i] = lengths[i].min(share);
+        remaining -= allowed[i];
+    }
+    allowed
+}
@@ -16,6 +16,7 @@ use vulkano::{
     memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator},
     pipeline::{
         graphics::{
+            color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendState},
             depth_stencil::DepthStencilState,
             input_assembly::{InputAssemblyState, PrimitiveTopology},
             vertex_input::Vertex,
@@ -27,7 +28,10 @@ use vulkano::{
     sync::{self, future::FenceSignalFuture, GpuFuture},
 };
 
-use crate::gui::{window::keyboard_layout::KeyboardView, GuiRenderer};
+use crate::gui::{
+    window::{keyboard_layout::KeyboardView, scene::NoteStyle},
+    GuiRenderer,
+};
 
 const NOTE_BUFFER_SIZE: u64 = 25000000;
 
@@ -38,13 +42,21 @@ pub struct NoteVertex {
     pub start_length: [f32; 2],
     #[format(R32_UINT)]
     pub key_color: u32,
+    #[format(R32_SFLOAT)]
+    pub bend: f32,
+    #[format(R32_SFLOAT)]
+    pub sustain: f32,
 }
 
 impl NoteVertex {
-    pub fn new(start: f32, len: f32, key: u8, color: u32) -> Self {
+    /// `bend` is how far the note is shifted sideways, in semitones, and `sustain`
+    /// is the length of the faded tail drawn while the sustain pedal holds the note
+    pub fn new(start: f32, len: f32, key: u8, color: u32, bend: f32, sustain: f32) -> Self {
         Self {
             start_length: [start, len],
             key_color: key as u32 | (color << 8),
+            bend,
+            sustain,
         }
     }
 }
@@ -107,6 +119,9 @@ pub struct NoteRenderPass {
     buffer_set: BufferSet,
     pipeline_clear: Arc<GraphicsPipeline>,
     pipeline_draw_over: Arc<GraphicsPipeline>,
+    /// Used when the notes are see-through, so that the overlapping notes blend
+    translucent_pipeline_clear: Arc<GraphicsPipeline>,
+    translucent_pipeline_draw_over: Arc<GraphicsPipeline>,
     render_pass_clear: Arc<RenderPass>,
     render_pass_draw_over: Arc<RenderPass>,
     key_locations: Subbuffer<[[KeyPosition; 256]]>,
@@ -212,6 +227,31 @@ impl NoteRenderPass {
             .unwrap();
 
         let pipeline_draw_over = pipeline_base
+            .clone()
+            .render_pass(Subpass::from(render_pass_draw_over.clone(), 0).unwrap())
+            .build(gfx_queue.device().clone())
+            .unwrap();
+
+        // The shader writes premultiplied colors, and the depth test would stop the
+        // notes behind from showing through
+        let translucent_base = pipeline_base
+            .depth_stencil_state(DepthStencilState::disabled())
+            .color_blend_state(ColorBlendState::new(1).blend(AttachmentBlend {
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::One,
+                color_destination: BlendFactor::OneMinusSrcAlpha,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::OneMinusSrcAlpha,
+            }));
+
+        let translucent_pipeline_clear = translucent_base
+            .clone()
+            .render_pass(Subpass::from(render_pass_clear.clone(), 0).unwrap())
+            .build(gfx_queue.device().clone())
+            .unwrap();
+
+        let translucent_pipeline_draw_over = translucent_base
             .render_pass(Subpass::from(render_pass_draw_over.clone(), 0).unwrap())
             .build(gfx_queue.device().clone())
             .unwrap();
@@ -221,6 +261,8 @@ impl NoteRenderPass {
             buffer_set: BufferSet::new(&renderer.device),
             pipeline_clear,
             pipeline_draw_over,
+            translucent_pipeline_clear,
+            translucent_pipeline_draw_over,
             render_pass_clear,
             render_pass_draw_over,
             depth_buffer,
@@ -239,6 +281,7 @@ impl NoteRenderPass {
         final_image: Arc<dyn ImageViewAbstract + 'static>,
         key_view: &KeyboardView,
         view_range: f32,
+        note_style: NoteStyle,
         mut fill_buffer: impl FnMut(&Subbuffer<[NoteVertex]>) -> NotePassStatus,
     ) {
         let img_dims = final_image.image().dimensions().width_height();
@@ -271,6 +314,15 @@ impl NoteRenderPass {
 
         let mut first_pass = true;
 
+        let (pipeline_clear, pipeline_draw_over) = if note_style.opacity < 1.0 {
+            (
+                &self.translucent_pipeline_clear,
+                &self.translucent_pipeline_draw_over,
+            )
+        } else {
+            (&self.pipeline_clear, &self.pipeline_draw_over)
+        };
+
         while status == NotePassStatus::HasMoreNotes {
             let buffer = self.buffer_set.next();
 
@@ -295,13 +347,13 @@ impl NoteRenderPass {
                 first_pass = false;
                 (
                     vec![Some([0.0, 0.0, 0.0, 0.0].into()), Some(1.0f32.into())],
-                    &self.pipeline_clear,
+                    pipeline_clear,
                     &self.render_pass_clear,
                 )
             } else {
                 (
                     vec![None, None],
-                    &self.pipeline_draw_over,
+                    pipeline_draw_over,
                     &self.render_pass_draw_over,
                 )
             };
@@ -339,6 +391,17 @@ impl NoteRenderPass {
                 height_time: view_range,
                 win_width: img_dims[0] as f32,
                 win_height: img_dims[1] as f32,
+                border_width: note_style.border_width,
+                rounding: note_style.rounding,
+                brightness: note_style.brightness,
+                saturation: note_style.saturation,
+                min_note_height: note_style.min_note_height,
+                gradient: note_style.gradient,
+                opacity: note_style.opacity,
+                attack_glow: note_style.attack_glow,
+                glow_time: note_style.glow_time,
+                edge_fade: note_style.edge_fade,
+                rounding_min_length: note_style.rounding_min_length,
             };
 
             command_buffer_builder
@@ -368,7 +431,7 @@ impl NoteRenderPass {
             if let Some(prev_future) = prev_future.take() {
                 match prev_future.wait(None) {
                     Ok(x) => x,
-                    Err(err) => println!("err: {err:?}"),
+                    Err(err) => log::error!("Failed to wait for the notes to draw: {err:?}"),
                 }
             }
 
@@ -388,7 +451,7 @@ impl NoteRenderPass {
         if let Some(prev_future) = prev_future {
             match prev_future.wait(None) {
                 Ok(x) => x,
-                Err(err) => println!("err: {err:?}"),
+                Err(err) => log::error!("Failed to wait for the notes to draw: {err:?}"),
             }
         }
     }
@@ -408,13 +471,19 @@ mod vs {
 #version 450
 layout(location = 0) in vec2 start_length;
 layout(location = 1) in uint key_color;
+layout(location = 2) in float bend;
+layout(location = 3) in float sustain;
 
 layout(location = 0) out vec2 v_start_length;
 layout(location = 1) out uint v_key_color;
+layout(location = 2) out float v_bend;
+layout(location = 3) out float v_sustain;
 
 void main() {
     v_start_length = start_length;
     v_key_color = key_color;
+    v_bend = bend;
+    v_sustain = sustain;
 }"
     }
 }
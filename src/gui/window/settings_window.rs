@@ -1,179 +1,1119 @@
-use egui::Context;
+use egui::{Context, Ui};
 
-use std::ops::RangeInclusive;
+use std::{ops::RangeInclusive, path::Path};
 
 use crate::{
-    audio_playback::{
-        xsynth::{convert_to_channel_init, convert_to_sf_init},
-        AudioPlayerType,
+    audio_playback::{kdmapi_available, AudioPlayerType},
+    gui::window::{channels::GM_INSTRUMENTS, shortcuts, xsynth_settings, GuiWasabiWindow},
+    settings::{
+        keybindings::shortcuts, note_speed_from_factor, note_speed_to_factor, CCMapping, CCTarget,
+        ColorBy, ColorPalette, KeyColorBlend, KeyRangePreset, MidiLoading, MidiSettings,
+        NoteSpeedUnit, OverlayCorner, StatsNumberFormat, Synth, UiTheme, WasabiSettings,
+        NOTE_SPEED_FACTOR_RANGE, TEMPO_OVERRIDE_RANGE, UI_SCALE_RANGE,
     },
-    gui::window::GuiWasabiWindow,
-    settings::{MidiLoading, Synth, WasabiSettings},
     state::WasabiState,
 };
 
+/// The smallest and largest buffer that can be picked with the slider
+const BUFFER_MS_RANGE: RangeInclusive<f64> = 1.0..=200.0;
+
+/// Buffers below this are likely to underrun on most systems
+const LOW_BUFFER_MS: f64 = 5.0;
+
+const NOTE_SPEED_HOVER: &str = "How fast the notes move. The speed factor goes from 0 to 2, \
+    higher is faster, and is what `--note-speed` takes. The notes take 2 minus the factor \
+    in seconds to cross the screen.";
+
+/// Draws a slider and a number box for the note speed, in the unit picked in the settings
+pub fn draw_note_speed(ui: &mut Ui, midi: &mut MidiSettings) {
+    match midi.note_speed_unit {
+        NoteSpeedUnit::Factor => {
+            // Only written back when changed, so the conversion doesn't drift the value
+            let mut factor = note_speed_to_factor(midi.note_speed);
+            let slider = ui
+                .add(egui::Slider::new(&mut factor, NOTE_SPEED_FACTOR_RANGE).show_value(false))
+                .on_hover_text(NOTE_SPEED_HOVER);
+            let value = ui
+                .add(
+                    egui::DragValue::new(&mut factor)
+                        .clamp_range(NOTE_SPEED_FACTOR_RANGE)
+                        .speed(0.01)
+                        .max_decimals(4),
+                )
+                .on_hover_text(NOTE_SPEED_HOVER);
+            if slider.changed() || value.changed() {
+                midi.note_speed = note_speed_from_factor(factor);
+            }
+        }
+        NoteSpeedUnit::Seconds => {
+            let range = note_speed_from_factor(*NOTE_SPEED_FACTOR_RANGE.end())
+                ..=note_speed_from_factor(*NOTE_SPEED_FACTOR_RANGE.start());
+            ui.add(
+                egui::Slider::new(&mut midi.note_speed, *range.end()..=*range.start())
+                    .show_value(false),
+            )
+            .on_hover_text(NOTE_SPEED_HOVER);
+            ui.add(
+                egui::DragValue::new(&mut midi.note_speed)
+                    .clamp_range(range)
+                    .speed(0.01)
+                    .max_decimals(4)
+                    .suffix(" s"),
+            )
+            .on_hover_text(NOTE_SPEED_HOVER);
+        }
+    }
+}
+
+/// Draws a row for each controller mapping, with a button to add more
+fn draw_cc_mappings(ui: &mut Ui, mappings: &mut Vec<CCMapping>) {
+    let mut remove = None;
+    for (i, mapping) in mappings.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.small_button("✖").clicked() {
+                remove = Some(i);
+            }
+
+            ui.add(
+                egui::DragValue::new(&mut mapping.controller)
+                    .clamp_range(0..=127)
+                    .prefix("CC "),
+            );
+
+            egui::ComboBox::from_id_source(("cc_mapping_channel", i))
+                .selected_text(match mapping.channel {
+                    Some(channel) => format!("Channel {}", channel + 1),
+                    None => "Any Channel".to_string(),
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut mapping.channel, None, "Any Channel");
+                    for channel in 0..16 {
+                        ui.selectable_value(
+                            &mut mapping.channel,
+                            Some(channel),
+                            format!("Channel {}", channel + 1),
+                        );
+                    }
+                });
+
+            egui::ComboBox::from_id_source(("cc_mapping_target", i))
+                .selected_text(mapping.target.as_str())
+                .show_ui(ui, |ui| {
+                    for target in [
+                        CCTarget::BackgroundBrightness,
+                        CCTarget::NoteBrightness,
+                        CCTarget::NoteSaturation,
+                        CCTarget::NoteOpacity,
+                    ] {
+                        ui.selectable_value(&mut mapping.target, target, target.as_str());
+                    }
+                });
+        });
+    }
+    if let Some(i) = remove {
+        mappings.remove(i);
+    }
+
+    if ui.button("Add").clicked() {
+        mappings.push(CCMapping {
+            controller: 1,
+            ..Default::default()
+        });
+    }
+}
+
+/// Lays out the control of a setting that some MIDI loading modes don't show. While
+/// one of them is picked, the control is greyed out with a note next to it saying so.
+fn loading_mode_setting(
+    ui: &mut Ui,
+    not_shown_by: Option<&str>,
+    add_contents: impl FnOnce(&mut Ui),
+) {
+    ui.horizontal(|ui| {
+        ui.add_enabled_ui(not_shown_by.is_none(), add_contents);
+        if let Some(mode) = not_shown_by {
+            ui.label(egui::RichText::new(format!("Not shown by {mode} loading")).weak());
+        }
+    });
+}
+
 pub fn draw_settings(
     win: &mut GuiWasabiWindow,
     settings: &mut WasabiSettings,
     state: &mut WasabiState,
     ctx: &Context,
 ) {
-    egui::Window::new("Settings")
+    // Cake loading draws every key in a single pass, without the details of each note
+    let not_shown_by_cake = (settings.midi.midi_loading == MidiLoading::Cake).then_some("Cake");
+
+    let shown = egui::Window::new("Settings")
         .resizable(true)
         .collapsible(true)
         .title_bar(true)
-        .scroll2([false, true])
         .enabled(true)
         .open(&mut state.settings_visible)
         .show(ctx, |ui| {
             let col_width = 160.0;
 
-            // Synth settings section
-            ui.heading("Synth");
-            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                // Synth settings section
+                egui::CollapsingHeader::new(egui::RichText::new("Synth").heading())
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new("synth_settings_grid")
+                            .num_columns(2)
+                            .spacing([40.0, 4.0])
+                            .min_col_width(col_width)
+                            .show(ui, |ui| {
+                                ui.label("Synth: ");
+                                let synth_prev = settings.synth.synth;
+                                egui::ComboBox::from_id_source("synth_select")
+                                    .selected_text(settings.synth.synth.as_str())
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut settings.synth.synth,
+                                            Synth::XSynth,
+                                            "XSynth",
+                                        );
+                                        let kdmapi = ui
+                                            .add_enabled(
+                                                kdmapi_available(),
+                                                egui::SelectableLabel::new(
+                                                    settings.synth.synth == Synth::Kdmapi,
+                                                    "KDMAPI",
+                                                ),
+                                            )
+                                            .on_disabled_hover_text(
+                                                "KDMAPI needs OmniMIDI, which is only \
+                                                available on Windows",
+                                            );
+                                        if kdmapi.clicked() {
+                                            settings.synth.synth = Synth::Kdmapi;
+                                        }
+                                    });
+                                if settings.synth.synth != synth_prev {
+                                    match settings.synth.synth {
+                                        Synth::Kdmapi => {
+                                            win.synth
+                                                .write()
+                                                .unwrap()
+                                                .switch_player(AudioPlayerType::Kdmapi);
+                                        }
+                                        Synth::XSynth => {
+                                            xsynth_settings::reload_xsynth(win, settings);
+                                        }
+                                    }
+                                }
+                                ui.end_row();
 
-            egui::Grid::new("synth_settings_grid")
-                .num_columns(2)
-                .spacing([40.0, 4.0])
-                .min_col_width(col_width)
-                .show(ui, |ui| {
-                    ui.label("Synth: ");
-                    let synth_prev = settings.synth.synth;
-                    egui::ComboBox::from_id_source("synth_select")
-                        .selected_text(settings.synth.synth.as_str())
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut settings.synth.synth, Synth::XSynth, "XSynth");
-                            ui.selectable_value(&mut settings.synth.synth, Synth::Kdmapi, "KDMAPI");
-                        });
-                    if settings.synth.synth != synth_prev {
-                        match settings.synth.synth {
-                            Synth::Kdmapi => {
-                                win.synth
-                                    .write()
-                                    .unwrap()
-                                    .switch_player(AudioPlayerType::Kdmapi);
-                            }
-                            Synth::XSynth => {
-                                win.synth
-                                    .write()
-                                    .unwrap()
-                                    .switch_player(AudioPlayerType::XSynth {
-                                        buffer: settings.synth.buffer_ms,
-                                        use_threadpool: settings.synth.use_threadpool,
-                                        ignore_range: settings.synth.vel_ignore.clone(),
-                                        options: convert_to_channel_init(settings),
+                                ui.label("Buffer Size: ");
+                                let is_xsynth = settings.synth.synth == Synth::XSynth;
+                                ui.add_enabled_ui(is_xsynth, |ui| {
+                                    ui.vertical(|ui| {
+                                        let response = ui.add(
+                                            egui::Slider::new(
+                                                &mut settings.synth.buffer_ms,
+                                                BUFFER_MS_RANGE,
+                                            )
+                                            .logarithmic(true)
+                                            .suffix(" ms"),
+                                        );
+                                        // Reopening the stream is slow, wait for the drag to end
+                                        if response.drag_released()
+                                            || (response.changed() && !response.dragged())
+                                        {
+                                            xsynth_settings::reload_xsynth(win, settings);
+                                        }
+
+                                        if !is_xsynth {
+                                            ui.label("KDMAPI uses its own buffer");
+                                        } else if settings.synth.buffer_ms < LOW_BUFFER_MS {
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(255, 170, 0),
+                                                format!(
+                                                    "≈{:.0} ms latency, the audio may crackle",
+                                                    settings.synth.buffer_ms
+                                                ),
+                                            );
+                                        } else {
+                                            ui.label(format!(
+                                                "≈{:.0} ms latency",
+                                                settings.synth.buffer_ms
+                                            ));
+                                        }
                                     });
-                                win.synth.write().unwrap().set_soundfont(
-                                    &settings.synth.sfz_path,
-                                    convert_to_sf_init(settings),
+                                });
+                                ui.end_row();
+
+                                ui.label("Stop Fade: ");
+                                let fade = ui
+                                    .add(
+                                        egui::DragValue::new(&mut settings.synth.fade_ms)
+                                            .speed(0.5)
+                                            .clamp_range(0.0..=50.0)
+                                            .suffix(" ms"),
+                                    )
+                                    .on_hover_text(
+                                        "Fades the notes out when pausing or seeking \
+                                        to avoid clicks, 0 stops them instantly",
+                                    );
+                                if fade.changed() {
+                                    win.synth
+                                        .write()
+                                        .unwrap()
+                                        .set_fade_ms(settings.synth.fade_ms);
+                                }
+                                ui.end_row();
+
+                                ui.label("Force Instrument: ").on_hover_text(
+                                    "Plays every channel except the drums with one instrument",
                                 );
-                                win.synth.write().unwrap().set_layer_count(
-                                    match settings.synth.layer_count {
-                                        0 => None,
-                                        _ => Some(settings.synth.layer_count),
-                                    },
+                                let force_program_prev = settings.synth.force_program;
+                                ui.horizontal(|ui| {
+                                    let mut forced = settings.synth.force_program.is_some();
+                                    ui.checkbox(&mut forced, "");
+                                    match (forced, settings.synth.force_program.as_mut()) {
+                                        (true, Some(program)) => {
+                                            egui::ComboBox::from_id_source("force_program_select")
+                                                .selected_text(
+                                                    GM_INSTRUMENTS
+                                                        .get(*program as usize)
+                                                        .copied()
+                                                        .unwrap_or("Unknown"),
+                                                )
+                                                .show_ui(ui, |ui| {
+                                                    for (i, name) in
+                                                        GM_INSTRUMENTS.iter().enumerate()
+                                                    {
+                                                        ui.selectable_value(program, i as u8, *name);
+                                                    }
+                                                });
+                                        }
+                                        (true, None) => settings.synth.force_program = Some(0),
+                                        (false, _) => settings.synth.force_program = None,
+                                    }
+                                });
+                                if settings.synth.force_program != force_program_prev {
+                                    win.synth
+                                        .write()
+                                        .unwrap()
+                                        .set_force_program(settings.synth.force_program);
+                                }
+                                ui.end_row();
+
+                                ui.label("Configure:");
+                                if ui.button("Open Synth Settings").clicked() {
+                                    state.xsynth_settings_visible = true;
+                                }
+                                ui.end_row();
+                            });
+                    });
+
+                // MIDI settings section
+                egui::CollapsingHeader::new(egui::RichText::new("MIDI").heading())
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new("midi_settings_grid")
+                            .num_columns(2)
+                            .spacing([40.0, 4.0])
+                            .min_col_width(col_width)
+                            .show(ui, |ui| {
+                                ui.label("Note speed: ").on_hover_text(NOTE_SPEED_HOVER);
+                                ui.spacing_mut().slider_width = 150.0;
+                                ui.horizontal(|ui| draw_note_speed(ui, &mut settings.midi));
+                                ui.end_row();
+
+                                ui.label("Note Speed Unit: ");
+                                egui::ComboBox::from_id_source("note_speed_unit_select")
+                                    .selected_text(settings.midi.note_speed_unit.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for unit in [NoteSpeedUnit::Factor, NoteSpeedUnit::Seconds]
+                                        {
+                                            ui.selectable_value(
+                                                &mut settings.midi.note_speed_unit,
+                                                unit,
+                                                unit.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label("Note Lookahead: ").on_hover_text(
+                                    "How long before reaching the keyboard the notes appear",
                                 );
-                            }
-                        }
-                    }
-                    ui.end_row();
+                                ui.horizontal(|ui| {
+                                    let mut limited = settings.midi.lookahead_seconds.is_some();
+                                    ui.checkbox(&mut limited, "");
+                                    match (limited, settings.midi.lookahead_seconds.as_mut()) {
+                                        (true, Some(lookahead)) => {
+                                            ui.add(
+                                                egui::DragValue::new(lookahead)
+                                                    .clamp_range(0.01..=2.0)
+                                                    .speed(0.01)
+                                                    .suffix(" s"),
+                                            );
+                                        }
+                                        (true, None) => {
+                                            settings.midi.lookahead_seconds =
+                                                Some(settings.midi.note_speed / 2.0)
+                                        }
+                                        (false, _) => settings.midi.lookahead_seconds = None,
+                                    }
+                                });
+                                ui.end_row();
 
-                    ui.label("Configure:");
-                    if ui.button("Open Synth Settings").clicked() {
-                        state.xsynth_settings_visible = true;
-                    }
-                    ui.end_row();
-                });
+                                ui.label("Fast Forward Speed: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut settings.midi.fast_forward_speed)
+                                        .speed(0.1)
+                                        .clamp_range(1.0..=50.0)
+                                        .suffix("x"),
+                                );
+                                ui.end_row();
 
-            // MIDI settings section
-            ui.add_space(6.0);
-            ui.heading("MIDI");
-            ui.separator();
+                                ui.label("Seek On Release: ").on_hover_text(
+                                    "Only seek once the seek bar is let go, which keeps dragging it smooth on huge MIDIs",
+                                );
+                                ui.checkbox(&mut settings.midi.seek_on_release, "");
+                                ui.end_row();
 
-            egui::Grid::new("midi_settings_grid")
-                .num_columns(2)
-                .spacing([40.0, 4.0])
-                .min_col_width(col_width)
-                .show(ui, |ui| {
-                    ui.label("Note speed: ");
-                    ui.spacing_mut().slider_width = 150.0;
-                    ui.add(egui::Slider::new(
-                        &mut settings.midi.note_speed,
-                        2.0..=0.001,
-                    ));
-                    ui.end_row();
-
-                    ui.label("Random Track Colors*: ");
-                    ui.checkbox(&mut settings.midi.random_colors, "");
-                    ui.end_row();
-
-                    ui.label("Keyboard Range: ");
-                    let mut firstkey = *settings.midi.key_range.start();
-                    let mut lastkey = *settings.midi.key_range.end();
-                    ui.horizontal(|ui| {
-                        ui.add(
-                            egui::DragValue::new(&mut firstkey)
-                                .speed(1)
-                                .clamp_range(RangeInclusive::new(0, 253)),
-                        );
-                        ui.add(
-                            egui::DragValue::new(&mut lastkey)
-                                .speed(1)
-                                .clamp_range(RangeInclusive::new(firstkey + 1, 254)),
-                        );
+                                ui.label("Transpose: ");
+                                let transpose = ui.add(
+                                    egui::Slider::new(&mut settings.midi.transpose, -24..=24)
+                                        .suffix(" semitones"),
+                                );
+                                if transpose.changed() {
+                                    win.synth
+                                        .write()
+                                        .unwrap()
+                                        .set_transpose(settings.midi.transpose);
+                                }
+                                ui.end_row();
+
+                                ui.label("Tempo Override*: ").on_hover_text(
+                                    "Play the MIDI at a fixed BPM, ignoring its tempo changes",
+                                );
+                                ui.horizontal(|ui| {
+                                    let mut overridden = settings.midi.tempo_override.is_some();
+                                    ui.checkbox(&mut overridden, "");
+                                    match (overridden, settings.midi.tempo_override.as_mut()) {
+                                        (true, Some(bpm)) => {
+                                            ui.add(
+                                                egui::DragValue::new(bpm)
+                                                    .clamp_range(TEMPO_OVERRIDE_RANGE)
+                                                    .speed(0.5)
+                                                    .suffix(" BPM"),
+                                            );
+                                        }
+                                        (true, None) => settings.midi.tempo_override = Some(120.0),
+                                        (false, _) => settings.midi.tempo_override = None,
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Layer Channel Offset*: ").on_hover_text(
+                                    "How many channels each MIDI layer is moved over by. The drums stay on channel 10",
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut settings.midi.layer_channel_offset)
+                                        .speed(1)
+                                        .clamp_range(0..=14),
+                                );
+                                ui.end_row();
+
+                                ui.label("Random Track Colors*: ");
+                                ui.checkbox(&mut settings.midi.random_colors, "");
+                                ui.end_row();
+
+                                ui.label("Color Palette: ")
+                                    .on_hover_text("Not used with random track colors");
+                                let palette_prev = settings.midi.color_palette;
+                                ui.add_enabled_ui(!settings.midi.random_colors, |ui| {
+                                    egui::ComboBox::from_id_source("color_palette_select")
+                                        .selected_text(settings.midi.color_palette.as_str())
+                                        .show_ui(ui, |ui| {
+                                            for palette in [
+                                                ColorPalette::Default,
+                                                ColorPalette::Colorblind,
+                                                ColorPalette::Grayscale,
+                                                ColorPalette::Pastel,
+                                            ] {
+                                                ui.selectable_value(
+                                                    &mut settings.midi.color_palette,
+                                                    palette,
+                                                    palette.as_str(),
+                                                );
+                                            }
+                                        });
+                                });
+                                ui.end_row();
+
+                                ui.label("Color By: ").on_hover_text(
+                                    "Whether each track, each channel or both get their own color",
+                                );
+                                let color_by_prev = settings.midi.color_by;
+                                egui::ComboBox::from_id_source("color_by_select")
+                                    .selected_text(settings.midi.color_by.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for color_by in
+                                            [ColorBy::Both, ColorBy::Track, ColorBy::Channel]
+                                        {
+                                            ui.selectable_value(
+                                                &mut settings.midi.color_by,
+                                                color_by,
+                                                color_by.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                let recolor = settings.midi.color_palette != palette_prev
+                                    || settings.midi.color_by != color_by_prev;
+                                if recolor {
+                                    win.recolor(&settings.midi);
+                                }
+
+                                ui.label("Color File: ").on_hover_text(
+                                    "A JSON or TOML file with colors for specific tracks and channels",
+                                );
+                                ui.horizontal(|ui| {
+                                    let name = settings
+                                        .midi
+                                        .color_file
+                                        .as_deref()
+                                        .and_then(|path| Path::new(path).file_name())
+                                        .and_then(|name| name.to_str())
+                                        .unwrap_or("None");
+                                    ui.label(name);
+
+                                    if ui.button("Import...").clicked() {
+                                        win.import_colors_dialog(settings);
+                                    }
+
+                                    if ui.button("Clear").clicked() {
+                                        win.clear_colors(settings);
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Keyboard Range: ");
+                                let mut firstkey = *settings.midi.key_range.start();
+                                let mut lastkey = *settings.midi.key_range.end();
+                                ui.horizontal(|ui| {
+                                    let first = ui.add(
+                                        egui::DragValue::new(&mut firstkey)
+                                            .speed(1)
+                                            .clamp_range(RangeInclusive::new(0, 253)),
+                                    );
+                                    let last = ui.add(
+                                        egui::DragValue::new(&mut lastkey)
+                                            .speed(1)
+                                            .clamp_range(RangeInclusive::new(1, 254)),
+                                    );
+
+                                    // Push the other end along instead of letting the range invert
+                                    if first.changed() && firstkey >= lastkey {
+                                        lastkey = firstkey + 1;
+                                    }
+                                    if last.changed() && lastkey <= firstkey {
+                                        firstkey = lastkey - 1;
+                                    }
+
+                                    let current = KeyRangePreset::find(&(firstkey..=lastkey));
+                                    for preset in KeyRangePreset::ALL {
+                                        let button = ui.selectable_label(
+                                            current == Some(preset),
+                                            preset.as_str(),
+                                        );
+                                        if button.clicked() {
+                                            firstkey = *preset.range().start();
+                                            lastkey = *preset.range().end();
+                                        }
+                                    }
+                                });
+                                ui.end_row();
+                                if firstkey != *settings.midi.key_range.start()
+                                    || lastkey != *settings.midi.key_range.end()
+                                {
+                                    settings.midi.key_range = firstkey..=lastkey;
+                                }
+
+                                ui.label("Follow Active Keys: ").on_hover_text(
+                                    "Zooms the keyboard in on the keys with notes on screen",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.checkbox(&mut settings.midi.follow_active_keys, "");
+                                });
+                                ui.end_row();
+
+                                ui.label("Resume Last MIDI: ").on_hover_text(
+                                    "Open the last opened MIDI on launch, \
+                                    when no MIDI is given on the command line",
+                                );
+                                ui.checkbox(&mut settings.midi.resume_last, "");
+                                ui.end_row();
+
+                                ui.label("MIDI Loading*: ");
+                                egui::ComboBox::from_id_source("midiload_select")
+                                    .selected_text(settings.midi.midi_loading.as_str())
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut settings.midi.midi_loading,
+                                            MidiLoading::Ram,
+                                            "In RAM",
+                                        );
+                                        ui.selectable_value(
+                                            &mut settings.midi.midi_loading,
+                                            MidiLoading::Live,
+                                            "Live",
+                                        );
+                                        ui.selectable_value(
+                                            &mut settings.midi.midi_loading,
+                                            MidiLoading::Cake,
+                                            "Cake",
+                                        );
+                                    });
+                            });
                     });
-                    ui.end_row();
-                    if firstkey != *settings.midi.key_range.start()
-                        || lastkey != *settings.midi.key_range.end()
-                    {
-                        settings.midi.key_range = firstkey..=lastkey;
-                    }
 
-                    ui.label("MIDI Loading*: ");
-                    egui::ComboBox::from_id_source("midiload_select")
-                        .selected_text(settings.midi.midi_loading.as_str())
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(
-                                &mut settings.midi.midi_loading,
-                                MidiLoading::Ram,
-                                "In RAM",
-                            );
-                            ui.selectable_value(
-                                &mut settings.midi.midi_loading,
-                                MidiLoading::Live,
-                                "Live",
-                            );
-                            ui.selectable_value(
-                                &mut settings.midi.midi_loading,
-                                MidiLoading::Cake,
-                                "Cake",
-                            );
-                        });
-                });
+                // Visual settings section
+                egui::CollapsingHeader::new(egui::RichText::new("Visual").heading())
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new("visual_settings_grid")
+                            .num_columns(2)
+                            .spacing([40.0, 4.0])
+                            .min_col_width(col_width)
+                            .show(ui, |ui| {
+                                if ui.button("Toggle Fullscreen").clicked() {
+                                    state.fullscreen = true;
+                                }
+                                ui.end_row();
 
-            // Visual settings section
-            ui.add_space(6.0);
-            ui.heading("Visual");
-            ui.separator();
+                                ui.label("UI Scale: ");
+                                ui.add(
+                                    egui::Slider::new(
+                                        &mut settings.visual.ui_scale,
+                                        UI_SCALE_RANGE,
+                                    )
+                                    .step_by(0.25)
+                                    .suffix("x"),
+                                );
+                                ui.end_row();
 
-            egui::Grid::new("visual_settings_grid")
-                .num_columns(2)
-                .spacing([40.0, 4.0])
-                .min_col_width(col_width)
-                .show(ui, |ui| {
-                    if ui.button("Toggle Fullscreen").clicked() {
-                        state.fullscreen = true;
-                    }
-                    ui.end_row();
+                                ui.label("Theme: ");
+                                egui::ComboBox::from_id_source("theme_select")
+                                    .selected_text(settings.visual.theme.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for theme in
+                                            [UiTheme::Dark, UiTheme::Light, UiTheme::System]
+                                        {
+                                            ui.selectable_value(
+                                                &mut settings.visual.theme,
+                                                theme,
+                                                theme.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
 
-                    ui.label("Background Color: ");
-                    ui.color_edit_button_srgba(&mut settings.visual.bg_color);
-                    ui.end_row();
+                                ui.label("Background Color: ");
+                                ui.color_edit_button_srgba(&mut settings.visual.bg_color);
+                                ui.end_row();
 
-                    ui.label("Bar Color: ");
-                    ui.color_edit_button_srgba(&mut settings.visual.bar_color);
-                    ui.end_row();
-                });
+                                ui.label("Background Gradient: ").on_hover_text(
+                                    "Fade the background into a second color towards the bottom",
+                                );
+                                ui.horizontal(|ui| {
+                                    let mut gradient = settings.visual.bg_color_bottom.is_some();
+                                    ui.checkbox(&mut gradient, "");
+                                    match (gradient, settings.visual.bg_color_bottom.as_mut()) {
+                                        (true, Some(color)) => {
+                                            ui.color_edit_button_srgba(color);
+                                        }
+                                        (true, None) => {
+                                            settings.visual.bg_color_bottom =
+                                                Some(settings.visual.bg_color)
+                                        }
+                                        (false, _) => settings.visual.bg_color_bottom = None,
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Background Image: ");
+                                ui.horizontal(|ui| {
+                                    let name = settings
+                                        .visual
+                                        .bg_image
+                                        .as_deref()
+                                        .and_then(|path| Path::new(path).file_name())
+                                        .and_then(|name| name.to_str())
+                                        .unwrap_or("None");
+                                    ui.label(name);
+
+                                    if ui.button("Browse...").clicked() {
+                                        let image_path = rfd::FileDialog::new()
+                                            .add_filter(
+                                                "image",
+                                                &["png", "jpg", "jpeg", "bmp", "gif"],
+                                            )
+                                            .pick_file();
+
+                                        if let Some(image_path) = image_path {
+                                            if let Ok(path) =
+                                                image_path.into_os_string().into_string()
+                                            {
+                                                settings.visual.bg_image = Some(path);
+                                            }
+                                        }
+                                    }
+
+                                    if ui.button("Clear").clicked() {
+                                        settings.visual.bg_image = None;
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Bar Color: ");
+                                ui.color_edit_button_srgba(&mut settings.visual.bar_color);
+                                ui.end_row();
+
+                                ui.label("Bar Height: ").on_hover_text(
+                                    "The bar scales with the keyboard when this is off",
+                                );
+                                ui.horizontal(|ui| {
+                                    let mut fixed = settings.visual.bar_height_px.is_some();
+                                    ui.checkbox(&mut fixed, "");
+                                    match (fixed, settings.visual.bar_height_px.as_mut()) {
+                                        (true, Some(height)) => {
+                                            ui.add(
+                                                egui::DragValue::new(height)
+                                                    .clamp_range(0.0..=100.0)
+                                                    .suffix(" px"),
+                                            );
+                                        }
+                                        (true, None) => settings.visual.bar_height_px = Some(5.0),
+                                        (false, _) => settings.visual.bar_height_px = None,
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Bar Gradient: ");
+                                ui.checkbox(&mut settings.visual.bar_gradient, "");
+                                ui.end_row();
+
+                                ui.label("Bar Border: ");
+                                ui.checkbox(&mut settings.visual.bar_border, "");
+                                ui.end_row();
+
+                                ui.label("Note Outline: ");
+                                ui.checkbox(&mut settings.visual.note_outline, "");
+                                ui.end_row();
+
+                                ui.label("Note Rounding: ");
+                                ui.add(
+                                    egui::Slider::new(
+                                        &mut settings.visual.note_rounding,
+                                        0.0..=10.0,
+                                    )
+                                    .suffix(" px"),
+                                );
+                                ui.end_row();
+
+                                ui.label("Min Rounded Length: ").on_hover_text(
+                                    "Notes shorter than this keep square corners, so short notes don't turn into circles",
+                                );
+                                ui.add_enabled(
+                                    settings.visual.note_rounding > 0.0,
+                                    egui::Slider::new(
+                                        &mut settings.visual.rounding_min_length_px,
+                                        0.0..=32.0,
+                                    )
+                                    .suffix(" px"),
+                                );
+                                ui.end_row();
+
+                                ui.label("Minimum Note Height: ");
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut settings.visual.min_note_height_px,
+                                            0.0..=10.0,
+                                        )
+                                        .suffix(" px"),
+                                    );
+                                });
+                                ui.end_row();
+
+                                ui.label("Note Gradient: ");
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.checkbox(&mut settings.visual.note_gradient, "");
+                                    ui.add_enabled(
+                                        settings.visual.note_gradient,
+                                        egui::Slider::new(
+                                            &mut settings.visual.gradient_strength,
+                                            0.0..=1.0,
+                                        )
+                                        .text("Strength"),
+                                    );
+                                });
+                                ui.end_row();
+
+                                ui.label("Note Opacity: ").on_hover_text(
+                                    "Lets the overlapping notes and the background show through the notes",
+                                );
+                                ui.add(egui::Slider::new(
+                                    &mut settings.visual.note_opacity,
+                                    0.0..=1.0,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Attack Glow: ").on_hover_text(
+                                    "Lights up the notes for a moment as they reach the keyboard",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.add(egui::Slider::new(
+                                        &mut settings.visual.attack_glow,
+                                        0.0..=1.0,
+                                    ));
+                                });
+                                ui.end_row();
+
+                                ui.label("Edge Fade: ").on_hover_text(
+                                    "How wide the fade is on notes cut off by the sides of the view",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut settings.visual.edge_fade_px,
+                                            0.0..=64.0,
+                                        )
+                                        .suffix(" px"),
+                                    );
+                                });
+                                ui.end_row();
+
+                                ui.label("CC Mappings: ").on_hover_text(
+                                    "Scale visual settings by the last value of a MIDI controller, \
+                                    e.g. CC 1 (the mod wheel) for the note brightness. \
+                                    A value of 127 leaves the setting unchanged.",
+                                );
+                                ui.vertical(|ui| draw_cc_mappings(ui, &mut settings.visual.cc_mappings));
+                                ui.end_row();
+
+                                ui.label("Show Sustain Pedal: ").on_hover_text(
+                                    "Draws a tail on the notes held by the sustain pedal",
+                                );
+                                // Live loading only sees the pedal once the notes are parsed
+                                let not_shown_by_ram_only = match settings.midi.midi_loading {
+                                    MidiLoading::Ram => None,
+                                    MidiLoading::Live => Some("Live"),
+                                    MidiLoading::Cake => Some("Cake"),
+                                };
+                                loading_mode_setting(ui, not_shown_by_ram_only, |ui| {
+                                    ui.checkbox(&mut settings.visual.show_sustain, "");
+                                });
+                                ui.end_row();
+
+                                ui.label("Offset Notes by Pan: ").on_hover_text(
+                                    "Moves the notes of panned channels slightly to their side",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.checkbox(&mut settings.visual.pan_notes, "");
+                                });
+                                ui.end_row();
+
+                                ui.label("Separate Drums: ").on_hover_text(
+                                    "Show channel 10 in a strip of drum names instead of as notes",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.checkbox(&mut settings.visual.separate_drums, "");
+                                });
+                                ui.end_row();
+
+                                ui.label("Note Tooltips: ").on_hover_text(
+                                    "Show the details of the note under the cursor while paused",
+                                );
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    ui.checkbox(&mut settings.visual.note_tooltips, "");
+                                });
+                                ui.end_row();
+
+                                ui.label("Rendered Note Limit: ");
+                                loading_mode_setting(ui, not_shown_by_cake, |ui| {
+                                    let mut limited = settings.visual.max_rendered_notes.is_some();
+                                    ui.checkbox(&mut limited, "");
+                                    match (limited, settings.visual.max_rendered_notes.as_mut()) {
+                                        (true, Some(max_notes)) => {
+                                            ui.add(
+                                                egui::DragValue::new(max_notes)
+                                                    .clamp_range(1000..=100_000_000)
+                                                    .speed(1000),
+                                            );
+                                        }
+                                        (true, None) => {
+                                            settings.visual.max_rendered_notes = Some(1_000_000)
+                                        }
+                                        (false, _) => settings.visual.max_rendered_notes = None,
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Note Brightness: ");
+                                ui.add(egui::Slider::new(
+                                    &mut settings.visual.brightness,
+                                    0.25..=2.0,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Note Saturation: ");
+                                ui.add(egui::Slider::new(
+                                    &mut settings.visual.saturation,
+                                    0.0..=2.0,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Key Color Blending: ");
+                                egui::ComboBox::from_id_source("key_color_blend_select")
+                                    .selected_text(settings.visual.key_color_blend.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for blend in [
+                                            KeyColorBlend::Off,
+                                            KeyColorBlend::Average,
+                                            KeyColorBlend::Additive,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut settings.visual.key_color_blend,
+                                                blend,
+                                                blend.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label("Flash on Beats: ");
+                                ui.checkbox(&mut settings.visual.beat_flash, "");
+                                ui.end_row();
+
+                                ui.label("VSync: ")
+                                    .on_hover_text("Applies after restarting wasabi");
+                                ui.checkbox(&mut settings.visual.vsync, "");
+                                ui.end_row();
+
+                                ui.label("Keep Screen Awake: ")
+                                    .on_hover_text("Stops the screen from going to sleep while a MIDI is playing");
+                                ui.checkbox(&mut settings.visual.inhibit_sleep, "");
+                                ui.end_row();
+
+                                ui.label("Show Channel Lights: ")
+                                    .on_hover_text("Light up each channel while it has notes playing");
+                                ui.checkbox(&mut settings.visual.show_channel_leds, "");
+                                ui.end_row();
+
+                                ui.label("Show FPS Counter: ");
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut settings.visual.show_fps, "");
+                                    ui.add_enabled_ui(settings.visual.show_fps, |ui| {
+                                        egui::ComboBox::from_id_source("fps_corner_select")
+                                            .selected_text(settings.visual.fps_corner.as_str())
+                                            .show_ui(ui, |ui| {
+                                                for corner in [
+                                                    OverlayCorner::TopLeft,
+                                                    OverlayCorner::TopRight,
+                                                    OverlayCorner::BottomLeft,
+                                                    OverlayCorner::BottomRight,
+                                                ] {
+                                                    ui.selectable_value(
+                                                        &mut settings.visual.fps_corner,
+                                                        corner,
+                                                        corner.as_str(),
+                                                    );
+                                                }
+                                            });
+                                    });
+                                });
+                                ui.end_row();
+
+                                ui.label("Show Frame Time Graph: ").on_hover_text(
+                                    "Show how long the recent frames took in the statistics, to spot stutters",
+                                );
+                                ui.checkbox(&mut settings.visual.show_frame_graph, "");
+                                ui.end_row();
+
+                                ui.label("Stats Number Format: ");
+                                egui::ComboBox::from_id_source("stats_number_format_select")
+                                    .selected_text(settings.visual.stats_number_format.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for format in [
+                                            StatsNumberFormat::Raw,
+                                            StatsNumberFormat::Grouped,
+                                            StatsNumberFormat::Compact,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut settings.visual.stats_number_format,
+                                                format,
+                                                format.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label("Stats Corner: ")
+                                    .on_hover_text("The corner of the note area that the statistics are shown in");
+                                egui::ComboBox::from_id_source("stats_corner_select")
+                                    .selected_text(settings.visual.stats_corner.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for corner in [
+                                            OverlayCorner::TopLeft,
+                                            OverlayCorner::TopRight,
+                                            OverlayCorner::BottomLeft,
+                                            OverlayCorner::BottomRight,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut settings.visual.stats_corner,
+                                                corner,
+                                                corner.as_str(),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label("Notes Fall Down: ");
+                                ui.checkbox(&mut settings.visual.notes_fall_down, "");
+                                ui.end_row();
+
+                                ui.label("Key Press Animation: ");
+                                ui.checkbox(&mut settings.visual.key_press_animation, "");
+                                ui.end_row();
+
+                                ui.label("Velocity Key Colors: ").on_hover_text(
+                                    "Make the pressed keys brighter the harder their notes were hit",
+                                );
+                                ui.checkbox(&mut settings.visual.velocity_key_colors, "");
+                                ui.end_row();
+
+                                ui.label("Key Color Opacity: ").on_hover_text(
+                                    "Draw the pressed key colors with the note opacity as well",
+                                );
+                                ui.checkbox(&mut settings.visual.keyboard_note_opacity, "");
+                                ui.end_row();
+
+                                ui.label("Keyboard Height: ")
+                                    .on_hover_text("Relative to the width of the keys");
+                                ui.add(egui::Slider::new(
+                                    &mut settings.visual.keyboard_height_ratio,
+                                    2.0..=30.0,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Max Keyboard Height: ");
+                                ui.horizontal(|ui| {
+                                    let mut limited = settings.visual.max_keyboard_height.is_some();
+                                    ui.checkbox(&mut limited, "");
+                                    match (limited, settings.visual.max_keyboard_height.as_mut()) {
+                                        (true, Some(max_height)) => {
+                                            ui.add(
+                                                egui::DragValue::new(max_height)
+                                                    .clamp_range(10.0..=1000.0)
+                                                    .suffix(" px"),
+                                            );
+                                        }
+                                        (true, None) => {
+                                            settings.visual.max_keyboard_height = Some(150.0)
+                                        }
+                                        (false, _) => settings.visual.max_keyboard_height = None,
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Visualize Pitch Bends: ");
+                                ui.checkbox(&mut settings.visual.visualize_pitch_bend, "");
+                                ui.end_row();
+
+                                ui.label("Pitch Bend Range: ");
+                                ui.add_enabled(
+                                    settings.visual.visualize_pitch_bend,
+                                    egui::DragValue::new(&mut settings.visual.pitch_bend_range)
+                                        .clamp_range(1..=24)
+                                        .suffix(" semitones"),
+                                );
+                                ui.end_row();
+                            });
+                    });
+
+                // Keybindings section
+                let keybindings_shown = egui::CollapsingHeader::new(
+                    egui::RichText::new("Keybindings").heading(),
+                )
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        if let Some(action) = state.rebinding_shortcut {
+                            if let Some(binding) = shortcuts::pressed_binding(ctx) {
+                                // Escape gives up on the rebinding instead of being bound
+                                if binding.key != egui::Key::Escape {
+                                    settings.keybindings.set(action, binding);
+                                }
+                                state.rebinding_shortcut = None;
+                            }
+                        }
+
+                        egui::Grid::new("keybindings_grid")
+                            .num_columns(2)
+                            .spacing([40.0, 4.0])
+                            .min_col_width(col_width)
+                            .show(ui, |ui| {
+                                for shortcut in shortcuts() {
+                                    ui.label(format!("{}: ", shortcut.description));
+                                    ui.horizontal(|ui| {
+                                        let rebinding =
+                                            state.rebinding_shortcut == Some(shortcut.action);
+                                        let text = if rebinding {
+                                            "Press a key (Esc to cancel)...".to_string()
+                                        } else {
+                                            settings.keybindings.get(shortcut.action).display_name()
+                                        };
+
+                                        let response = ui.selectable_label(rebinding, text);
+                                        if response.clicked() {
+                                            // Keep the button from being clicked again
+                                            // if the new binding is Space or Enter
+                                            response.surrender_focus();
+                                            state.rebinding_shortcut =
+                                                (!rebinding).then_some(shortcut.action);
+                                        }
+
+                                        let conflicts =
+                                            settings.keybindings.conflicts(shortcut.action);
+                                        if !conflicts.is_empty() {
+                                            let others = conflicts
+                                                .iter()
+                                                .filter_map(|action| {
+                                                    shortcuts().iter().find(|s| s.action == *action)
+                                                })
+                                                .map(|s| s.description)
+                                                .collect::<Vec<_>>()
+                                                .join(", ");
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(255, 170, 0),
+                                                format!("Also used by: {others}"),
+                                            );
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+
+                        if ui.button("Reset to Defaults").clicked() {
+                            settings.keybindings = Default::default();
+                            state.rebinding_shortcut = None;
+                        }
+                    })
+                    .body_returned
+                    .is_some();
+                if !keybindings_shown {
+                    state.rebinding_shortcut = None;
+                }
+            });
 
             ui.separator();
             ui.vertical_centered(|ui| {
@@ -181,6 +1121,14 @@ pub fn draw_settings(
                 if ui.button("Save").clicked() {
                     settings.save_to_file();
                 }
+                ui.checkbox(&mut settings.autosave, "Save Automatically")
+                    .on_hover_text("Save the settings a few seconds after they change");
             });
         });
+
+    // The shortcuts stay off while a key is being rebound, so that can't outlast the window
+    let body_shown = shown.map_or(false, |shown| shown.inner.is_some());
+    if !body_shown || !state.settings_visible {
+        state.rebinding_shortcut = None;
+    }
 }
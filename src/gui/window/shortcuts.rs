@@ -0,0 +1,90 @@
+use egui::{Align2, Context, Event};
+
+use crate::settings::keybindings::{shortcuts, KeyBinding, Keybindings, ShortcutAction};
+
+/// The actions of the shortcuts that were pressed this frame. Nothing is triggered
+/// while a text field has focus, so typing into it doesn't control the playback.
+pub fn pressed_actions(ctx: &Context, bindings: &Keybindings) -> Vec<ShortcutAction> {
+    if ctx.wants_keyboard_input() {
+        return Vec::new();
+    }
+    ctx.input(|input| {
+        input
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => shortcuts()
+                    .iter()
+                    .find(|s| !s.held && bindings.get(s.action).matches(*key, *modifiers))
+                    .map(|s| s.action),
+                Event::Text(text) if text == "?" => Some(ShortcutAction::ToggleShortcuts),
+                _ => None,
+            })
+            .collect()
+    })
+}
+
+/// Whether the key of a held shortcut is down, outside of a focused text field
+pub fn is_held(ctx: &Context, action: ShortcutAction, bindings: &Keybindings) -> bool {
+    !ctx.wants_keyboard_input() && ctx.input(|input| input.key_down(bindings.get(action).key))
+}
+
+/// The key combination pressed this frame, used when rebinding a shortcut
+pub fn pressed_binding(ctx: &Context) -> Option<KeyBinding> {
+    ctx.input(|input| {
+        input.events.iter().find_map(|event| match event {
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => Some(KeyBinding::from_key(*key, *modifiers)),
+            _ => None,
+        })
+    })
+}
+
+/// Whether any key was pressed this frame, used to dismiss the overlay
+pub fn any_key_pressed(ctx: &Context) -> bool {
+    ctx.input(|input| {
+        input
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::Key { pressed: true, .. } | Event::Text(_)))
+    })
+}
+
+pub fn draw_shortcuts(ctx: &Context, bindings: &Keybindings) {
+    egui::Window::new("Keyboard Shortcuts")
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(true)
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            egui::Grid::new("shortcuts_grid")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for shortcut in shortcuts() {
+                        let mut name = bindings.get(shortcut.action).display_name();
+                        if shortcut.held {
+                            name.push_str(" (hold)");
+                        }
+                        ui.strong(name);
+                        ui.label(shortcut.description);
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.vertical_centered(|ui| {
+                ui.label("Press any key to close");
+            });
+        });
+}
@@ -0,0 +1,47 @@
+use keepawake::KeepAwake;
+
+/// Keeps the display and the system awake while a MIDI is playing.
+///
+/// The inhibit is released when it is dropped, and the OS also releases it
+/// when the process exits, so a crash can't leave the system unable to sleep.
+pub struct SleepInhibitor {
+    awake: Option<KeepAwake>,
+    /// Set after the inhibit failed, so it isn't retried on every frame
+    failed: bool,
+}
+
+impl SleepInhibitor {
+    pub fn new() -> Self {
+        Self {
+            awake: None,
+            failed: false,
+        }
+    }
+
+    pub fn update(&mut self, inhibit: bool) {
+        if !inhibit {
+            self.awake = None;
+            self.failed = false;
+            return;
+        }
+
+        if self.awake.is_some() || self.failed {
+            return;
+        }
+
+        let awake = keepawake::Builder::default()
+            .display(true)
+            .idle(true)
+            .reason("Playing a MIDI")
+            .app_name("Wasabi")
+            .app_reverse_domain("io.github.arduano.wasabi")
+            .create();
+        match awake {
+            Ok(awake) => self.awake = Some(awake),
+            Err(err) => {
+                log::warn!("Failed to keep the screen awake: {err}");
+                self.failed = true;
+            }
+        }
+    }
+}
@@ -1,12 +1,29 @@
-use egui::{Context, Frame, Pos2};
+use std::time::Duration;
 
-use crate::{gui::window::GuiWasabiWindow, midi::MIDIFileBase};
+use egui::{Color32, Context, Frame, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+use crate::{
+    gui::window::{fps::Fps, peaks::Peak, toast::ToastLevel, GuiWasabiWindow, LIVE_SEEK_MESSAGE},
+    midi::MIDIFileBase,
+    settings::{OverlayCorner, StatsNumberFormat, VisualSettings},
+    state::WasabiState,
+};
+
+const FRAME_GRAPH_HEIGHT: f32 = 40.0;
+/// Frames a little over the refresh interval are only timing jitter, a frame that
+/// misses the refresh takes about twice as long
+const MISSED_FRAME_FACTOR: f32 = 1.5;
+/// Used when the monitor doesn't report its refresh rate
+const DEFAULT_REFRESH_RATE_MHZ: u32 = 60_000;
 
 pub struct GuiMidiStats {
     time_passed: f64,
     time_total: f64,
     notes_on_screen: u64,
+    notes_capped: bool,
     voice_count: u64,
+    max_voices: Option<u64>,
+    output_device: Option<String>,
 }
 
 impl GuiMidiStats {
@@ -15,21 +32,45 @@ impl GuiMidiStats {
             time_passed: 0.0,
             time_total: 0.0,
             notes_on_screen: 0,
+            notes_capped: false,
             voice_count: 0,
+            max_voices: None,
+            output_device: None,
         }
     }
 
-    pub fn set_voice_count(&mut self, voices: u64) {
+    pub fn set_voice_count(&mut self, voices: u64, max_voices: Option<u64>) {
         self.voice_count = voices;
+        self.max_voices = max_voices;
+    }
+
+    pub fn set_output_device(&mut self, device: Option<String>) {
+        self.output_device = device;
     }
 
-    pub fn set_rendered_note_count(&mut self, notes: u64) {
+    pub fn set_rendered_note_count(&mut self, notes: u64, capped: bool) {
         self.notes_on_screen = notes;
+        self.notes_capped = capped;
     }
 }
 
-pub fn draw_stats(win: &mut GuiWasabiWindow, ctx: &Context, pos: Pos2, mut stats: GuiMidiStats) {
+pub fn draw_stats(
+    win: &mut GuiWasabiWindow,
+    state: &mut WasabiState,
+    ctx: &Context,
+    notes_rect: Rect,
+    mut stats: GuiMidiStats,
+    settings: &VisualSettings,
+) {
     let onepx = ctx.pixels_per_point();
+    let corner = settings.stats_corner;
+    let number_format = settings.stats_number_format;
+
+    let height = if settings.show_frame_graph {
+        190.0 + FRAME_GRAPH_HEIGHT + 24.0
+    } else {
+        190.0
+    };
 
     let stats_frame = Frame::default()
         .inner_margin(egui::style::Margin::same(7.0))
@@ -47,8 +88,11 @@ pub fn draw_stats(win: &mut GuiWasabiWindow, ctx: &Context, pos: Pos2, mut stats
         .scroll2([false, false])
         .enabled(true)
         .frame(stats_frame)
-        .fixed_pos(pos)
-        .fixed_size(egui::Vec2::new(200.0, 128.0))
+        .pivot(corner.align())
+        .fixed_pos(corner.align().pos_in_rect(&notes_rect.shrink(10.0)))
+        // Keeps the window on screen when the window is too small for the note area
+        .constrain(true)
+        .fixed_size(egui::Vec2::new(200.0, height))
         .show(ctx, |ui| {
             let mut time_millis: u64 = 0;
             let mut time_sec: u64 = 0;
@@ -58,6 +102,7 @@ pub fn draw_stats(win: &mut GuiWasabiWindow, ctx: &Context, pos: Pos2, mut stats
             let mut length_min: u64 = 0;
 
             let mut note_stats = Default::default();
+            let mut musical_position = Default::default();
 
             if let Some(midi_file) = win.midi_file.as_mut() {
                 stats.time_total = if let Some(length) = midi_file.midi_length() {
@@ -82,21 +127,40 @@ pub fn draw_stats(win: &mut GuiWasabiWindow, ctx: &Context, pos: Pos2, mut stats
                 time_min = stats.time_passed as u64 / 60;
 
                 note_stats = midi_file.stats();
+                musical_position = midi_file.position_musical();
             }
 
             ui.horizontal(|ui| {
-                ui.monospace("Time:");
+                let label = if state.show_musical_time {
+                    "Bar:"
+                } else {
+                    "Time:"
+                };
+                let label = ui
+                    .add(
+                        egui::Label::new(egui::RichText::new(label).monospace())
+                            .sense(Sense::click()),
+                    )
+                    .on_hover_text("Click to switch between clock time and bar:beat:tick");
+                if label.clicked() {
+                    state.show_musical_time = !state.show_musical_time;
+                }
+
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.monospace(format!(
-                        "{:0width$}:{:0width$}.{} / {:0width$}:{:0width$}.{}",
-                        time_min,
-                        time_sec,
-                        time_millis,
-                        length_min,
-                        length_sec,
-                        length_millis,
-                        width = 2
-                    ));
+                    if state.show_musical_time {
+                        ui.monospace(musical_position.to_string());
+                    } else {
+                        ui.monospace(format!(
+                            "{:0width$}:{:0width$}.{} / {:0width$}:{:0width$}.{}",
+                            time_min,
+                            time_sec,
+                            time_millis,
+                            length_min,
+                            length_sec,
+                            length_millis,
+                            width = 2
+                        ));
+                    }
                 });
             });
 
@@ -107,34 +171,218 @@ pub fn draw_stats(win: &mut GuiWasabiWindow, ctx: &Context, pos: Pos2, mut stats
                 });
             });
 
+            if settings.show_frame_graph {
+                let refresh_rate = state.refresh_rate_mhz.unwrap_or(DEFAULT_REFRESH_RATE_MHZ);
+                draw_frame_graph(ui, &win.fps, 1_000_000.0 / refresh_rate as f32);
+            }
+
             ui.horizontal(|ui| {
                 ui.monospace("Voice Count:");
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.monospace(format!("{}", stats.voice_count));
+                    match stats.max_voices {
+                        Some(max) => ui.monospace(format!("{} / {}", stats.voice_count, max)),
+                        None => ui.monospace(format!("{}", stats.voice_count)),
+                    };
                 });
             });
 
+            if let Some(device) = &stats.output_device {
+                ui.horizontal(|ui| {
+                    ui.monospace("Device:");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.monospace(device);
+                    });
+                });
+            }
+
             ui.horizontal(|ui| {
                 ui.monospace("Rendered:");
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.monospace(format!("{}", stats.notes_on_screen));
+                    let notes = format_number(stats.notes_on_screen, number_format);
+                    if stats.notes_capped {
+                        ui.monospace(format!("{notes} (capped)"));
+                    } else {
+                        ui.monospace(notes);
+                    }
                 });
             });
 
-            fn num_or_q(num: Option<impl ToString>) -> String {
+            let nps_jump = draw_peak(ui, "Peak NPS:", win.peaks.nps(), number_format);
+            let voices_jump = draw_peak(ui, "Peak Voices:", win.peaks.voices(), number_format);
+            if let (Some(time), Some(midi_file)) =
+                (nps_jump.or(voices_jump), win.midi_file.as_mut())
+            {
+                let time_prev = midi_file.timer().get_time().as_secs_f64();
+                if midi_file.allows_seeking_backward() || time_prev < time {
+                    midi_file.timer_mut().seek(Duration::from_secs_f64(time));
+                } else {
+                    win.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("Reset Peaks").clicked() {
+                        win.peaks.reset();
+                    }
+                });
+            });
+
+            let num_or_q = |num: Option<u64>| {
                 if let Some(num) = num {
-                    num.to_string()
+                    format_number(num, number_format)
                 } else {
                     "?".to_string()
                 }
-            }
+            };
+            // Live loading only knows the total once the whole file has been read
+            let total_notes = match (note_stats.total_notes, win.midi_file.is_some()) {
+                (None, true) => "streaming".to_string(),
+                (total, _) => num_or_q(total),
+            };
 
             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                 ui.monospace(format!(
                     "{} / {}",
                     num_or_q(note_stats.passed_notes),
-                    num_or_q(note_stats.total_notes)
+                    total_notes
                 ));
             });
         });
 }
+
+/// The time each recent frame took, with the frames that missed the refresh
+/// of the monitor in red. `budget_ms` is the refresh interval.
+fn draw_frame_graph(ui: &mut Ui, fps: &Fps, budget_ms: f32) {
+    let times: Vec<f32> = fps
+        .frame_times()
+        .map(|time| time.as_secs_f32() * 1000.0)
+        .collect();
+    let worst = times.iter().copied().fold(0.0, f32::max);
+
+    ui.horizontal(|ui| {
+        ui.monospace("Frame Time:");
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.monospace(format!("{worst:.1} ms max"));
+        });
+    });
+
+    let (rect, response) = ui.allocate_exact_size(
+        Vec2::new(ui.available_width(), FRAME_GRAPH_HEIGHT),
+        Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, Color32::from_black_alpha(120));
+
+    // Frames of twice the budget fill the graph, longer ones are cut off
+    let scale = rect.height() / (budget_ms * 2.0);
+    let bar_width = rect.width() / times.len().max(1) as f32;
+    for (i, ms) in times.iter().enumerate() {
+        let color = if *ms > budget_ms * MISSED_FRAME_FACTOR {
+            Color32::from_rgb(230, 60, 50)
+        } else {
+            Color32::from_gray(160)
+        };
+        let left = rect.left() + i as f32 * bar_width;
+        let top = (rect.bottom() - ms * scale).max(rect.top());
+        let bar = Rect::from_min_max(
+            Pos2::new(left, top),
+            Pos2::new(left + bar_width, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, color);
+    }
+    painter.hline(
+        rect.x_range(),
+        rect.bottom() - budget_ms * scale,
+        Stroke::new(1.0, Color32::from_gray(90)),
+    );
+
+    response.on_hover_text(format!(
+        "How long each frame took over the last few seconds. The line is the \
+        {budget_ms:.1} ms refresh interval of the monitor, and the frames that \
+        missed it are red"
+    ));
+}
+
+/// A row with the peak value and a button to seek to it, returns the time to seek to if clicked
+fn draw_peak(
+    ui: &mut Ui,
+    label: &str,
+    peak: Option<Peak>,
+    number_format: StatsNumberFormat,
+) -> Option<f64> {
+    ui.horizontal(|ui| {
+        ui.monospace(label);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            let peak = peak?;
+            let jump = ui.small_button("Jump").on_hover_text("Seek to the peak");
+            ui.monospace(format_number(peak.value, number_format));
+            jump.clicked().then_some(peak.time)
+        })
+        .inner
+    })
+    .inner
+}
+
+/// Formats the numbers shown in the statistics
+fn format_number(num: u64, format: StatsNumberFormat) -> String {
+    match format {
+        StatsNumberFormat::Raw => num.to_string(),
+        StatsNumberFormat::Grouped => separate_thousands(num),
+        StatsNumberFormat::Compact => compact_number(num),
+    }
+}
+
+/// Shortens a number with an SI suffix, e.g. 1.2M
+fn compact_number(num: u64) -> String {
+    const SUFFIXES: [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+
+    if num < 1000 {
+        return num.to_string();
+    }
+
+    let mut value = num as f64;
+    let mut suffix = "";
+    for next in SUFFIXES {
+        // Anything that would round up to 1000.0 moves on to the next suffix
+        if value < 999.95 {
+            break;
+        }
+        value /= 1000.0;
+        suffix = next;
+    }
+    format!("{value:.1}{suffix}")
+}
+
+/// Formats a number with commas between each group of thousands, e.g. 1,234,567
+fn separate_thousands(num: u64) -> String {
+    let digits = num.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+/// Just the FPS counter, for when the full statistics aren't wanted
+pub fn draw_fps(ctx: &Context, notes_rect: Rect, fps: &Fps, corner: OverlayCorner) {
+    let fps_frame = Frame::default()
+        .inner_margin(egui::style::Margin::same(4.0))
+        .fill(egui::Color32::from_rgba_unmultiplied(7, 7, 7, 200))
+        .rounding(egui::Rounding::same(4.0));
+
+    let align = corner.align();
+    egui::Area::new("fps_overlay")
+        .order(egui::Order::Foreground)
+        .pivot(align)
+        .fixed_pos(align.pos_in_rect(&notes_rect.shrink(10.0)))
+        .interactable(false)
+        .show(ctx, |ui| {
+            fps_frame.show(ui, |ui| {
+                ui.monospace(format!("FPS: {}", fps.get_fps().round()));
+            });
+        });
+}
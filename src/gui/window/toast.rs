@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use egui::{Align2, Color32, Context, Frame, RichText};
+
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+/// How long a toast takes to fade out at the end of its duration
+const TOAST_FADE: Duration = Duration::from_millis(500);
+/// The most toasts shown at once, the oldest ones make room for new ones
+const MAX_TOASTS: usize = 4;
+
+/// How important a toast is, which decides its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    fn color(self) -> Color32 {
+        match self {
+            ToastLevel::Info => Color32::from_gray(230),
+            ToastLevel::Warning => Color32::from_rgb(255, 190, 60),
+            ToastLevel::Error => Color32::from_rgb(255, 100, 90),
+        }
+    }
+}
+
+struct Toast {
+    message: String,
+    level: ToastLevel,
+    shown_at: Instant,
+}
+
+/// Sends toasts to the window, from any thread
+#[derive(Clone)]
+pub struct ToastSender(Sender<Toast>);
+
+impl ToastSender {
+    pub fn send(&self, message: impl Into<String>, level: ToastLevel) {
+        let toast = Toast {
+            message: message.into(),
+            level,
+            shown_at: Instant::now(),
+        };
+        self.0.send(toast).ok();
+    }
+}
+
+/// Short messages shown at the bottom of the window for a few seconds.
+/// Messages can be sent from other threads through [`GuiToasts::sender`].
+pub struct GuiToasts {
+    sender: Sender<Toast>,
+    reciever: Receiver<Toast>,
+    current: VecDeque<Toast>,
+}
+
+impl GuiToasts {
+    pub fn new() -> Self {
+        let (sender, reciever) = crossbeam_channel::unbounded();
+        Self {
+            sender,
+            reciever,
+            current: VecDeque::new(),
+        }
+    }
+
+    pub fn sender(&self) -> ToastSender {
+        ToastSender(self.sender.clone())
+    }
+
+    pub fn push(&self, message: impl Into<String>, level: ToastLevel) {
+        self.sender().send(message, level);
+    }
+
+    pub fn draw(&mut self, ctx: &Context) {
+        for toast in self.reciever.try_iter() {
+            // Repeating the newest toast keeps it up instead of stacking copies of it
+            if let Some(last) = self.current.back_mut() {
+                if last.message == toast.message && last.level == toast.level {
+                    last.shown_at = toast.shown_at;
+                    continue;
+                }
+            }
+            if self.current.len() == MAX_TOASTS {
+                self.current.pop_front();
+            }
+            self.current.push_back(toast);
+        }
+        self.current
+            .retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+
+        if self.current.is_empty() {
+            return;
+        }
+
+        egui::Area::new("toast")
+            .anchor(Align2::CENTER_BOTTOM, [0.0, -20.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                for toast in self.current.iter() {
+                    let remaining = TOAST_DURATION.saturating_sub(toast.shown_at.elapsed());
+                    let alpha = (remaining.as_secs_f32() / TOAST_FADE.as_secs_f32()).min(1.0);
+
+                    let toast_frame = Frame::default()
+                        .inner_margin(egui::style::Margin::same(7.0))
+                        .fill(Color32::from_rgba_unmultiplied(
+                            7,
+                            7,
+                            7,
+                            (200.0 * alpha) as u8,
+                        ))
+                        .rounding(egui::Rounding::same(6.0));
+
+                    toast_frame.show(ui, |ui| {
+                        let color = toast.level.color().linear_multiply(alpha);
+                        ui.label(RichText::new(toast.message.as_str()).color(color));
+                    });
+                }
+            });
+    }
+}
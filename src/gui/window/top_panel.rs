@@ -1,11 +1,19 @@
-use egui::{Context, Frame};
+use egui::{Color32, Context, Frame, Id, Rect, Sense, Ui};
 
 use std::time::Duration;
 
 use crate::{
-    gui::window::GuiWasabiWindow, midi::MIDIFileBase, settings::WasabiSettings, state::WasabiState,
+    gui::window::{
+        settings_window::draw_note_speed, toast::ToastLevel, GuiWasabiWindow, LIVE_SEEK_MESSAGE,
+    },
+    midi::{MIDIFileBase, NoteDensity},
+    settings::{keybindings::ShortcutAction, WasabiSettings},
+    state::WasabiState,
 };
 
+/// The height of the note density strip above the seek bar
+const DENSITY_HEIGHT: f32 = 6.0;
+
 pub fn draw_panel(
     win: &mut GuiWasabiWindow,
     settings: &mut WasabiSettings,
@@ -14,7 +22,11 @@ pub fn draw_panel(
 ) {
     let panel_frame = Frame::default()
         .inner_margin(egui::style::Margin::same(10.0))
-        .fill(egui::Color32::from_rgb(42, 42, 42));
+        .fill(if ctx.style().visuals.dark_mode {
+            egui::Color32::from_rgb(42, 42, 42)
+        } else {
+            ctx.style().visuals.panel_fill
+        });
 
     egui::TopBottomPanel::top("Top panel")
         .frame(panel_frame)
@@ -30,9 +42,33 @@ pub fn draw_panel(
                         midi_file.timer_mut().pause();
                         win.synth.write().unwrap().reset();
                         win.midi_file = None;
+                        win.validation = None;
+                        win.layers.clear();
+                        win.peaks.reset();
                     }
                 }
 
+                if win.midi_file.is_some()
+                    && ui
+                        .button("Add Layer")
+                        .on_hover_text("Play another MIDI on top of this one")
+                        .clicked()
+                {
+                    win.add_layer_dialog(settings);
+                }
+
+                if !win.layers.is_empty()
+                    && ui
+                        .button(format!("Clear Layers ({})", win.layers.len()))
+                        .clicked()
+                {
+                    win.clear_layers();
+                }
+
+                if win.midi_file.is_some() && ui.button("Export Stats").clicked() {
+                    win.export_stats_dialog();
+                }
+
                 ui.add_space(10.0);
 
                 if ui.button("Settings").clicked() {
@@ -42,27 +78,98 @@ pub fn draw_panel(
                     }
                 }
 
+                if ui.button("Tracks").clicked() {
+                    state.tracks_visible = !state.tracks_visible;
+                }
+
+                if ui.button("Playlist").clicked() {
+                    state.playlist_visible = !state.playlist_visible;
+                }
+
+                if ui
+                    .button("Events")
+                    .on_hover_text("Show the last events sent to the synth")
+                    .clicked()
+                {
+                    state.event_log_visible = !state.event_log_visible;
+                }
+
                 ui.add_space(10.0);
 
-                if ui.button("Play").clicked() {
-                    if let Some(midi_file) = win.midi_file.as_mut() {
-                        midi_file.timer_mut().play();
+                // The transport buttons only work once a MIDI is loaded
+                ui.add_enabled_ui(win.midi_file.is_some(), |ui| {
+                    if ui
+                        .button("Start")
+                        .on_hover_text(shortcut_hint(
+                            settings,
+                            ShortcutAction::JumpToStart,
+                            "Jump to start",
+                        ))
+                        .clicked()
+                    {
+                        win.jump_to_start();
                     }
-                }
-                if ui.button("Pause").clicked() {
-                    if let Some(midi_file) = win.midi_file.as_mut() {
-                        midi_file.timer_mut().pause();
+                    if ui.button("Play").clicked() {
+                        if let Some(midi_file) = win.midi_file.as_mut() {
+                            midi_file.timer_mut().play();
+                        }
+                    }
+                    if ui.button("Pause").clicked() {
+                        if let Some(midi_file) = win.midi_file.as_mut() {
+                            midi_file.timer_mut().pause();
+                        }
+                    }
+                    if ui
+                        .button("Stop")
+                        .on_hover_text(shortcut_hint(
+                            settings,
+                            ShortcutAction::Stop,
+                            "Pause and go back to the start",
+                        ))
+                        .clicked()
+                    {
+                        win.stop();
                     }
+                    if ui
+                        .button("End")
+                        .on_hover_text(shortcut_hint(
+                            settings,
+                            ShortcutAction::JumpToEnd,
+                            "Jump to just before the last note ends",
+                        ))
+                        .clicked()
+                    {
+                        win.jump_to_end();
+                    }
+                });
+                if !win.playlist.is_empty() {
+                    if ui.button("Previous").clicked() {
+                        win.play_previous(settings);
+                    }
+                    if ui.button("Next").clicked() {
+                        win.play_next(settings);
+                    }
+                }
+                if ui
+                    .button("Panic")
+                    .on_hover_text(shortcut_hint(
+                        settings,
+                        ShortcutAction::Panic,
+                        "Stop all playing notes",
+                    ))
+                    .clicked()
+                {
+                    win.synth.write().unwrap().panic();
+                }
+                if ui.checkbox(&mut state.loop_playback, "Loop").changed() && state.loop_playback {
+                    win.loops_played = 0;
                 }
 
                 ui.add_space(10.0);
 
                 ui.horizontal(|ui| {
                     ui.label("Note speed: ");
-                    ui.add(
-                        egui::Slider::new(&mut settings.midi.note_speed, 2.0..=0.001)
-                            .show_value(false),
-                    );
+                    draw_note_speed(ui, &mut settings.midi);
                 })
             });
 
@@ -71,14 +178,32 @@ pub fn draw_panel(
                 || ui.add(egui::Slider::new(&mut 0.0, 0.0..=1.0).show_value(false));
             if let Some(midi_file) = win.midi_file.as_mut() {
                 if let Some(length) = midi_file.midi_length() {
-                    let mut time = midi_file.timer().get_time().as_secs_f64();
+                    let current = midi_file.timer().get_time().as_secs_f64();
+                    let mut time = win.seek_preview.unwrap_or(current);
                     let time_prev = time;
 
-                    ui.add(egui::Slider::new(&mut time, 0.0..=length).show_value(false));
-                    if (time_prev != time)
-                        && (midi_file.allows_seeking_backward() || time_prev < time)
-                    {
-                        midi_file.timer_mut().seek(Duration::from_secs_f64(time));
+                    if let Some(density) = midi_file.note_density() {
+                        if let Some(clicked) = draw_density(ui, density, length) {
+                            time = clicked;
+                        }
+                    }
+                    let slider =
+                        ui.add(egui::Slider::new(&mut time, 0.0..=length).show_value(false));
+
+                    // Seeking can be slow on huge MIDIs, so it can wait for the slider to be let go
+                    let dragging = settings.midi.seek_on_release && slider.dragged();
+                    let released = !dragging && win.seek_preview.take().is_some();
+                    if dragging {
+                        win.seek_preview = Some(time);
+                        egui::show_tooltip_at_pointer(ctx, Id::new("seek_preview"), |ui| {
+                            ui.label(format!("{:02}:{:04.1}", time as u64 / 60, time % 60.0));
+                        });
+                    } else if released || time_prev != time {
+                        if midi_file.allows_seeking_backward() || current < time {
+                            midi_file.timer_mut().seek(Duration::from_secs_f64(time));
+                        } else {
+                            win.toasts.push(LIVE_SEEK_MESSAGE, ToastLevel::Info);
+                        }
                     }
                 } else {
                     empty_slider();
@@ -88,3 +213,50 @@ pub fn draw_panel(
             }
         });
 }
+
+/// Draws a heatmap of how many notes start throughout the MIDI, returns
+/// the time that was clicked on
+fn draw_density(ui: &mut Ui, density: &NoteDensity, length: f64) -> Option<f64> {
+    let width = ui.spacing().slider_width;
+    let (rect, response) =
+        ui.allocate_exact_size(egui::vec2(width, DENSITY_HEIGHT), Sense::click());
+
+    let max = density.max();
+    if length > 0.0 && max > 0 {
+        let painter = ui.painter_at(rect);
+        let x_at = |time: f64| rect.left() + (time / length).min(1.0) as f32 * rect.width();
+
+        for (i, count) in density.buckets().iter().enumerate() {
+            let start = i as f64 * density.bucket_length();
+            if start >= length {
+                break;
+            }
+
+            // The square root keeps the quieter sections visible next to the peaks
+            let heat = (*count as f32 / max as f32).sqrt();
+            let segment = Rect::from_x_y_ranges(
+                x_at(start)..=x_at(start + density.bucket_length()),
+                rect.y_range(),
+            );
+            painter.rect_filled(segment, 0.0, density_color(heat));
+        }
+    }
+
+    let pos = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())?;
+    let fraction = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+    Some(fraction as f64 * length)
+}
+
+/// A button's hover text followed by the key that its shortcut is bound to
+fn shortcut_hint(settings: &WasabiSettings, action: ShortcutAction, text: &str) -> String {
+    let binding = settings.keybindings.get(action).display_name();
+    format!("{text} ({binding})")
+}
+
+/// Goes from a dim blue for empty sections to orange for the busiest ones
+fn density_color(heat: f32) -> Color32 {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * heat).round() as u8;
+    Color32::from_rgb(lerp(35, 255), lerp(45, 140), lerp(80, 0))
+}
@@ -0,0 +1,144 @@
+use std::thread;
+
+use crossbeam_channel::Receiver;
+use egui::{Color32, Context, Sense, Ui, Vec2};
+
+use crate::{
+    gui::window::GuiWasabiWindow,
+    midi::{MIDIFileBase, MIDIValidation},
+    state::WasabiState,
+};
+
+/// Files can have thousands of tracks, so only this many rows are shown
+const MAX_TRACK_ROWS: usize = 256;
+
+/// The check for common problems in the loaded MIDI, which reads
+/// the whole file again in the background
+pub enum MIDIValidationState {
+    Running(Receiver<Result<MIDIValidation, String>>),
+    Done(Result<MIDIValidation, String>),
+}
+
+impl MIDIValidationState {
+    fn start(path: String) -> Self {
+        let (snd, rcv) = crossbeam_channel::bounded(1);
+        thread::spawn(move || {
+            let validation = MIDIValidation::from_file(&path).map_err(|e| e.to_string());
+            snd.send(validation).ok();
+        });
+        MIDIValidationState::Running(rcv)
+    }
+}
+
+fn draw_validation(win: &mut GuiWasabiWindow, ui: &mut Ui) {
+    if let Some(MIDIValidationState::Running(rcv)) = &win.validation {
+        if let Ok(result) = rcv.try_recv() {
+            win.validation = Some(MIDIValidationState::Done(result));
+        }
+    }
+
+    match &win.validation {
+        None => {
+            let clicked = ui
+                .button("Check for Problems")
+                .on_hover_text("Look for unended notes, stray note offs and invalid values")
+                .clicked();
+            if clicked {
+                if let Some(midi_file) = win.midi_file.as_ref() {
+                    let path = midi_file.signature().filepath.clone();
+                    win.validation = Some(MIDIValidationState::start(path));
+                }
+            }
+        }
+        Some(MIDIValidationState::Running(_)) => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Checking the MIDI...");
+            });
+        }
+        Some(MIDIValidationState::Done(Ok(validation))) => {
+            let issues = validation.issues();
+            if issues.is_empty() {
+                ui.label("No problems found");
+            }
+            for issue in issues {
+                ui.colored_label(ui.visuals().warn_fg_color, issue);
+            }
+        }
+        Some(MIDIValidationState::Done(Err(err))) => {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+    }
+}
+
+pub fn draw_tracks(win: &mut GuiWasabiWindow, state: &mut WasabiState, ctx: &Context) {
+    let mut sort_by_notes = state.sort_tracks_by_notes;
+
+    egui::Window::new("Tracks")
+        .resizable(true)
+        .collapsible(true)
+        .title_bar(true)
+        .scroll2([false, true])
+        .enabled(true)
+        .open(&mut state.tracks_visible)
+        .show(ctx, |ui| {
+            if let Some(midi_file) = win.midi_file.as_ref() {
+                let format = midi_file.format();
+                ui.label(format!("Format: {format}"));
+                if let Some(warning) = format.warning() {
+                    ui.colored_label(ui.visuals().warn_fg_color, warning);
+                }
+                draw_validation(win, ui);
+                ui.separator();
+            }
+
+            let Some(counts) = win.midi_file.as_ref().and_then(|m| m.track_note_counts()) else {
+                ui.label("The note counts are not available for this MIDI");
+                return;
+            };
+
+            ui.checkbox(&mut sort_by_notes, "Sort by note count");
+            ui.separator();
+
+            let mut tracks: Vec<_> = counts.iter().copied().enumerate().collect();
+            if sort_by_notes {
+                tracks.sort_by(|a, b| b.1.cmp(&a.1));
+            }
+
+            // The colors of the first channel in each track
+            let colors = win
+                .midi_file
+                .as_ref()
+                .map(|m| m.track_colors().to_vec())
+                .unwrap_or_default();
+
+            egui::Grid::new("tracks_grid")
+                .num_columns(3)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Track");
+                    ui.strong("Notes");
+                    ui.end_row();
+
+                    for (track, count) in tracks.iter().take(MAX_TRACK_ROWS) {
+                        let swatch = colors.get(track * 16).map_or(Color32::GRAY, |color| {
+                            Color32::from_rgb(color.red(), color.green(), color.blue())
+                        });
+                        let (rect, _) = ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
+                        ui.painter().rect_filled(rect, 2.0, swatch);
+
+                        ui.label(format!("{}", track + 1));
+                        ui.label(format!("{}", count));
+                        ui.end_row();
+                    }
+                });
+
+            if tracks.len() > MAX_TRACK_ROWS {
+                ui.label(format!("…and {} more", tracks.len() - MAX_TRACK_ROWS));
+            }
+        });
+
+    state.sort_tracks_by_notes = sort_by_notes;
+}
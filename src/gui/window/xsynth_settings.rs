@@ -1,17 +1,52 @@
+use crossbeam_channel::{Receiver, TryRecvError};
 use egui::Context;
 
-use std::ops::RangeInclusive;
+use std::{ops::RangeInclusive, path::Path, thread};
 
 use crate::{
     audio_playback::{
-        xsynth::{convert_to_channel_init, convert_to_sf_init},
+        xsynth::{convert_to_channel_init, convert_to_sf_init, load_soundfont, render_threads},
         AudioPlayerType,
     },
-    gui::window::GuiWasabiWindow,
-    settings::WasabiSettings,
+    gui::window::{toast::ToastLevel, GuiWasabiWindow},
+    settings::{Synth, WasabiSettings},
     state::WasabiState,
 };
 
+/// The sample rates offered in the settings, other rates can be set in the config
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 96000];
+
+/// Loads the current soundfont from its file again on another thread, since big
+/// soundfonts take a while, then switches to it. The result is shown as a toast.
+fn reload_soundfont(win: &GuiWasabiWindow, settings: &WasabiSettings) -> Option<Receiver<()>> {
+    let stream_params = win.synth.read().unwrap().soundfont_stream_params()?;
+    let synth = win.synth.clone();
+    let toasts = win.toasts.sender();
+    let path = settings.synth.sfz_path.clone();
+    let options = convert_to_sf_init(settings);
+
+    let (snd, rcv) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        let soundfont = load_soundfont(&path, stream_params, options);
+        match synth
+            .write()
+            .unwrap()
+            .use_reloaded_soundfont(&path, soundfont)
+        {
+            Ok(()) => {
+                let name = Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.clone());
+                toasts.send(format!("Reloaded {name}"), ToastLevel::Info);
+            }
+            Err(err) => toasts.send(err, ToastLevel::Error),
+        }
+        snd.send(()).ok();
+    });
+    Some(rcv)
+}
+
 pub fn draw_xsynth_settings(
     win: &mut GuiWasabiWindow,
     settings: &mut WasabiSettings,
@@ -36,14 +71,6 @@ pub fn draw_xsynth_settings(
                 .spacing([40.0, 4.0])
                 .min_col_width(col_width)
                 .show(ui, |ui| {
-                    ui.label("Synth Render Buffer (ms)*: ");
-                    ui.add(
-                        egui::DragValue::new(&mut settings.synth.buffer_ms)
-                            .speed(0.1)
-                            .clamp_range(RangeInclusive::new(0.001, 1000.0)),
-                    );
-                    ui.end_row();
-
                     ui.label("SFZ Path: ");
                     ui.horizontal(|ui| {
                         ui.add(egui::TextEdit::singleline(&mut settings.synth.sfz_path));
@@ -67,6 +94,73 @@ pub fn draw_xsynth_settings(
                                 convert_to_sf_init(settings),
                             );
                         }
+
+                        if let Some(rcv) = &win.soundfont_reload {
+                            if matches!(rcv.try_recv(), Err(TryRecvError::Empty)) {
+                                ui.spinner();
+                            } else {
+                                win.soundfont_reload = None;
+                            }
+                        } else {
+                            let clicked = ui
+                                .button("Reload SoundFont")
+                                .on_hover_text(
+                                    "Loads the soundfont from its file again, to hear the \
+                                    changes made to it without restarting",
+                                )
+                                .clicked();
+                            if clicked {
+                                win.soundfont_reload = reload_soundfont(win, settings);
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Soundfont Cycle: ").on_hover_text(
+                        "The soundfonts that the next soundfont shortcut (F8) switches between. \
+                        They are kept loaded, so switching is instant.",
+                    );
+                    ui.vertical(|ui| {
+                        let mut changed = false;
+                        let mut remove = None;
+                        for (i, path) in settings.synth.soundfont_cycle.iter().enumerate() {
+                            let name = Path::new(path)
+                                .file_name()
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or_else(|| path.clone());
+                            ui.horizontal(|ui| {
+                                if ui.small_button("✖").clicked() {
+                                    remove = Some(i);
+                                }
+                                ui.label(name).on_hover_text(path);
+                            });
+                        }
+                        if let Some(i) = remove {
+                            settings.synth.soundfont_cycle.remove(i);
+                            changed = true;
+                        }
+
+                        if ui.button("Add...").clicked() {
+                            let sfz_paths = rfd::FileDialog::new()
+                                .add_filter("sfz/sf2", &["sfz", "sf2"])
+                                .pick_files();
+
+                            for path in sfz_paths.into_iter().flatten() {
+                                if let Some(path) = path.to_str() {
+                                    if !settings.synth.soundfont_cycle.iter().any(|p| p == path) {
+                                        settings.synth.soundfont_cycle.push(path.to_owned());
+                                        changed = true;
+                                    }
+                                }
+                            }
+                        }
+
+                        if changed {
+                            if let Some(err) = win.synth.write().unwrap().take_device_error() {
+                                win.push_toast(err, ToastLevel::Error);
+                            }
+                            win.synth.write().unwrap().preload_soundfonts(settings);
+                        }
                     });
                     ui.end_row();
 
@@ -97,6 +191,34 @@ pub fn draw_xsynth_settings(
                     }
                     ui.end_row();
 
+                    ui.label("Voice Limit: ").on_hover_text(
+                        "While this many voices are playing, each new note cuts off the \
+                        quietest held note, the oldest one if several are as quiet",
+                    );
+                    let max_voices_prev = settings.synth.max_voices;
+                    ui.horizontal(|ui| {
+                        let mut limited = settings.synth.max_voices.is_some();
+                        ui.checkbox(&mut limited, "");
+                        match (limited, settings.synth.max_voices.as_mut()) {
+                            (true, Some(max_voices)) => {
+                                ui.add(
+                                    egui::Slider::new(max_voices, 1000..=1_000_000)
+                                        .logarithmic(true)
+                                        .suffix(" voices"),
+                                );
+                            }
+                            (true, None) => settings.synth.max_voices = Some(100_000),
+                            (false, _) => settings.synth.max_voices = None,
+                        }
+                    });
+                    if settings.synth.max_voices != max_voices_prev {
+                        win.synth
+                            .write()
+                            .unwrap()
+                            .set_max_voices(settings.synth.max_voices);
+                    }
+                    ui.end_row();
+
                     ui.label("Ignore notes with velocities between*: ");
                     let mut lovel = *settings.synth.vel_ignore.start();
                     let mut hivel = *settings.synth.vel_ignore.end();
@@ -130,49 +252,209 @@ pub fn draw_xsynth_settings(
                 .spacing([40.0, 4.0])
                 .min_col_width(col_width)
                 .show(ui, |ui| {
-                    ui.label("Fade out voice when killing it*: ");
-                    ui.checkbox(&mut settings.synth.fade_out_kill, "");
+                    // These are applied when the soundfont is loaded, so toggling
+                    // them reloads XSynth right away
+                    let xsynth = settings.synth.synth == Synth::XSynth;
+                    let mut reload = false;
+
+                    ui.label("Fade out voice when killing it: ");
+                    reload |= ui
+                        .add_enabled(
+                            xsynth,
+                            egui::Checkbox::new(&mut settings.synth.fade_out_kill, ""),
+                        )
+                        .changed();
                     ui.end_row();
 
-                    ui.label("Linear release envelope*: ");
-                    ui.checkbox(&mut settings.synth.linear_envelope, "");
+                    ui.label("Linear release envelope: ");
+                    reload |= ui
+                        .add_enabled(
+                            xsynth,
+                            egui::Checkbox::new(&mut settings.synth.linear_envelope, ""),
+                        )
+                        .changed();
                     ui.end_row();
 
-                    ui.label("Use Effects*: ");
-                    ui.checkbox(&mut settings.synth.use_effects, "");
+                    ui.label("Use Effects: ");
+                    reload |= ui
+                        .add_enabled(
+                            xsynth,
+                            egui::Checkbox::new(&mut settings.synth.use_effects, ""),
+                        )
+                        .changed();
                     ui.end_row();
 
+                    if reload {
+                        reload_xsynth(win, settings);
+                    }
+
                     ui.label("Use Threadpool*: ");
                     ui.checkbox(&mut settings.synth.use_threadpool, "");
                     ui.end_row();
+
+                    ui.label("Render Threads: ");
+                    let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(settings.synth.use_threadpool, |ui| {
+                            egui::ComboBox::from_id_source("render_threads_select")
+                                .selected_text(match settings.synth.render_threads {
+                                    Some(threads) => threads.to_string(),
+                                    None => "Auto".to_string(),
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut settings.synth.render_threads,
+                                        None,
+                                        "Auto",
+                                    );
+                                    for threads in 1..=max_threads {
+                                        ui.selectable_value(
+                                            &mut settings.synth.render_threads,
+                                            Some(threads),
+                                            threads.to_string(),
+                                        );
+                                    }
+                                })
+                                .response
+                                .on_hover_text(
+                                    "More threads let the synth keep up with higher NPS, \
+                                    but add scheduling overhead that can increase latency \
+                                    on lighter MIDIs. Auto uses one thread per CPU core.",
+                                );
+                        });
+                        if settings.synth.render_threads != render_threads() {
+                            ui.weak("Applies when Wasabi is restarted");
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Sample Rate: ");
+                    let sample_rate_prev = settings.synth.sample_rate;
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("sample_rate_select")
+                            .selected_text(match settings.synth.sample_rate {
+                                Some(rate) => format!("{rate} Hz"),
+                                None => "Device Default".to_string(),
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut settings.synth.sample_rate,
+                                    None,
+                                    "Device Default",
+                                );
+                                for rate in SAMPLE_RATES {
+                                    ui.selectable_value(
+                                        &mut settings.synth.sample_rate,
+                                        Some(rate),
+                                        format!("{rate} Hz"),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text(
+                                "XSynth renders at the rate that the audio device is \
+                                opened at. Rates that the device doesn't support \
+                                fall back to its default rate.",
+                            );
+                        // Shown when the device didn't take the rate, as XSynth can't resample
+                        let playing_rate = win
+                            .synth
+                            .read()
+                            .unwrap()
+                            .soundfont_stream_params()
+                            .map(|params| params.sample_rate);
+                        if let (Some(rate), Some(playing)) =
+                            (settings.synth.sample_rate, playing_rate)
+                        {
+                            if rate != playing {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 170, 0),
+                                    format!("Not supported, playing at {playing} Hz"),
+                                );
+                            }
+                        }
+                    });
+                    if settings.synth.sample_rate != sample_rate_prev {
+                        reload_xsynth(win, settings);
+                    }
+                    ui.end_row();
                 });
 
             ui.separator();
             ui.vertical_centered(|ui| {
                 ui.label("Options marked with (*) will apply when the synth is reloaded.");
                 if ui.button("Reload XSynth").clicked() {
-                    win.synth
-                        .write()
-                        .unwrap()
-                        .switch_player(AudioPlayerType::XSynth {
-                            buffer: settings.synth.buffer_ms,
-                            use_threadpool: settings.synth.use_threadpool,
-                            ignore_range: settings.synth.vel_ignore.clone(),
-                            options: convert_to_channel_init(settings),
-                        });
-                    win.synth
-                        .write()
-                        .unwrap()
-                        .set_soundfont(&settings.synth.sfz_path, convert_to_sf_init(settings));
-                    win.synth
-                        .write()
-                        .unwrap()
-                        .set_layer_count(if settings.synth.limit_layers {
-                            Some(settings.synth.layer_count)
-                        } else {
-                            None
-                        });
+                    reload_xsynth(win, settings);
                 }
             });
         });
 }
+
+/// A banner under the top panel for when XSynth has no soundfont, since it plays nothing
+pub fn draw_soundfont_warning(
+    win: &mut GuiWasabiWindow,
+    state: &mut WasabiState,
+    ctx: &Context,
+    top: f32,
+) {
+    let Some(error) = win
+        .synth
+        .read()
+        .unwrap()
+        .soundfont_error()
+        .map(|e| e.to_owned())
+    else {
+        return;
+    };
+    if state.xsynth_settings_visible {
+        return;
+    }
+
+    let warning_frame = egui::Frame::default()
+        .inner_margin(egui::style::Margin::same(7.0))
+        .fill(egui::Color32::from_rgba_unmultiplied(7, 7, 7, 200))
+        .rounding(egui::Rounding::same(6.0));
+
+    egui::Area::new("soundfont_warning")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, top + 10.0])
+        .show(ctx, |ui| {
+            warning_frame.show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(255, 170, 0),
+                        format!("{error}, XSynth won't make any sound."),
+                    );
+                    if ui.button("Pick a Soundfont").clicked() {
+                        state.xsynth_settings_visible = true;
+                    }
+                });
+            });
+        });
+}
+
+pub fn reload_xsynth(win: &mut GuiWasabiWindow, settings: &WasabiSettings) {
+    win.synth
+        .write()
+        .unwrap()
+        .switch_player(AudioPlayerType::XSynth {
+            buffer: settings.synth.buffer_ms,
+            use_threadpool: settings.synth.use_threadpool,
+            ignore_range: settings.synth.vel_ignore.clone(),
+            options: convert_to_channel_init(settings),
+            output_device: settings.synth.output_device.clone(),
+            sample_rate: settings.synth.sample_rate,
+        });
+    win.synth.write().unwrap().preload_soundfonts(settings);
+    win.synth
+        .write()
+        .unwrap()
+        .set_soundfont(&settings.synth.sfz_path, convert_to_sf_init(settings));
+    win.synth
+        .write()
+        .unwrap()
+        .set_layer_count(if settings.synth.limit_layers {
+            Some(settings.synth.layer_count)
+        } else {
+            None
+        });
+}
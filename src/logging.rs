@@ -0,0 +1,26 @@
+use env_logger::{Builder, Env};
+
+/// Warnings from everything, plus the main events of loading and playing
+const DEFAULT_FILTER: &str = "warn,wasabi=info";
+const VERBOSE_FILTER: &str = "info,wasabi=debug";
+const QUIET_FILTER: &str = "error";
+
+/// Starts the logger. This runs before the settings are loaded so that their
+/// messages get logged too, which is why `--verbose` and `--quiet` are looked
+/// for in the arguments directly, clap only checks and documents them.
+/// `RUST_LOG` takes precedence over both flags.
+pub fn init() {
+    let mut filter = DEFAULT_FILTER;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--verbose" => filter = VERBOSE_FILTER,
+            "--quiet" | "-q" => filter = QUIET_FILTER,
+            "--" => break,
+            _ => {}
+        }
+    }
+
+    Builder::from_env(Env::default().default_filter_or(filter))
+        .format_timestamp(None)
+        .init();
+}
@@ -2,20 +2,23 @@
 #![feature(coroutines)]
 #![feature(impl_trait_in_assoc_type)]
 
+mod audio_only;
 mod audio_playback;
 mod gui;
+mod logging;
 mod midi;
 mod renderer;
 mod scenes;
 mod settings;
 mod state;
 
+use std::path::Path;
+
 use egui_winit_vulkano::{Gui, GuiConfig};
 use gui::{window::GuiWasabiWindow, GuiRenderer, GuiState};
-use renderer::Renderer;
-use vulkano::swapchain::PresentMode;
+use renderer::{frame_dump::FrameDump, screenshot::FrameCapturer, Renderer};
 
-use settings::WasabiSettings;
+use settings::{WasabiSettings, UI_SCALE_RANGE};
 use state::WasabiState;
 use winit::{
     dpi::{LogicalSize, Size},
@@ -28,10 +31,40 @@ pub const WINDOW_SIZE: Size = Size::Logical(LogicalSize {
     height: 720.0,
 });
 
-pub const PRESENT_MODE: PresentMode = PresentMode::Immediate;
-pub const WAYLAND_PRESENT_MODE: PresentMode = PresentMode::Mailbox;
-
 pub fn main() {
+    logging::init();
+
+    // Load the settings values
+    let mut settings = WasabiSettings::new_or_load();
+    audio_playback::xsynth::init_render_threads(settings.synth.render_threads);
+    let mut wasabi_state = WasabiState {
+        loop_playback: settings.loop_count.is_some(),
+        ..Default::default()
+    };
+
+    if let (Some(stats_path), Some(midi_path)) = (&settings.export_stats, &settings.load_midi_file)
+    {
+        match midi::export_midi_summary(midi_path, Path::new(stats_path)) {
+            Ok(()) => log::info!("Saved the MIDI statistics to {stats_path}"),
+            Err(err) => log::error!("Failed to export the MIDI statistics: {err}"),
+        }
+    }
+
+    if settings.validate {
+        if let Some(midi_path) = &settings.load_midi_file {
+            match midi::MIDIValidation::from_file(midi_path) {
+                Ok(validation) => println!("{validation}"),
+                Err(err) => log::error!("Failed to check the MIDI: {err}"),
+            }
+        }
+        return;
+    }
+
+    if settings.audio_only {
+        audio_only::run(&settings);
+        return;
+    }
+
     // Winit event loop
     let event_loop = EventLoop::new();
     let monitor = event_loop
@@ -41,17 +74,19 @@ pub fn main() {
 
     let mode = monitor.video_modes().next().expect("no mode found");
 
-    // Load the settings values
-    let mut settings = WasabiSettings::new_or_load();
-    let mut wasabi_state = WasabiState::default();
-
     // Create renderer for our scene & ui
     let mut renderer = Renderer::new(
         &event_loop,
         "Wasabi",
         settings.visual.fullscreen,
+        settings.visual.vsync,
         mode.clone(),
     );
+    wasabi_state.system_theme = renderer.window().theme();
+    wasabi_state.refresh_rate_mhz = renderer
+        .window()
+        .current_monitor()
+        .and_then(|monitor| monitor.refresh_rate_millihertz());
 
     // Vulkano & Winit & egui integration
     let mut gui = Gui::new(
@@ -74,6 +109,28 @@ pub fn main() {
 
     let mut gui_state = GuiWasabiWindow::new(&mut gui_render_data, &mut settings);
 
+    let frame_capturer = FrameCapturer::new(renderer.device());
+
+    let mut frame_dump = settings.frames_out.as_ref().and_then(|path| {
+        let queue = renderer.queue();
+        match FrameDump::new(
+            path,
+            settings.frames_fps,
+            settings.frames_size,
+            &queue,
+            renderer.format(),
+        ) {
+            Ok(frame_dump) => {
+                log::warn!("No audio is written with the frames");
+                Some(frame_dump)
+            }
+            Err(err) => {
+                log::error!("Failed to open {path} for the frames: {err}");
+                None
+            }
+        }
+    });
+
     event_loop.run(move |event, _, control_flow| {
         let device = renderer.device();
         let queue = renderer.queue();
@@ -93,6 +150,9 @@ pub fn main() {
                     WindowEvent::CloseRequested => {
                         *control_flow = ControlFlow::Exit;
                     }
+                    WindowEvent::ThemeChanged(theme) => {
+                        wasabi_state.system_theme = Some(theme);
+                    }
                     WindowEvent::DroppedFile(path) => {
                         gui_state.load_midi(&mut settings, path);
                     }
@@ -100,13 +160,50 @@ pub fn main() {
                 }
             }
             Event::RedrawRequested(_) => {
+                let mut captured_frame = None;
+
+                // No frames are written until a downloaded MIDI is loaded
+                let downloading = gui_state.is_downloading();
+                if let Some(frame_dump) = frame_dump.as_mut().filter(|_| !downloading) {
+                    if !gui_state.step_to(frame_dump.time()) {
+                        match frame_dump.finish() {
+                            Ok(()) => log::info!("Wrote {} frames", frame_dump.frames()),
+                            Err(err) => log::error!("Failed to write the frames: {err}"),
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+
+                    let frame_renderer = GuiRenderer {
+                        gui: &mut gui,
+                        device: device.clone(),
+                        queue: queue.clone(),
+                        format,
+                    };
+                    gui_state.draw_offscreen(&frame_renderer, &settings, frame_dump.image());
+                    let flipped = !settings.visual.notes_fall_down;
+                    if let Err(err) = frame_dump.write(&queue, flipped) {
+                        log::error!("Failed to write the frames: {err}");
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                }
+
+                // The UI scale is applied on top of the monitor's own scale factor
+                let ui_scale = settings
+                    .visual
+                    .ui_scale
+                    .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end());
+                gui.context()
+                    .set_pixels_per_point(renderer.window().scale_factor() as f32 * ui_scale);
+
                 renderer.render(|frame, future| {
                     // Generate egui layouts
                     gui.immediate_ui(|gui| {
                         let mut gui_render_data = GuiRenderer {
                             gui,
                             device,
-                            queue,
+                            queue: queue.clone(),
                             format,
                         };
 
@@ -118,8 +215,26 @@ pub fn main() {
                     });
 
                     // Render the layouts
-                    gui.draw_on_image(future, frame.image.clone())
+                    let future = gui.draw_on_image(future, frame.image.clone());
+
+                    if wasabi_state.take_screenshot {
+                        wasabi_state.take_screenshot = false;
+                        let (future, captured) =
+                            frame_capturer.capture(&queue, frame.image.image().clone(), future);
+                        captured_frame = Some(captured);
+                        future
+                    } else {
+                        future
+                    }
                 });
+
+                // The frame has been presented at this point, so the copy is finished
+                if let Some(captured_frame) = captured_frame {
+                    match captured_frame {
+                        Ok(frame) => gui_state.save_screenshot(frame),
+                        Err(err) => gui_state.screenshot_failed(&err),
+                    }
+                }
             }
             Event::MainEventsCleared => {
                 renderer.window().request_redraw();
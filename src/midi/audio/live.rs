@@ -14,10 +14,64 @@ use crate::{
     },
 };
 
+/// The last value of every control that was sent on each channel. The live loader
+/// doesn't keep the events that were already played, so these are what gets sent
+/// again once the synth was reset.
+struct SentControls {
+    /// 128 controllers per channel, then the program, the channel
+    /// pressure and the pitch bend of each channel
+    values: Vec<Option<u32>>,
+}
+
+impl SentControls {
+    const PROGRAMS: usize = 16 * 128;
+    const PRESSURES: usize = Self::PROGRAMS + 16;
+    const PITCH_BENDS: usize = Self::PRESSURES + 16;
+
+    fn new() -> Self {
+        SentControls {
+            values: vec![None; Self::PITCH_BENDS + 16],
+        }
+    }
+
+    fn slot(data: u32) -> Option<usize> {
+        let channel = (data & 0x0F) as usize;
+        match data & 0xF0 {
+            0xB0 => Some(channel * 128 + ((data >> 8) & 0x7F) as usize),
+            0xC0 => Some(Self::PROGRAMS + channel),
+            0xD0 => Some(Self::PRESSURES + channel),
+            0xE0 => Some(Self::PITCH_BENDS + channel),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, data: u32) {
+        if let Some(slot) = Self::slot(data) {
+            self.values[slot] = Some(data);
+        }
+    }
+
+    /// The controls in the order they're sent again. The RPN and NRPN numbers go
+    /// first, so that the data entry after them goes to the right parameter, and
+    /// the bank selects go before the programs.
+    fn iter(&self) -> impl '_ + Iterator<Item = u32> {
+        let is_parameter_number =
+            |data: &u32| data & 0xF0 == 0xB0 && (98..=101).contains(&((data >> 8) & 0x7F));
+        let values = self.values.iter().flatten().copied();
+        values
+            .clone()
+            .filter(is_parameter_number)
+            .chain(values.filter(move |data| !is_parameter_number(data)))
+    }
+}
+
 pub struct LiveAudioPlayer {
     events: Receiver<CompressedAudio>,
     timer: TimeListener,
     player: Arc<RwLock<SimpleTemporaryPlayer>>,
+    controls: SentControls,
+    /// The reset count of the synth when the controls were last sent to it
+    synth_resets: u64,
 }
 
 impl LiveAudioPlayer {
@@ -30,6 +84,8 @@ impl LiveAudioPlayer {
             events,
             timer,
             player,
+            controls: SentControls::new(),
+            synth_resets: 0,
         }
     }
 
@@ -39,23 +95,12 @@ impl LiveAudioPlayer {
 
             let max_fall_time = 0.1;
 
-            let reset = || {
-                if let Ok(mut player) = self.player.clone().write() {
-                    player.reset();
-                };
-            };
-
-            let push_cc = |e: &CompressedAudio| {
-                if let Ok(mut player) = self.player.clone().write() {
-                    player.push_events(e.iter_control_events());
-                }
-            };
-
-            for event in self.events.into_iter() {
+            let events = self.events.clone();
+            for event in events.into_iter() {
                 if self.timer.is_paused() {
-                    reset();
+                    self.reset();
                     match self.timer.wait_until_unpause() {
-                        UnpauseWaitResult::Unpaused => push_cc(&event),
+                        UnpauseWaitResult::Unpaused => self.push(&event, true),
                         UnpauseWaitResult::UnpausedAndSeeked(time) => {
                             if time.as_secs_f64() - event.time > max_fall_time {
                                 seek_catching_up = true;
@@ -69,7 +114,7 @@ impl LiveAudioPlayer {
                 if seek_catching_up {
                     let time = self.timer.get_time().as_secs_f64();
                     if time - event.time > max_fall_time {
-                        push_cc(&event);
+                        self.push(&event, true);
                         continue;
                     } else {
                         seek_catching_up = false;
@@ -83,22 +128,49 @@ impl LiveAudioPlayer {
                         continue;
                     }
                     WaitResult::Seeked(time) => {
-                        reset();
+                        self.reset();
                         if time.as_secs_f64() - event.time > max_fall_time {
                             seek_catching_up = true;
                         }
                         continue;
                     }
                     WaitResult::Killed => {
-                        reset();
+                        self.reset();
                         break;
                     }
                 }
 
-                if let Ok(mut player) = self.player.clone().write() {
-                    player.push_events(event.iter_events());
-                }
+                self.push(&event, false);
             }
         })
     }
+
+    fn reset(&self) {
+        if let Ok(mut player) = self.player.write() {
+            player.reset();
+        };
+    }
+
+    /// Sends the events, or only their controls while catching up after a seek.
+    /// If the synth was reset since the last events, by this MIDI or by a layer
+    /// playing into the same synth, the controls sent so far are sent again first.
+    fn push(&mut self, event: &CompressedAudio, controls_only: bool) {
+        let Ok(mut player) = self.player.write() else {
+            return;
+        };
+
+        if player.reset_count() != self.synth_resets {
+            player.push_events(self.controls.iter());
+            self.synth_resets = player.reset_count();
+        }
+
+        for data in event.iter_control_events() {
+            self.controls.record(data);
+        }
+        if controls_only {
+            player.push_events(event.iter_control_events());
+        } else {
+            player.push_events(event.iter_events());
+        }
+    }
 }
@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    audio_playback::SimpleTemporaryPlayer,
+    audio_playback::{SimpleTemporaryPlayer, DRUM_CHANNEL},
     midi::shared::{
         audio::CompressedAudio,
         timer::{SeekWaitResult, TimeListener, UnpauseWaitResult, WaitResult},
@@ -17,6 +17,25 @@ pub struct InRamAudioPlayer {
     timer: TimeListener,
     player: Arc<RwLock<SimpleTemporaryPlayer>>,
     index: usize,
+    channel_offset: u8,
+    /// The reset count of the synth when the controls were last sent to it
+    synth_resets: u64,
+}
+
+/// Moves a channel event over by the offset, wrapping around after channel 16. The
+/// drums stay on the drum channel, and the other channels skip over it, so that
+/// melodic parts and drums don't swap.
+fn offset_channel(data: u32, offset: u8) -> u32 {
+    let channel = (data & 0x0F) as usize;
+    if offset == 0 || !(0x80..0xF0).contains(&(data & 0xF0)) || channel == DRUM_CHANNEL {
+        return data;
+    }
+
+    // Counted among the 15 channels that aren't the drum channel
+    let melodic = |channel: usize| channel - (channel > DRUM_CHANNEL) as usize;
+    let moved = (melodic(channel) + offset as usize) % 15;
+    let moved = moved + (moved >= DRUM_CHANNEL) as usize;
+    (data & !0x0F) | moved as u32
 }
 
 impl InRamAudioPlayer {
@@ -30,9 +49,18 @@ impl InRamAudioPlayer {
             timer,
             player,
             index: 0,
+            channel_offset: 0,
+            synth_resets: 0,
         }
     }
 
+    /// Plays the events on other channels, so that a MIDI layered on top of
+    /// another one doesn't change its instruments
+    pub fn with_channel_offset(mut self, channel_offset: u8) -> Self {
+        self.channel_offset = channel_offset;
+        self
+    }
+
     pub fn spawn_playback(mut self) -> JoinHandle<()> {
         thread::spawn(move || loop {
             let reset = || {
@@ -86,7 +114,14 @@ impl InRamAudioPlayer {
             }
 
             if let Ok(mut player) = self.player.clone().write() {
-                player.push_events(event.iter_events());
+                // Another MIDI playing into the same synth seeked and reset it,
+                // so this one's controls have to be sent again
+                if player.reset_count() != self.synth_resets {
+                    self.push_controls(&mut player);
+                }
+                let offset = self.channel_offset;
+                let event = &self.events[self.index];
+                player.push_events(event.iter_events().map(|e| offset_channel(e, offset)));
             }
             self.index += 1;
         })
@@ -129,9 +164,21 @@ impl InRamAudioPlayer {
         // Reset and push all control events before
         if let Ok(mut player) = self.player.clone().write() {
             player.reset();
-            for i in 0..(self.index) {
-                player.push_events(self.events[i].iter_control_events());
-            }
+            player.clear_channels();
+            self.push_controls(&mut player);
         };
     }
+
+    /// Sends all the control events before the current one to the synth
+    fn push_controls(&mut self, player: &mut SimpleTemporaryPlayer) {
+        let offset = self.channel_offset;
+        for event in &self.events[..self.index] {
+            player.push_events(
+                event
+                    .iter_control_events()
+                    .map(|e| offset_channel(e, offset)),
+            );
+        }
+        self.synth_resets = player.reset_count();
+    }
 }
@@ -45,6 +45,28 @@ impl CakeBlock {
             })
         }
     }
+
+    /// Gives every note the color of its track and channel, going through the leaves
+    /// so that the notes can be told apart from them
+    pub fn recolor(&mut self, colors: &[i32]) {
+        let mut leaves = vec![self.tree[0].length_marker_len()];
+
+        while let Some(index) = leaves.pop() {
+            let node = self.tree[index];
+
+            for offset in [node.leaf_left(), node.leaf_right()] {
+                if offset > 0 {
+                    let note = &mut self.tree[index - offset as usize];
+                    if !note.is_note_empty() {
+                        note.set_note_color(colors[note.note_track_channel()]);
+                    }
+                } else {
+                    leaves.push(index - (-offset) as usize);
+                }
+            }
+        }
+    }
+
     pub fn get_notes_passed_at(&self, time: u32) -> u32 {
         let mut last_notes_passed;
         let mut next_index = self.tree[0].length_marker_len();
@@ -70,3 +92,48 @@ impl CakeBlock {
         last_notes_passed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::cake::tree_serializer::TreeSerializer;
+
+    /// A block of the start, end, and track and channel of each note
+    fn block(notes: &[(i32, i32, i32)]) -> CakeBlock {
+        let mut events: Vec<_> = notes
+            .iter()
+            .flat_map(|&(start, end, track_channel)| {
+                [(start, true, track_channel), (end, false, track_channel)]
+            })
+            .collect();
+        events.sort_by_key(|&(time, is_on, _)| (time, is_on));
+
+        let mut tree = TreeSerializer::new();
+        for (time, is_on, track_channel) in events {
+            if is_on {
+                tree.start_note(time, track_channel, 0);
+            } else {
+                tree.end_note(time, track_channel);
+            }
+        }
+        CakeBlock {
+            start_time: 0,
+            end_time: 100,
+            tree: tree.complete_and_seal(100),
+        }
+    }
+
+    #[test]
+    fn recolor_changes_every_note_but_not_the_gaps() {
+        let mut block = block(&[(0, 10, 1), (5, 20, 2), (30, 40, 1), (35, 60, 0)]);
+        block.recolor(&[0x0000FF, 0x00FF00, 0xFF0000]);
+
+        let color_at = |time| block.get_note_at(time).map(|n| n.color.as_u32());
+        assert_eq!(color_at(2), Some(0x00FF00));
+        assert_eq!(color_at(7), Some(0xFF0000));
+        assert_eq!(color_at(25), None);
+        assert_eq!(color_at(32), Some(0x00FF00));
+        assert_eq!(color_at(50), Some(0x0000FF));
+        assert_eq!(color_at(70), None);
+    }
+}
@@ -1,13 +1,5 @@
 use bytemuck::{Pod, Zeroable};
 
-use crate::midi::MIDIColor;
-
-fn get_col(c: i32) -> i32 {
-    let rgb = MIDIColor::new_from_hue((c * 123) as f64);
-
-    rgb.0 as i32
-}
-
 #[repr(C)]
 #[derive(Pod, Debug, Copy, Clone, Zeroable)]
 pub struct IntVector4 {
@@ -27,12 +19,14 @@ impl IntVector4 {
         }
     }
 
-    pub fn new_note(start: i32, end: i32, track_channel: i32) -> IntVector4 {
+    /// The track and channel are kept next to the color, so that the note can be
+    /// recolored later. The shaders only read the color.
+    pub fn new_note(start: i32, end: i32, color: i32, track_channel: i32) -> IntVector4 {
         IntVector4 {
             val1: start,
             val2: end,
-            val3: get_col(track_channel),
-            val4: 0,
+            val3: color,
+            val4: track_channel,
         }
     }
 
@@ -40,6 +34,10 @@ impl IntVector4 {
         self.val2 = end;
     }
 
+    pub fn set_note_color(&mut self, color: i32) {
+        self.val3 = color;
+    }
+
     pub fn new_empty() -> IntVector4 {
         IntVector4 {
             val1: 0,
@@ -95,6 +93,10 @@ impl IntVector4 {
         self.val3 as u32
     }
 
+    pub fn note_track_channel(&self) -> usize {
+        self.val4 as usize
+    }
+
     pub fn is_note_empty(&self) -> bool {
         self.val3 == -1
     }
@@ -1,4 +1,5 @@
 use std::{
+    ops::RangeInclusive,
     sync::{Arc, RwLock},
     thread,
     time::Duration,
@@ -6,27 +7,38 @@ use std::{
 
 use midi_toolkit::{
     events::{Event, MIDIEventEnum},
-    io::MIDIFile as TKMIDIFile,
     pipe,
     sequence::{
         event::{cancel_tempo_events, scale_event_time, Delta, EventBatch, Track},
         unwrap_items, TimeCaster,
     },
 };
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
 use crate::{
     audio_playback::SimpleTemporaryPlayer,
     midi::{
         audio::ram::InRamAudioPlayer,
         cake::tree_threader::{NoteEvent, ThreadedTreeSerializers},
-        open_file_and_signature,
-        shared::{audio::CompressedAudio, timer::TimeKeeper},
+        open_midi_and_signature,
+        shared::{
+            audio::CompressedAudio,
+            density::{NoteDensity, NoteDensityWriter},
+            division::{MIDIDivision, TempoCanceller},
+            musical_time::{MusicalTimeMap, MusicalTimeMapWriter},
+            timer::TimeKeeper,
+        },
+        summary::{MIDISummary, MIDISummaryWriter},
     },
+    settings::{colors::ColorAssignments, ColorBy, ColorPalette},
 };
 
 use self::blocks::CakeBlock;
 
-use super::{MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature};
+use super::{
+    Beat, MIDIColor, MIDIFileBase, MIDIFileLoadError, MIDIFileStats, MIDIFileUniqueSignature,
+    MIDIFormat, MusicalPosition,
+};
 
 pub mod blocks;
 pub mod intvec4;
@@ -39,27 +51,65 @@ pub struct CakeMIDIFile {
     timer: TimeKeeper,
     length: f64,
     note_count: u64,
+    track_note_counts: Vec<u64>,
+    note_density: NoteDensity,
     ticks_per_second: u32,
+    musical_time: Arc<RwLock<MusicalTimeMap>>,
     signature: MIDIFileUniqueSignature,
+    format: MIDIFormat,
+    colors: Vec<MIDIColor>,
+    /// The colors picked on load when the MIDI was loaded with random colors
+    random_colors: Option<Vec<MIDIColor>>,
+    /// How many times the notes were recolored, so that the renderer uploads them again
+    recolors: u64,
+    summary: MIDISummary,
 }
 
 impl CakeMIDIFile {
     pub fn load_from_file(
         path: &str,
         player: Arc<RwLock<SimpleTemporaryPlayer>>,
-        _random_colors: bool,
-    ) -> Self {
+        random_colors: bool,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+        tempo_override: Option<f64>,
+    ) -> Result<Self, MIDIFileLoadError> {
         let ticks_per_second = 10000;
 
-        let (file, signature) = open_file_and_signature(path);
-        let midi = TKMIDIFile::open_from_stream(file, None).unwrap();
+        let (midi, signature, format) = open_midi_and_signature(path)?;
 
-        let ppq = midi.ppq();
-        let merged = pipe!(
+        let division = MIDIDivision::from_raw(midi.ppq());
+        let track_count = midi.track_count();
+
+        let random_colors =
+            random_colors.then(|| MIDIColor::new_random_vec_for_tracks(track_count));
+        let colors = MIDIColor::new_vec_for_settings(
+            track_count,
+            random_colors.as_deref(),
+            palette,
+            color_by,
+            assignments,
+        );
+        let tree_colors = tree_colors(&colors);
+
+        let mut time_map = MusicalTimeMapWriter::new(division, tempo_override);
+        let mut tempo_canceller = TempoCanceller::new(division, tempo_override);
+        let musical_time = time_map.map();
+        let batches = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(move |batch| {
+            if let Ok(batch) = batch {
+                time_map.push_batch(batch);
+            }
+        })
+        .map(move |batch| tempo_canceller.cancel(batch));
+        let merged = pipe!(
+            batches
             |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
+            |>scale_event_time(1.0 / division.ticks_per_quarter())
             |>unwrap_items()
         );
 
@@ -73,6 +123,9 @@ impl CakeMIDIFile {
             let mut time = 0.0;
 
             let mut note_count = 0;
+            let mut track_note_counts = vec![0; track_count];
+            let mut density = NoteDensityWriter::new();
+            let mut summary = MIDISummaryWriter::new(format, track_count);
 
             for batch in key_rcv.into_iter() {
                 time += batch.delta;
@@ -92,9 +145,13 @@ impl CakeMIDIFile {
                                 NoteEvent::On {
                                     time: int_time,
                                     channel_track: channel_track(e.channel, track),
+                                    color: tree_colors[channel_track(e.channel, track) as usize],
                                 },
                             );
                             note_count += 1;
+                            track_note_counts[track as usize] += 1;
+                            density.push(time);
+                            summary.push_note(time, track as usize, e.channel, e.key);
                         }
                         Event::NoteOff(e) => {
                             trees.push_event(
@@ -104,6 +161,7 @@ impl CakeMIDIFile {
                                     channel_track: channel_track(e.channel, track),
                                 },
                             );
+                            summary.push_note_off(time);
                         }
                         _ => {}
                     }
@@ -121,7 +179,13 @@ impl CakeMIDIFile {
                 })
                 .collect();
 
-            (keys, note_count)
+            (
+                keys,
+                note_count,
+                track_note_counts,
+                density.finish(),
+                summary,
+            )
         });
 
         let audio_join_handle = thread::spawn(|| {
@@ -142,21 +206,31 @@ impl CakeMIDIFile {
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
+        let (keys, note_count, track_note_counts, note_density, summary) =
+            key_join_handle.join().unwrap();
+        let summary = summary.finish(length, division, &musical_time.read().unwrap());
         let audio = audio_join_handle.join().unwrap();
 
         let mut timer = TimeKeeper::new();
 
         InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
 
-        CakeMIDIFile {
+        Ok(CakeMIDIFile {
             blocks: keys,
             timer,
             length,
             note_count,
+            track_note_counts,
+            note_density,
             ticks_per_second,
+            musical_time,
             signature,
-        }
+            format,
+            colors,
+            random_colors,
+            recolors: 0,
+            summary,
+        })
     }
 
     pub fn key_blocks(&self) -> &[CakeBlock] {
@@ -176,10 +250,16 @@ impl CakeMIDIFile {
             file_signature: self.signature.clone(),
             note_count: self.note_count,
             buffer_sizes: self.blocks.iter().map(|b| b.tree.len()).collect(),
+            recolors: self.recolors,
         }
     }
 }
 
+/// The colors as they're written into the note trees
+fn tree_colors(colors: &[MIDIColor]) -> Vec<i32> {
+    colors.iter().map(|c| c.as_u32() as i32).collect()
+}
+
 /// A struct that uniquely identifies a cake midi file.
 /// This lets the renderer know if the file has changed.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -187,6 +267,7 @@ pub struct CakeSignature {
     file_signature: MIDIFileUniqueSignature,
     note_count: u64,
     buffer_sizes: Vec<usize>,
+    recolors: u64,
 }
 
 impl MIDIFileBase for CakeMIDIFile {
@@ -229,4 +310,60 @@ impl MIDIFileBase for CakeMIDIFile {
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn format(&self) -> MIDIFormat {
+        self.format
+    }
+
+    fn position_musical(&self) -> MusicalPosition {
+        let time = self.timer.get_time().as_secs_f64();
+        self.musical_time.read().unwrap().position_at(time)
+    }
+
+    fn beat_at(&self, time: f64) -> Beat {
+        self.musical_time.read().unwrap().beat_at(time)
+    }
+    fn track_note_counts(&self) -> Option<&[u64]> {
+        Some(&self.track_note_counts)
+    }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        Some(&self.note_density)
+    }
+
+    fn active_key_range(&self) -> Option<RangeInclusive<usize>> {
+        // The notes are only read on the GPU
+        None
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        &self.colors
+    }
+
+    fn summary(&self) -> Option<&MIDISummary> {
+        Some(&self.summary)
+    }
+
+    fn recolor(
+        &mut self,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) {
+        let track_count = self.colors.len() / 16;
+        self.colors = MIDIColor::new_vec_for_settings(
+            track_count,
+            self.random_colors.as_deref(),
+            palette,
+            color_by,
+            assignments,
+        );
+
+        // The colors are written into the note trees, so every note gets rewritten
+        let tree_colors = tree_colors(&self.colors);
+        self.blocks
+            .par_iter_mut()
+            .for_each(|block| block.recolor(&tree_colors));
+        self.recolors += 1;
+    }
 }
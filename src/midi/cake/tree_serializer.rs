@@ -16,6 +16,7 @@ enum TreeFrame {
 
 struct NoteMarker {
     start: i32,
+    color: i32,
     track_channel: i32,
     written_pos: Option<i32>,
 }
@@ -69,6 +70,7 @@ impl TreeSerializer {
                     self.written_values.push(IntVector4::new_note(
                         marker.start,
                         0,
+                        marker.color,
                         marker.track_channel,
                     ));
                     marker.written_pos = Some(written_pos);
@@ -113,7 +115,7 @@ impl TreeSerializer {
 
     /// Processes a note start. If the time is greater than the last tree time, the tree is
     /// updated to the new time. Then, the note is pushed to the note stack.
-    pub fn start_note(&mut self, time: i32, track_channel: i32) {
+    pub fn start_note(&mut self, time: i32, track_channel: i32, color: i32) {
         if time > self.last_tree_time {
             self.process_change(time);
         }
@@ -124,6 +126,7 @@ impl TreeSerializer {
             track_channel,
             NoteMarker {
                 start: time,
+                color,
                 track_channel,
                 written_pos: None,
             },
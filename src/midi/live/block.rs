@@ -14,21 +14,23 @@ pub struct LiveNoteBlock {
 pub struct LiveMIDINote {
     pub len: f32,
     pub track_chan: TrackAndChannel,
+    pub velocity: u8,
 }
 
 impl LiveNoteBlock {
-    /// Creates a new block from an iterator of Track/Channel values.
+    /// Creates a new block from an iterator of Track/Channel values and velocities.
     /// This assumes that the lengths will be added in the future.
     pub fn new_from_trackchans(
         time: f64,
-        track_chans_iter: impl ExactSizeIterator<Item = TrackAndChannel>,
+        track_chans_iter: impl ExactSizeIterator<Item = (TrackAndChannel, u8)>,
     ) -> Self {
         let mut notes: Vec<LiveMIDINote> = Vec::with_capacity(track_chans_iter.len());
 
-        for track_chan in track_chans_iter {
+        for (track_chan, velocity) in track_chans_iter {
             notes.push(LiveMIDINote {
                 len: f32::INFINITY,
                 track_chan,
+                velocity,
             });
         }
 
@@ -63,7 +65,7 @@ unsafe impl Sync for LiveRefNoteBlock {}
 impl LiveRefNoteBlock {
     pub fn new_from_trackchans(
         time: f64,
-        track_chans_iter: impl ExactSizeIterator<Item = TrackAndChannel>,
+        track_chans_iter: impl ExactSizeIterator<Item = (TrackAndChannel, u8)>,
     ) -> (
         Self,
         impl ExactSizeIterator<Item = LiveNoteEnderHandleWithTrackChan>,
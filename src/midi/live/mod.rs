@@ -1,11 +1,15 @@
 use std::{
+    ops::RangeInclusive,
     sync::{Arc, RwLock},
     thread,
 };
 
-use midi_toolkit::{io::MIDIFile as TKMIDIFile, sequence::event::get_channels_array_statistics};
+use midi_toolkit::sequence::event::get_channels_array_statistics;
 
-use crate::audio_playback::SimpleTemporaryPlayer;
+use crate::{
+    audio_playback::SimpleTemporaryPlayer,
+    settings::{colors::ColorAssignments, ColorBy, ColorPalette},
+};
 
 use self::{
     parse::LiveMidiParser,
@@ -13,8 +17,13 @@ use self::{
 };
 
 use super::{
-    open_file_and_signature, shared::timer::TimeKeeper, MIDIFile, MIDIFileBase, MIDIFileStats,
-    MIDIFileUniqueSignature, MIDIViewRange,
+    open_midi_and_signature,
+    shared::{
+        density::NoteDensity, division::MIDIDivision, musical_time::MusicalTimeMap,
+        timer::TimeKeeper,
+    },
+    Beat, MIDIColor, MIDIFile, MIDIFileBase, MIDIFileLoadError, MIDIFileStats,
+    MIDIFileUniqueSignature, MIDIFormat, MIDISummary, MIDIViewRange, MusicalPosition,
 };
 
 pub mod block;
@@ -31,7 +40,9 @@ pub struct LiveLoadMIDIFile {
     view_data: LiveNoteViewData,
     timer: TimeKeeper,
     stats: Arc<RwLock<Option<ParseStats>>>,
+    musical_time: Arc<RwLock<MusicalTimeMap>>,
     signature: MIDIFileUniqueSignature,
+    format: MIDIFormat,
 }
 
 impl LiveLoadMIDIFile {
@@ -39,22 +50,71 @@ impl LiveLoadMIDIFile {
         path: &str,
         player: Arc<RwLock<SimpleTemporaryPlayer>>,
         random_colors: bool,
-    ) -> Self {
-        let (file, signature) = open_file_and_signature(path);
+        palette: ColorPalette,
+        color_by: ColorBy,
+        tempo_override: Option<f64>,
+    ) -> Result<Self, MIDIFileLoadError> {
+        Self::load(
+            path,
+            player,
+            random_colors,
+            palette,
+            color_by,
+            tempo_override,
+            true,
+        )
+    }
 
-        let midi = TKMIDIFile::open_from_stream(file, None).unwrap();
+    /// Loads the MIDI only to play it, for when nothing is rendered. The notes are
+    /// otherwise kept until they're drawn, so they would pile up for the whole MIDI.
+    pub fn load_without_notes(
+        path: &str,
+        player: Arc<RwLock<SimpleTemporaryPlayer>>,
+        tempo_override: Option<f64>,
+    ) -> Result<Self, MIDIFileLoadError> {
+        Self::load(
+            path,
+            player,
+            false,
+            ColorPalette::default(),
+            ColorBy::default(),
+            tempo_override,
+            false,
+        )
+    }
+
+    fn load(
+        path: &str,
+        player: Arc<RwLock<SimpleTemporaryPlayer>>,
+        random_colors: bool,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        tempo_override: Option<f64>,
+        parse_notes: bool,
+    ) -> Result<Self, MIDIFileLoadError> {
+        let (midi, signature, format) = open_midi_and_signature(path)?;
 
         let stats_outer = Arc::new(RwLock::new(None));
         let stats = stats_outer.clone();
 
-        let ppq = midi.ppq();
+        // When the tempo events are ignored every tick has the same length, so the
+        // length follows from the ticks instead of the tempo events
+        let division = MIDIDivision::from_raw(midi.ppq());
+        let ppq = division.ticks_per_quarter().round() as u16;
+        let seconds_per_tick = division
+            .fixed_tempo(tempo_override)
+            .map(|tempo| tempo / 1_000_000.0 / division.ticks_per_quarter());
         let tracks = midi.iter_all_tracks().collect();
         thread::spawn(move || {
             let stats = get_channels_array_statistics(tracks);
             if let Ok(stats) = stats {
+                let length = match seconds_per_tick {
+                    Some(seconds_per_tick) => stats.total_length_ticks() as f64 * seconds_per_tick,
+                    None => stats.calculate_total_duration(ppq).as_secs_f64(),
+                };
                 let mut parser_stats = stats_outer.write().unwrap();
                 *parser_stats = Some(ParseStats {
-                    length: stats.calculate_total_duration(ppq).as_secs_f64(),
+                    length,
                     note_count: stats.note_count(),
                 });
             }
@@ -62,15 +122,19 @@ impl LiveLoadMIDIFile {
 
         let mut timer = TimeKeeper::new();
 
-        let parer = LiveMidiParser::init(&midi, player, &mut timer);
-        let file = LiveNoteViewData::new(parer, midi.track_count(), random_colors);
+        let parer = LiveMidiParser::init(&midi, player, &mut timer, tempo_override, parse_notes);
+        let musical_time = parer.musical_time();
+        let file =
+            LiveNoteViewData::new(parer, midi.track_count(), random_colors, palette, color_by);
 
-        LiveLoadMIDIFile {
+        Ok(LiveLoadMIDIFile {
             view_data: file,
             timer,
             stats,
+            musical_time,
             signature,
-        }
+            format,
+        })
     }
 }
 
@@ -108,12 +172,56 @@ impl MIDIFileBase for LiveLoadMIDIFile {
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn format(&self) -> MIDIFormat {
+        self.format
+    }
+
+    fn position_musical(&self) -> MusicalPosition {
+        let time = self.timer.get_time().as_secs_f64();
+        self.musical_time.read().unwrap().position_at(time)
+    }
+
+    fn beat_at(&self, time: f64) -> Beat {
+        self.musical_time.read().unwrap().beat_at(time)
+    }
+
+    fn track_note_counts(&self) -> Option<&[u64]> {
+        // The notes are parsed while playing, so the counts aren't known ahead of time
+        None
+    }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        None
+    }
+
+    fn active_key_range(&self) -> Option<RangeInclusive<usize>> {
+        self.view_data.active_key_range()
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        self.view_data.track_colors()
+    }
+
+    fn summary(&self) -> Option<&MIDISummary> {
+        // The notes are parsed while playing, so the whole file hasn't been seen yet
+        None
+    }
+
+    fn recolor(
+        &mut self,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) {
+        self.view_data.set_palette(palette, color_by, assignments);
+    }
 }
 
 impl MIDIFile for LiveLoadMIDIFile {
     type ColumnsViews<'a> = LiveCurrentNoteViews<'a> where Self: 'a;
 
-    fn get_current_column_views(&mut self, range: f64) -> Self::ColumnsViews<'_> {
+    fn get_current_column_views(&mut self, range: f64, _sustain: bool) -> Self::ColumnsViews<'_> {
         let time = self.timer.get_time().as_secs_f64();
         let new_range = MIDIViewRange::new(time, time + range);
         self.view_data.shift_view_range(new_range);
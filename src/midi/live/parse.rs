@@ -20,7 +20,11 @@ use crate::{
     audio_playback::SimpleTemporaryPlayer,
     midi::{
         audio::live::LiveAudioPlayer,
-        shared::timer::{TimeKeeper, WaitResult},
+        shared::{
+            division::{MIDIDivision, TempoCanceller},
+            musical_time::{MusicalTimeMap, MusicalTimeMapWriter},
+            timer::{TimeKeeper, WaitResult},
+        },
     },
 };
 
@@ -38,30 +42,51 @@ pub struct ThreadManager {
 
 pub struct LiveMidiParser {
     file_manager: ThreadManager,
-    note_manager: ThreadManager,
+    note_manager: Option<ThreadManager>,
     audio_manager: ThreadManager,
     note_reciever: Receiver<LiveNoteBlockWithKey>,
+    musical_time: Arc<RwLock<MusicalTimeMap>>,
 }
 
 impl LiveMidiParser {
+    /// Starts parsing the MIDI in the background. Without `parse_notes` only the
+    /// audio is played, and no notes are sent to be drawn.
     pub fn init(
         midi: &TKMIDIFile<DiskReader>,
         player: Arc<RwLock<SimpleTemporaryPlayer>>,
         timer: &mut TimeKeeper,
+        tempo_override: Option<f64>,
+        parse_notes: bool,
     ) -> Self {
-        let ppq = midi.ppq();
-        let merged = pipe!(
+        let division = MIDIDivision::from_raw(midi.ppq());
+        let mut time_map = MusicalTimeMapWriter::new(division, tempo_override);
+        let mut tempo_canceller = TempoCanceller::new(division, tempo_override);
+        let musical_time = time_map.map();
+        let batches = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(move |batch| {
+            if let Ok(batch) = batch {
+                time_map.push_batch(batch);
+            }
+        })
+        .map(move |batch| tempo_canceller.cancel(batch));
+        let merged = pipe!(
+            batches
             |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
+            |>scale_event_time(1.0 / division.ticks_per_quarter())
             |>unwrap_items()
         );
 
-        let (note_snd, note_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let (note_snd, notes) = if parse_notes {
+            let (note_snd, note_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+            (Some(note_snd), Some(notes::init_note_manager(note_rcv)))
+        } else {
+            (None, None)
+        };
         let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
 
-        let notes = notes::init_note_manager(note_rcv);
         let audio = audio::init_audio_manager(audio_rcv);
 
         LiveAudioPlayer::new(audio.reciever, timer.get_listener(), player).spawn_playback();
@@ -86,9 +111,11 @@ impl LiveMidiParser {
 
                 let block = Arc::new(block);
 
-                let res = note_snd.send(block.clone());
-                if res.is_err() {
-                    break;
+                if let Some(note_snd) = &note_snd {
+                    let res = note_snd.send(block.clone());
+                    if res.is_err() {
+                        break;
+                    }
                 }
 
                 let res = audio_snd.send(block);
@@ -103,9 +130,13 @@ impl LiveMidiParser {
                 handle: file_handle,
                 parse_time: parse_time_outer,
             },
-            note_manager: notes.manager,
+            note_reciever: match &notes {
+                Some(notes) => notes.reciever.clone(),
+                None => crossbeam_channel::never(),
+            },
+            note_manager: notes.map(|notes| notes.manager),
             audio_manager: audio.manager,
-            note_reciever: notes.reciever,
+            musical_time,
         }
     }
 
@@ -119,6 +150,10 @@ impl LiveMidiParser {
         self.file_manager.parse_time.load(Ordering::Relaxed)
     }
 
+    pub fn musical_time(&self) -> Arc<RwLock<MusicalTimeMap>> {
+        self.musical_time.clone()
+    }
+
     pub fn recieve_next_note_blocks(&self) -> impl '_ + Iterator<Item = LiveNoteBlockWithKey> {
         self.note_reciever.try_iter()
     }
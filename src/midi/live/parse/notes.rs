@@ -88,7 +88,7 @@ impl UnendedNotesHandler {
 
 struct ParserState {
     unended_notes: UnendedNotesHandler,
-    keys: Box<[Vec<TrackAndChannel>]>,
+    keys: Box<[Vec<(TrackAndChannel, u8)>]>,
     sender: Sender<LiveNoteBlockWithKey>,
 }
 
@@ -105,8 +105,8 @@ impl ParserState {
         }
     }
 
-    fn add_note(&mut self, key: u8, track_chan: TrackAndChannel) {
-        self.keys[key as usize].push(track_chan);
+    fn add_note(&mut self, key: u8, track_chan: TrackAndChannel, velocity: u8) {
+        self.keys[key as usize].push((track_chan, velocity));
     }
 
     fn flush(&mut self, time: f64) -> Result<(), ()> {
@@ -166,7 +166,8 @@ pub fn init_note_manager(blocks: Receiver<Arc<TrackEventBatch>>) -> NoteParserRe
             for event in block.iter_events() {
                 match event.as_event() {
                     Event::NoteOn(e) => {
-                        state.add_note(e.key, TrackAndChannel::new(event.track, e.channel));
+                        let track_chan = TrackAndChannel::new(event.track, e.channel);
+                        state.add_note(e.key, track_chan, e.velocity);
                     }
                     Event::NoteOff(e) => {
                         state.end_note(e.key, TrackAndChannel::new(event.track, e.channel), time);
@@ -1,9 +1,14 @@
 #![allow(dead_code)]
 
+use std::ops::RangeInclusive;
+
 use gen_iter::GenIter;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-use crate::midi::{DisplacedMIDINote, MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange};
+use crate::{
+    midi::{DisplacedMIDINote, MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange},
+    settings::{colors::ColorAssignments, ColorBy, ColorPalette},
+};
 
 use super::{column::LiveNoteColumn, parse::LiveMidiParser};
 
@@ -11,6 +16,8 @@ pub struct LiveNoteViewData {
     parser: LiveMidiParser,
     columns: Vec<LiveNoteColumn>,
     default_track_colors: Vec<MIDIColor>,
+    /// The colors picked when the MIDI was loaded with random colors
+    random_track_colors: Option<Vec<MIDIColor>>,
     view_range: MIDIViewRange,
 }
 
@@ -25,9 +32,17 @@ impl<'a> LiveCurrentNoteViews<'a> {
 }
 
 impl LiveNoteViewData {
-    pub fn new(parser: LiveMidiParser, track_count: usize, random_colors: bool) -> Self {
+    pub fn new(
+        parser: LiveMidiParser,
+        track_count: usize,
+        random_colors: bool,
+        palette: ColorPalette,
+        color_by: ColorBy,
+    ) -> Self {
         let mut columns = Vec::with_capacity(256);
         columns.resize_with(256, LiveNoteColumn::new);
+        let random_track_colors =
+            random_colors.then(|| MIDIColor::new_random_vec_for_tracks(track_count));
         LiveNoteViewData {
             parser,
             columns,
@@ -35,14 +50,37 @@ impl LiveNoteViewData {
                 start: 0.0,
                 end: 0.0,
             },
-            default_track_colors: if random_colors {
-                MIDIColor::new_random_vec_for_tracks(track_count)
-            } else {
-                MIDIColor::new_vec_for_tracks(track_count)
-            },
+            default_track_colors: MIDIColor::new_vec_for_settings(
+                track_count,
+                random_track_colors.as_deref(),
+                palette,
+                color_by,
+                &ColorAssignments::default(),
+            ),
+            random_track_colors,
         }
     }
 
+    pub fn set_palette(
+        &mut self,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) {
+        let track_count = self.default_track_colors.len() / 16;
+        self.default_track_colors = MIDIColor::new_vec_for_settings(
+            track_count,
+            self.random_track_colors.as_deref(),
+            palette,
+            color_by,
+            assignments,
+        );
+    }
+
+    pub fn track_colors(&self) -> &[MIDIColor] {
+        &self.default_track_colors
+    }
+
     pub fn shift_view_range(&mut self, new_view_range: MIDIViewRange) {
         if self.view_range.start > new_view_range.start {
             panic!("Can't shift live loaded view range backwards");
@@ -110,6 +148,13 @@ impl LiveNoteViewData {
             .map(|column| column.data.notes_passed_keyboard)
             .sum()
     }
+
+    pub fn active_key_range(&self) -> Option<RangeInclusive<usize>> {
+        let is_active = |column: &LiveNoteColumn| column.data.rendered_notes > 0;
+        let first = self.columns.iter().position(is_active)?;
+        let last = self.columns.iter().rposition(is_active)?;
+        Some(first..=last)
+    }
 }
 
 pub struct LiveNoteColumnView<'a> {
@@ -155,6 +200,10 @@ impl<'a> MIDINoteColumnView for LiveNoteColumnView<'a> {
                         start,
                         len: note.len,
                         color: colors[note.track_chan.as_usize()],
+                        channel: note.track_chan.channel(),
+                        velocity: note.velocity,
+                        // The pedal isn't tracked while the MIDI is still being parsed
+                        sustain: 0.0,
                     };
                 }
             }
@@ -8,9 +8,18 @@ mod ram;
 mod audio;
 
 mod shared;
-use std::{fs::File, time::UNIX_EPOCH};
+mod summary;
+mod validate;
+use std::{
+    fmt,
+    io::{self, Cursor},
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+    time::UNIX_EPOCH,
+};
 
 use enum_dispatch::enum_dispatch;
+use midi_toolkit::io::{DiskReader, MIDIFile as TKMIDIFile};
 use palette::{convert::FromColorUnclamped, Hsv, Srgb};
 use rand::Rng;
 
@@ -18,7 +27,22 @@ pub use cake::{blocks::CakeBlock, intvec4::IntVector4, CakeMIDIFile, CakeSignatu
 pub use live::LiveLoadMIDIFile;
 pub use ram::InRamMIDIFile;
 
-use self::shared::timer::TimeKeeper;
+pub use self::shared::{
+    container::{download_midi, is_stdin_path, is_url},
+    density::NoteDensity,
+    format::MIDIFormat,
+    musical_time::{Beat, MusicalPosition},
+};
+use self::shared::{
+    container::{read_remote_midi, unwrap_midi_container, MIDIStream},
+    timer::TimeKeeper,
+};
+use crate::{
+    audio_playback::SimpleTemporaryPlayer,
+    settings::{colors::ColorAssignments, ColorBy, ColorPalette, MidiLoading},
+};
+pub use summary::{export_midi_summary, MIDISummary};
+pub use validate::MIDIValidation;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MIDIFileStats {
@@ -50,17 +74,63 @@ pub struct MIDIFileUniqueSignature {
     pub last_modified: u128,
 }
 
-fn open_file_and_signature(path: &str) -> (File, MIDIFileUniqueSignature) {
-    let file = std::fs::File::open(path).unwrap();
-    let file_length = file.metadata().unwrap().len();
-    let file_last_modified = file
-        .metadata()
-        .unwrap()
+#[derive(Debug)]
+pub enum MIDIFileLoadError {
+    Io(io::Error),
+    MissingMIDIChunk,
+    Download(String),
+    NotAMIDI,
+    Parse(String),
+}
+
+impl fmt::Display for MIDIFileLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MIDIFileLoadError::Io(e) => write!(f, "Failed to read the file: {e}"),
+            MIDIFileLoadError::MissingMIDIChunk => {
+                write!(f, "The RIFF container doesn't contain a MIDI data chunk")
+            }
+            MIDIFileLoadError::Download(e) => write!(f, "Failed to download the file: {e}"),
+            MIDIFileLoadError::NotAMIDI => write!(f, "The data is not a MIDI file"),
+            MIDIFileLoadError::Parse(e) => write!(f, "Failed to parse the MIDI: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for MIDIFileLoadError {
+    fn from(e: io::Error) -> Self {
+        MIDIFileLoadError::Io(e)
+    }
+}
+
+fn open_midi_and_signature(
+    path: &str,
+) -> Result<(TKMIDIFile<DiskReader>, MIDIFileUniqueSignature, MIDIFormat), MIDIFileLoadError> {
+    if let Some(bytes) = read_remote_midi(path) {
+        let bytes = bytes?;
+        let signature = MIDIFileUniqueSignature {
+            filepath: path.to_string(),
+            length_in_bytes: bytes.len() as u64,
+            last_modified: 0,
+        };
+
+        let mut stream = unwrap_midi_container(MIDIStream::Embedded(Cursor::new(bytes)))?;
+        let format = MIDIFormat::read(&mut stream)?;
+        let midi = TKMIDIFile::open_from_stream(stream, None)
+            .map_err(|e| MIDIFileLoadError::Parse(format!("{e:?}")))?;
+
+        return Ok((midi, signature, format));
+    }
+
+    let file = std::fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let file_length = metadata.len();
+    let file_last_modified = metadata
         .modified()
-        .unwrap()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_micros();
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_micros())
+        .unwrap_or(0);
 
     let signature = MIDIFileUniqueSignature {
         filepath: path.to_string(),
@@ -68,7 +138,12 @@ fn open_file_and_signature(path: &str) -> (File, MIDIFileUniqueSignature) {
         last_modified: file_last_modified,
     };
 
-    (file, signature)
+    let mut stream = unwrap_midi_container(MIDIStream::File(file))?;
+    let format = MIDIFormat::read(&mut stream)?;
+    let midi = TKMIDIFile::open_from_stream(stream, None)
+        .map_err(|e| MIDIFileLoadError::Parse(format!("{e:?}")))?;
+
+    Ok((midi, signature, format))
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -104,6 +179,63 @@ impl MIDIColor {
         vec
     }
 
+    /// The colors for each channel of each track, using one of the built-in palettes
+    pub fn new_vec_for_palette(
+        tracks: usize,
+        palette: ColorPalette,
+        color_by: ColorBy,
+    ) -> Vec<Self> {
+        // The Okabe-Ito palette, ordered so that neighbouring channels differ the most
+        const COLORBLIND: [u32; 8] = [
+            0xE69F00, 0x0072B2, 0xF0E442, 0xCC79A7, 0x009E73, 0xD55E00, 0x56B4E9, 0xBBBBBB,
+        ];
+        // Alternates between light and dark, so neighbouring channels stay apart
+        const GRAYSCALE: [u8; 8] = [230, 110, 190, 70, 250, 150, 210, 90];
+
+        let count = tracks * 16;
+
+        let mut vec = Vec::with_capacity(count);
+        for i in 0..count {
+            let track = i / 16;
+            let channel = i % 16;
+            let value = match color_by {
+                ColorBy::Both => track + channel,
+                ColorBy::Track => track,
+                ColorBy::Channel => channel,
+            };
+            vec.push(match palette {
+                ColorPalette::Default => MIDIColor::new_from_hue(value as f64 * -16.0 % 360.0),
+                ColorPalette::Colorblind => MIDIColor(COLORBLIND[value % COLORBLIND.len()]),
+                ColorPalette::Grayscale => {
+                    let v = GRAYSCALE[value % GRAYSCALE.len()];
+                    MIDIColor::new(v, v, v)
+                }
+                ColorPalette::Pastel => {
+                    let hsv: Hsv<Srgb, f64> = Hsv::new(value as f64 * -16.0 % 360.0, 0.4, 0.95);
+                    let rgb = palette::rgb::Rgb::from_color_unclamped(hsv);
+                    Self::new(
+                        (rgb.red * 255.0) as u8,
+                        (rgb.green * 255.0) as u8,
+                        (rgb.blue * 255.0) as u8,
+                    )
+                }
+            });
+        }
+
+        vec
+    }
+
+    /// Replaces the colors of the tracks and channels that have a color assigned
+    pub fn apply_assignments(colors: &mut [Self], assignments: &ColorAssignments) {
+        for (i, color) in colors.iter_mut().enumerate() {
+            if let Some(assigned) = assignments.color(i / 16, i % 16) {
+                *color = MIDIColor::new(assigned.r(), assigned.g(), assigned.b());
+            }
+        }
+    }
+
+    /// A random color for every channel of every track. They're kept for as long as
+    /// the MIDI is loaded, so that changing the color grouping doesn't pick new ones.
     pub fn new_random_vec_for_tracks(tracks: usize) -> Vec<Self> {
         let count = tracks * 16;
 
@@ -118,6 +250,47 @@ impl MIDIColor {
         vec
     }
 
+    /// The colors that the tracks are drawn with: the random colors grouped by the
+    /// track or channel, or otherwise the palette with the assigned colors on top
+    pub fn new_vec_for_settings(
+        tracks: usize,
+        random: Option<&[Self]>,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) -> Vec<Self> {
+        let Some(random) = random else {
+            let mut colors = Self::new_vec_for_palette(tracks, palette, color_by);
+            Self::apply_assignments(&mut colors, assignments);
+            return colors;
+        };
+
+        // Every channel of a track, or every track of a channel, shares one random color
+        let count = tracks * 16;
+        match color_by {
+            ColorBy::Both => random.to_vec(),
+            ColorBy::Track => (0..count).map(|i| random[i / 16]).collect(),
+            ColorBy::Channel => (0..count).map(|i| random[i % 16]).collect(),
+        }
+    }
+
+    /// Applies the brightness and saturation from the visual settings. The note
+    /// shaders do the same conversion, so that the keys match the notes.
+    pub fn adjusted(self, brightness: f32, saturation: f32) -> Self {
+        if brightness == 1.0 && saturation == 1.0 {
+            return self;
+        }
+
+        let [r, g, b] = [self.red(), self.green(), self.blue()].map(|c| c as f32 / 255.0);
+        let luma = r * 0.299 + g * 0.587 + b * 0.114;
+        let adjust = |c: f32| {
+            let c = (luma + (c - luma) * saturation) * brightness;
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+
+        Self::new(adjust(r), adjust(g), adjust(b))
+    }
+
     pub fn as_u32(&self) -> u32 {
         self.0
     }
@@ -154,6 +327,34 @@ pub trait MIDIFileBase {
     fn allows_seeking_backward(&self) -> bool;
 
     fn signature(&self) -> &MIDIFileUniqueSignature;
+
+    /// The format number from the header of the MIDI
+    fn format(&self) -> MIDIFormat;
+
+    /// The current playback position in bars, beats and ticks
+    fn position_musical(&self) -> MusicalPosition;
+
+    /// The beat that is playing at the given time, for the beat flash
+    fn beat_at(&self, time: f64) -> Beat;
+
+    /// The amount of notes in each track, if they are known
+    fn track_note_counts(&self) -> Option<&[u64]>;
+
+    /// How many notes start throughout the MIDI, if it was parsed ahead of time
+    fn note_density(&self) -> Option<&NoteDensity>;
+
+    /// The lowest and highest keys with notes in the last rendered view range, if known
+    fn active_key_range(&self) -> Option<RangeInclusive<usize>>;
+
+    /// The color of every channel of every track, 16 for each track
+    fn track_colors(&self) -> &[MIDIColor];
+
+    /// The statistics of the whole MIDI, if it was parsed ahead of time
+    fn summary(&self) -> Option<&MIDISummary>;
+
+    /// Gives the tracks the colors of another palette, with the assigned colors on top.
+    /// MIDIs loaded with random colors keep them, and only group them differently.
+    fn recolor(&mut self, palette: ColorPalette, color_by: ColorBy, assignments: &ColorAssignments);
 }
 
 /// This trait contains a function to retrieve the column view of the midi
@@ -162,7 +363,9 @@ pub trait MIDIFile: MIDIFileBase {
     where
         Self: 'a;
 
-    fn get_current_column_views(&mut self, range: f64) -> Self::ColumnsViews<'_>;
+    /// `sustain` asks for the tails of the notes held by the sustain pedal, which
+    /// take a lookup for every note
+    fn get_current_column_views(&mut self, range: f64, sustain: bool) -> Self::ColumnsViews<'_>;
 }
 
 pub trait MIDINoteViews {
@@ -186,6 +389,10 @@ pub struct DisplacedMIDINote {
     pub start: f32,
     pub len: f32,
     pub color: MIDIColor,
+    pub channel: u8,
+    pub velocity: u8,
+    /// How long the sustain pedal keeps the note sounding after it ends
+    pub sustain: f32,
 }
 
 #[enum_dispatch(MIDIFileBase)]
@@ -194,3 +401,111 @@ pub enum MIDIFileUnion {
     Live(live::LiveLoadMIDIFile),
     Cake(cake::CakeMIDIFile),
 }
+
+impl MIDIFileUnion {
+    pub fn load_from_file(
+        path: &str,
+        loading: MidiLoading,
+        player: Arc<RwLock<SimpleTemporaryPlayer>>,
+        random_colors: bool,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+        tempo_override: Option<f64>,
+    ) -> Result<Self, MIDIFileLoadError> {
+        let mut midi = match loading {
+            MidiLoading::Ram => InRamMIDIFile::load_from_file(
+                path,
+                player,
+                random_colors,
+                palette,
+                color_by,
+                tempo_override,
+                0,
+            )
+            .map(Self::InRam),
+            MidiLoading::Live => LiveLoadMIDIFile::load_from_file(
+                path,
+                player,
+                random_colors,
+                palette,
+                color_by,
+                tempo_override,
+            )
+            .map(Self::Live),
+            MidiLoading::Cake => CakeMIDIFile::load_from_file(
+                path,
+                player,
+                random_colors,
+                palette,
+                color_by,
+                assignments,
+                tempo_override,
+            )
+            .map(Self::Cake),
+        }?;
+
+        if !random_colors && !assignments.is_empty() {
+            midi.recolor(palette, color_by, assignments);
+        }
+        Ok(midi)
+    }
+
+    /// Loads a MIDI to play on top of another one. Layers are always loaded into
+    /// RAM, so that they can follow the seeking of the main MIDI.
+    ///
+    /// The layer plays from its own thread, as the main MIDI might be streamed
+    /// in live and can't have the layer's events merged into it. The players
+    /// share the synth, and send their controls again when another one resets it.
+    pub fn load_layer_from_file(
+        path: &str,
+        player: Arc<RwLock<SimpleTemporaryPlayer>>,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        tempo_override: Option<f64>,
+        channel_offset: u8,
+    ) -> Result<Self, MIDIFileLoadError> {
+        InRamMIDIFile::load_from_file(
+            path,
+            player,
+            false,
+            palette,
+            color_by,
+            tempo_override,
+            channel_offset,
+        )
+        .map(Self::InRam)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 25 fps with 40 ticks per frame, so 1000 ticks per second. A note is held from
+    /// 0 to 2500 ticks, with a tempo event at 500 ticks that has to be ignored.
+    const SMPTE_MIDI: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/smpte.mid");
+
+    fn load(loading: MidiLoading) -> MIDIFileUnion {
+        let player = Arc::new(RwLock::new(SimpleTemporaryPlayer::without_synth()));
+        MIDIFileUnion::load_from_file(
+            SMPTE_MIDI,
+            loading,
+            player,
+            false,
+            ColorPalette::Default,
+            ColorBy::Both,
+            &ColorAssignments::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn smpte_length_ignores_the_tempo() {
+        for loading in [MidiLoading::Ram, MidiLoading::Cake] {
+            let length = load(loading).midi_length().unwrap();
+            assert!((length - 2.5).abs() < 1e-9, "{length}");
+        }
+    }
+}
@@ -10,21 +10,23 @@ pub struct InRamNoteBlock {
 pub struct BasicMIDINote {
     pub len: f32,
     pub track_chan: TrackAndChannel,
+    pub velocity: u8,
 }
 
 impl InRamNoteBlock {
-    /// Creates a new block from an iterator of Track/Channel values.
+    /// Creates a new block from an iterator of Track/Channel values and velocities.
     /// This assumes that the lengths will be added in the future.
     pub fn new_from_trackchans(
         time: f64,
-        track_chans_iter: impl ExactSizeIterator<Item = TrackAndChannel>,
+        track_chans_iter: impl ExactSizeIterator<Item = (TrackAndChannel, u8)>,
     ) -> Self {
         let mut notes: Vec<BasicMIDINote> = Vec::with_capacity(track_chans_iter.len());
 
-        for track_chan in track_chans_iter {
+        for (track_chan, velocity) in track_chans_iter {
             notes.push(BasicMIDINote {
                 len: 0.0,
                 track_chan,
+                velocity,
             });
         }
 
@@ -1,8 +1,16 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+};
+
+use crate::settings::{colors::ColorAssignments, ColorBy, ColorPalette};
+
 use self::view::{InRamCurrentNoteViews, InRamNoteViewData};
 
 use super::{
-    shared::timer::TimeKeeper, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature,
-    MIDIViewRange,
+    shared::{density::NoteDensity, musical_time::MusicalTimeMap, timer::TimeKeeper},
+    Beat, MIDIColor, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature, MIDIFormat,
+    MIDISummary, MIDIViewRange, MusicalPosition,
 };
 
 pub mod block;
@@ -10,15 +18,32 @@ pub mod column;
 mod parse;
 pub mod view;
 
+/// A lyric event, e.g. from a karaoke (.kar) file
+#[derive(Debug, Clone)]
+pub struct MIDILyric {
+    pub time: f64,
+    pub text: String,
+}
+
 pub struct InRamMIDIFile {
     view_data: InRamNoteViewData,
     timer: TimeKeeper,
     length: f64,
     note_count: u64,
+    track_note_counts: Vec<u64>,
+    note_density: NoteDensity,
+    lyrics: Vec<MIDILyric>,
+    musical_time: Arc<RwLock<MusicalTimeMap>>,
     signature: MIDIFileUniqueSignature,
+    format: MIDIFormat,
+    summary: MIDISummary,
 }
 
-impl InRamMIDIFile {}
+impl InRamMIDIFile {
+    pub fn lyrics(&self) -> &[MIDILyric] {
+        &self.lyrics
+    }
+}
 
 impl MIDIFileBase for InRamMIDIFile {
     fn midi_length(&self) -> Option<f64> {
@@ -51,16 +76,58 @@ impl MIDIFileBase for InRamMIDIFile {
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn format(&self) -> MIDIFormat {
+        self.format
+    }
+
+    fn position_musical(&self) -> MusicalPosition {
+        let time = self.timer.get_time().as_secs_f64();
+        self.musical_time.read().unwrap().position_at(time)
+    }
+
+    fn beat_at(&self, time: f64) -> Beat {
+        self.musical_time.read().unwrap().beat_at(time)
+    }
+
+    fn track_note_counts(&self) -> Option<&[u64]> {
+        Some(&self.track_note_counts)
+    }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        Some(&self.note_density)
+    }
+
+    fn active_key_range(&self) -> Option<RangeInclusive<usize>> {
+        self.view_data.active_key_range()
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        self.view_data.track_colors()
+    }
+
+    fn summary(&self) -> Option<&MIDISummary> {
+        Some(&self.summary)
+    }
+
+    fn recolor(
+        &mut self,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) {
+        self.view_data.set_palette(palette, color_by, assignments);
+    }
 }
 
 impl MIDIFile for InRamMIDIFile {
     type ColumnsViews<'a> = InRamCurrentNoteViews<'a> where Self: 'a;
 
-    fn get_current_column_views(&mut self, range: f64) -> Self::ColumnsViews<'_> {
+    fn get_current_column_views(&mut self, range: f64, sustain: bool) -> Self::ColumnsViews<'_> {
         let time = self.timer.get_time().as_secs_f64();
         let new_range = MIDIViewRange::new(time, time + range);
         self.view_data.shift_view_range(new_range);
 
-        InRamCurrentNoteViews::new(&self.view_data)
+        InRamCurrentNoteViews::new(&self.view_data, sustain)
     }
 }
@@ -5,8 +5,7 @@ use std::{
 };
 
 use midi_toolkit::{
-    events::{Event, MIDIEventEnum},
-    io::MIDIFile as TKMIDIFile,
+    events::{Event, MIDIEventEnum, TextEventKind},
     pipe,
     sequence::{
         event::{cancel_tempo_events, scale_event_time, Delta, EventBatch, Track},
@@ -19,13 +18,24 @@ use crate::{
     audio_playback::SimpleTemporaryPlayer,
     midi::{
         audio::ram::InRamAudioPlayer,
-        open_file_and_signature,
+        open_midi_and_signature,
         ram::{column::InRamNoteColumn, view::InRamNoteViewData},
-        shared::{audio::CompressedAudio, timer::TimeKeeper, track_channel::TrackAndChannel},
+        shared::{
+            audio::CompressedAudio,
+            density::NoteDensityWriter,
+            division::{MIDIDivision, TempoCanceller},
+            musical_time::MusicalTimeMapWriter,
+            sustain::SustainMapWriter,
+            timer::TimeKeeper,
+            track_channel::TrackAndChannel,
+        },
+        summary::MIDISummaryWriter,
+        MIDIFileLoadError,
     },
+    settings::{ColorBy, ColorPalette},
 };
 
-use super::{block::InRamNoteBlock, InRamMIDIFile};
+use super::{block::InRamNoteBlock, InRamMIDIFile, MIDILyric};
 
 struct UnendedNote {
     column_index: usize,
@@ -34,7 +44,7 @@ struct UnendedNote {
 
 struct Key {
     column: Vec<InRamNoteBlock>,
-    block_builder: Vec<TrackAndChannel>,
+    block_builder: Vec<(TrackAndChannel, u8)>,
     unended_notes: FxHashMap<TrackAndChannel, VecDeque<UnendedNote>>,
 }
 
@@ -47,10 +57,10 @@ impl Key {
         }
     }
 
-    fn add_note(&mut self, track_chan: TrackAndChannel) {
+    fn add_note(&mut self, track_chan: TrackAndChannel, velocity: u8) {
         let block_index = self.block_builder.len();
         let column_index = self.column.len();
-        self.block_builder.push(track_chan);
+        self.block_builder.push((track_chan, velocity));
         let unended_queue = self.unended_notes.entry(track_chan).or_default();
         unended_queue.push_back(UnendedNote {
             column_index,
@@ -100,16 +110,35 @@ impl InRamMIDIFile {
         path: &str,
         player: Arc<RwLock<SimpleTemporaryPlayer>>,
         random_colors: bool,
-    ) -> Self {
-        let (file, signature) = open_file_and_signature(path);
-        let midi = TKMIDIFile::open_from_stream(file, None).unwrap();
+        palette: ColorPalette,
+        color_by: ColorBy,
+        tempo_override: Option<f64>,
+        channel_offset: u8,
+    ) -> Result<Self, MIDIFileLoadError> {
+        let (midi, signature, format) = open_midi_and_signature(path)?;
 
-        let ppq = midi.ppq();
-        let merged = pipe!(
+        // Karaoke files store their lyrics in plain text events
+        let is_karaoke = path.to_lowercase().ends_with(".kar");
+
+        let division = MIDIDivision::from_raw(midi.ppq());
+        let track_count = midi.track_count();
+        let mut time_map = MusicalTimeMapWriter::new(division, tempo_override);
+        let mut tempo_canceller = TempoCanceller::new(division, tempo_override);
+        let musical_time = time_map.map();
+        let batches = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(move |batch| {
+            if let Ok(batch) = batch {
+                time_map.push_batch(batch);
+            }
+        })
+        .map(move |batch| tempo_canceller.cancel(batch));
+        let merged = pipe!(
+            batches
             |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
+            |>scale_event_time(1.0 / division.ticks_per_quarter())
             |>unwrap_items()
         );
 
@@ -117,12 +146,18 @@ impl InRamMIDIFile {
         let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
         let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
 
-        let key_join_handle = thread::spawn(|| {
+        let key_join_handle = thread::spawn(move || {
             let mut keys: Vec<Key> = (0..256).map(|_| Key::new()).collect();
 
             let mut time = 0.0;
 
             let mut notes = 0;
+            let mut track_notes = vec![0; track_count];
+
+            let mut lyrics = Vec::new();
+            let mut sustain = SustainMapWriter::new();
+            let mut density = NoteDensityWriter::new();
+            let mut summary = MIDISummaryWriter::new(format, track_count);
 
             fn flush_keys(time: f64, keys: &mut [Key]) {
                 for key in keys.iter_mut() {
@@ -141,12 +176,32 @@ impl InRamMIDIFile {
                     match event.as_event() {
                         Event::NoteOn(e) => {
                             let track_chan = TrackAndChannel::new(track, e.channel);
-                            keys[e.key as usize].add_note(track_chan);
+                            keys[e.key as usize].add_note(track_chan, e.velocity);
                             notes += 1;
+                            track_notes[track as usize] += 1;
+                            density.push(time);
+                            summary.push_note(time, track as usize, e.channel, e.key);
                         }
                         Event::NoteOff(e) => {
                             let track_chan = TrackAndChannel::new(track, e.channel);
                             keys[e.key as usize].end_note(track_chan, time);
+                            summary.push_note_off(time);
+                        }
+                        Event::ControlChange(e) if e.controller == 64 => {
+                            sustain.push(e.channel, time, e.value);
+                        }
+                        Event::Text(e) => {
+                            let is_lyric = match e.kind {
+                                TextEventKind::Lyric => true,
+                                TextEventKind::TextEvent => is_karaoke,
+                                _ => false,
+                            };
+                            if is_lyric {
+                                lyrics.push(MIDILyric {
+                                    time,
+                                    text: String::from_utf8_lossy(&e.bytes).into_owned(),
+                                });
+                            }
                         }
                         _ => {}
                     }
@@ -159,7 +214,15 @@ impl InRamMIDIFile {
                 key.end_all(time);
             }
 
-            (keys, notes)
+            (
+                keys,
+                notes,
+                track_notes,
+                lyrics,
+                sustain.finish(time),
+                density.finish(),
+                summary,
+            )
         });
 
         let audio_join_handle = thread::spawn(|| {
@@ -180,24 +243,41 @@ impl InRamMIDIFile {
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
+        let (keys, note_count, track_note_counts, lyrics, sustain, note_density, summary) =
+            key_join_handle.join().unwrap();
+        let summary = summary.finish(length, division, &musical_time.read().unwrap());
         let audio = audio_join_handle.join().unwrap();
 
         let mut timer = TimeKeeper::new();
 
-        InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        InRamAudioPlayer::new(audio, timer.get_listener(), player)
+            .with_channel_offset(channel_offset)
+            .spawn_playback();
 
         let columns = keys
             .into_iter()
             .map(|key| InRamNoteColumn::new(key.column))
             .collect();
 
-        InRamMIDIFile {
-            view_data: InRamNoteViewData::new(columns, midi.track_count(), random_colors),
+        Ok(InRamMIDIFile {
+            view_data: InRamNoteViewData::new(
+                columns,
+                track_count,
+                random_colors,
+                palette,
+                color_by,
+                sustain,
+            ),
             timer,
             length,
             note_count,
+            track_note_counts,
+            note_density,
+            lyrics,
+            musical_time,
             signature,
-        }
+            format,
+            summary,
+        })
     }
 }
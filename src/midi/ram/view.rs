@@ -1,39 +1,65 @@
+use std::ops::RangeInclusive;
+
 use gen_iter::GenIter;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-use crate::midi::{DisplacedMIDINote, MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange};
+use crate::{
+    midi::{
+        shared::sustain::SustainMap, DisplacedMIDINote, MIDIColor, MIDINoteColumnView,
+        MIDINoteViews, MIDIViewRange,
+    },
+    settings::{colors::ColorAssignments, ColorBy, ColorPalette},
+};
 
 use super::column::InRamNoteColumn;
 
 pub struct InRamNoteViewData {
     columns: Vec<InRamNoteColumn>,
     default_track_colors: Vec<MIDIColor>,
+    /// The colors picked when the MIDI was loaded with random colors
+    random_track_colors: Option<Vec<MIDIColor>>,
     view_range: MIDIViewRange,
+    sustain: SustainMap,
 }
 
 pub struct InRamCurrentNoteViews<'a> {
     data: &'a InRamNoteViewData,
+    /// Whether the sustain tails are looked up
+    sustain: bool,
 }
 
 impl<'a> InRamCurrentNoteViews<'a> {
-    pub fn new(data: &'a InRamNoteViewData) -> Self {
-        InRamCurrentNoteViews { data }
+    pub fn new(data: &'a InRamNoteViewData, sustain: bool) -> Self {
+        InRamCurrentNoteViews { data, sustain }
     }
 }
 
 impl InRamNoteViewData {
-    pub fn new(columns: Vec<InRamNoteColumn>, track_count: usize, random_colors: bool) -> Self {
+    pub fn new(
+        columns: Vec<InRamNoteColumn>,
+        track_count: usize,
+        random_colors: bool,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        sustain: SustainMap,
+    ) -> Self {
+        let random_track_colors =
+            random_colors.then(|| MIDIColor::new_random_vec_for_tracks(track_count));
         InRamNoteViewData {
             columns,
+            sustain,
             view_range: MIDIViewRange {
                 start: 0.0,
                 end: 0.0,
             },
-            default_track_colors: if random_colors {
-                MIDIColor::new_random_vec_for_tracks(track_count)
-            } else {
-                MIDIColor::new_vec_for_tracks(track_count)
-            },
+            default_track_colors: MIDIColor::new_vec_for_settings(
+                track_count,
+                random_track_colors.as_deref(),
+                palette,
+                color_by,
+                &ColorAssignments::default(),
+            ),
+            random_track_colors,
         }
     }
 
@@ -43,9 +69,38 @@ impl InRamNoteViewData {
             .map(|column| column.data.notes_to_keyboard)
             .sum()
     }
+
+    pub fn active_key_range(&self) -> Option<RangeInclusive<usize>> {
+        let is_active = |column: &InRamNoteColumn| {
+            column.data.notes_to_render_end > column.data.notes_to_render_start
+        };
+        let first = self.columns.iter().position(is_active)?;
+        let last = self.columns.iter().rposition(is_active)?;
+        Some(first..=last)
+    }
 }
 
 impl InRamNoteViewData {
+    pub fn set_palette(
+        &mut self,
+        palette: ColorPalette,
+        color_by: ColorBy,
+        assignments: &ColorAssignments,
+    ) {
+        let track_count = self.default_track_colors.len() / 16;
+        self.default_track_colors = MIDIColor::new_vec_for_settings(
+            track_count,
+            self.random_track_colors.as_deref(),
+            palette,
+            color_by,
+            assignments,
+        );
+    }
+
+    pub fn track_colors(&self) -> &[MIDIColor] {
+        &self.default_track_colors
+    }
+
     pub fn shift_view_range(&mut self, new_view_range: MIDIViewRange) {
         let old_view_range = self.view_range;
         self.view_range = new_view_range;
@@ -150,6 +205,7 @@ impl<'a> MIDINoteViews for InRamCurrentNoteViews<'a> {
             view: self.data,
             column: &self.data.columns[key],
             view_range: self.data.view_range,
+            sustain: self.sustain,
         }
     }
 
@@ -167,6 +223,7 @@ pub struct InRamNoteColumnView<'a> {
     view: &'a InRamNoteViewData,
     column: &'a InRamNoteColumn,
     view_range: MIDIViewRange,
+    sustain: bool,
 }
 
 impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
@@ -174,6 +231,7 @@ impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
 
     fn iterate_displaced_notes(&self) -> Self::Iter<'_> {
         let colors = &self.view.default_track_colors;
+        let sustain = self.sustain.then_some(&self.view.sustain);
 
         let iter = GenIter(move || {
             for block_index in self.column.data.block_range.clone().rev() {
@@ -181,10 +239,17 @@ impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
                 let start = (block.start - self.view_range.start) as f32;
 
                 for note in block.notes.iter().rev() {
+                    let channel = note.track_chan.channel();
+                    let end = block.start + note.len as f64;
                     yield DisplacedMIDINote {
                         start,
                         len: note.len,
                         color: colors[note.track_chan.as_usize()],
+                        channel,
+                        velocity: note.velocity,
+                        sustain: sustain
+                            .and_then(|sustain| sustain.release_after(channel, end))
+                            .map_or(0.0, |released| (released - end) as f32),
                     };
                 }
             }
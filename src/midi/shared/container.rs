@@ -0,0 +1,179 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use crate::midi::MIDIFileLoadError;
+
+/// A readable MIDI byte stream, either the file itself or a standard MIDI
+/// file that was extracted from a container (e.g. RMID).
+pub enum MIDIStream {
+    File(File),
+    Embedded(Cursor<Vec<u8>>),
+}
+
+impl Read for MIDIStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MIDIStream::File(file) => file.read(buf),
+            MIDIStream::Embedded(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for MIDIStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            MIDIStream::File(file) => file.seek(pos),
+            MIDIStream::Embedded(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Checks if the file is wrapped in a RIFF (RMID) container, and if so
+/// extracts the embedded standard MIDI file from its `data` chunk.
+pub fn unwrap_midi_container(mut file: MIDIStream) -> Result<MIDIStream, MIDIFileLoadError> {
+    let mut header = [0u8; 12];
+    let is_riff = match file.read_exact(&mut header) {
+        Ok(()) => &header[0..4] == b"RIFF" && &header[8..12] == b"RMID",
+        Err(_) => false,
+    };
+    file.seek(SeekFrom::Start(0))?;
+
+    if !is_riff {
+        return Ok(file);
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let start = pos + 8;
+        let end = (start + len).min(bytes.len());
+
+        if id == b"data" {
+            return Ok(MIDIStream::Embedded(Cursor::new(
+                bytes[start..end].to_vec(),
+            )));
+        }
+
+        // Chunks are padded to an even length
+        pos = start + len + (len & 1);
+    }
+
+    Err(MIDIFileLoadError::MissingMIDIChunk)
+}
+
+/// The path that reads the MIDI from stdin
+pub const STDIN_PATH: &str = "-";
+
+/// How much is downloaded between progress updates
+const DOWNLOAD_CHUNK: usize = 64 * 1024;
+
+/// The biggest MIDI that gets downloaded, as it's kept in memory until it's saved
+const MAX_DOWNLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// How long connecting, or waiting for the next bytes, can take before giving up
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stdin can only be read once, so a MIDI from it can't be loaded a second time
+pub fn is_stdin_path(path: &str) -> bool {
+    path == STDIN_PATH
+}
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Downloads a MIDI, calling `progress` with the downloaded and total byte counts as it
+/// goes. The total is only known if the server sends it. Downloads bigger than
+/// [`MAX_DOWNLOAD_SIZE`] or that stall for [`DOWNLOAD_TIMEOUT`] fail.
+pub fn download_midi(
+    url: &str,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<Vec<u8>, MIDIFileLoadError> {
+    let too_big = || {
+        MIDIFileLoadError::Download(format!(
+            "The MIDI is bigger than {} MiB",
+            MAX_DOWNLOAD_SIZE / 1024 / 1024
+        ))
+    };
+
+    let response = ureq::AgentBuilder::new()
+        .timeout_connect(DOWNLOAD_TIMEOUT)
+        .timeout_read(DOWNLOAD_TIMEOUT)
+        .build()
+        .get(url)
+        .call()
+        .map_err(|e| MIDIFileLoadError::Download(e.to_string()))?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+    if total.map_or(false, |total| total > MAX_DOWNLOAD_SIZE) {
+        return Err(too_big());
+    }
+
+    let mut reader = response.into_reader();
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut chunk = vec![0; DOWNLOAD_CHUNK];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        bytes.extend_from_slice(&chunk[..read]);
+        if bytes.len() as u64 > MAX_DOWNLOAD_SIZE {
+            return Err(too_big());
+        }
+        progress(bytes.len() as u64, total);
+    }
+
+    check_is_midi(&bytes)?;
+    Ok(bytes)
+}
+
+/// Reads a whole MIDI into memory, either from stdin (`-`) or from an HTTP(S) URL.
+/// Returns `None` if the path is a regular file path.
+pub fn read_remote_midi(path: &str) -> Option<Result<Vec<u8>, MIDIFileLoadError>> {
+    if is_stdin_path(path) {
+        let mut bytes = Vec::new();
+        let result = io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_err(MIDIFileLoadError::from)
+            .and_then(|_| check_is_midi(&bytes));
+        Some(result.map(|_| bytes))
+    } else if is_url(path) {
+        log::info!("Downloading {path}");
+        let mut logged_percent = 0;
+        Some(download_midi(path, |downloaded, total| match total {
+            Some(total) if total > 0 => {
+                let percent = downloaded * 100 / total;
+                if percent >= logged_percent + 10 {
+                    logged_percent = percent - percent % 10;
+                    log::info!("Downloaded {percent}% of {} KiB", total / 1024);
+                }
+            }
+            _ => log::debug!("Downloaded {} KiB", downloaded / 1024),
+        }))
+    } else {
+        None
+    }
+}
+
+/// Makes sure we actually got a MIDI before trying to parse it
+fn check_is_midi(bytes: &[u8]) -> Result<(), MIDIFileLoadError> {
+    let is_midi = bytes.starts_with(b"MThd")
+        || (bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"RMID");
+    if is_midi {
+        Ok(())
+    } else {
+        Err(MIDIFileLoadError::NotAMIDI)
+    }
+}
@@ -0,0 +1,65 @@
+/// The most buckets a histogram can have, no matter how long the MIDI is
+const MAX_BUCKETS: usize = 1024;
+
+/// The length of the buckets before any of them get merged
+const INITIAL_BUCKET_LENGTH: f64 = 0.05;
+
+/// How many notes start in each slice of the MIDI, used for the heatmap
+/// above the seek bar
+#[derive(Debug, Clone)]
+pub struct NoteDensity {
+    bucket_length: f64,
+    buckets: Vec<u64>,
+}
+
+impl NoteDensity {
+    /// The length of each bucket in seconds
+    pub fn bucket_length(&self) -> f64 {
+        self.bucket_length
+    }
+
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    pub fn max(&self) -> u64 {
+        self.buckets.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Counts the notes while a MIDI is being parsed. Whenever the histogram
+/// gets too long, neighbouring buckets are merged and the length doubles.
+pub struct NoteDensityWriter {
+    density: NoteDensity,
+}
+
+impl NoteDensityWriter {
+    pub fn new() -> Self {
+        Self {
+            density: NoteDensity {
+                bucket_length: INITIAL_BUCKET_LENGTH,
+                buckets: Vec::new(),
+            },
+        }
+    }
+
+    pub fn push(&mut self, time: f64) {
+        let density = &mut self.density;
+
+        let mut index = (time.max(0.0) / density.bucket_length) as usize;
+        while index >= MAX_BUCKETS {
+            density.buckets = density.buckets.chunks(2).map(|c| c.iter().sum()).collect();
+            density.bucket_length *= 2.0;
+            index /= 2;
+        }
+
+        if index >= density.buckets.len() {
+            density.buckets.resize(index + 1, 0);
+        }
+        density.buckets[index] += 1;
+    }
+
+    pub fn finish(self) -> NoteDensity {
+        self.density
+    }
+}
@@ -0,0 +1,163 @@
+use midi_toolkit::{
+    events::{Event, MIDIEventEnum},
+    sequence::event::{Delta, EventBatch, Track},
+};
+
+/// The tempo MIDIs start with, in microseconds per quarter note (120 BPM)
+pub const DEFAULT_TEMPO: u32 = 500000;
+
+/// How the ticks in a MIDI file map to time, read from the division in its header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MIDIDivision {
+    /// The ticks are a fraction of a quarter note, so their length depends on the tempo
+    TicksPerQuarter(u16),
+    /// The ticks are a fraction of a SMPTE frame, so they have a fixed length
+    /// and the tempo events only matter for the musical position
+    Smpte {
+        frames_per_second: f64,
+        ticks_per_frame: u16,
+    },
+}
+
+impl MIDIDivision {
+    pub fn from_raw(division: u16) -> Self {
+        if division & 0x8000 == 0 {
+            return Self::TicksPerQuarter(division);
+        }
+
+        // The upper byte is the negative frame rate, where 29 means 29.97 fps (drop frame)
+        let frames = ((division >> 8) as u8 as i8).unsigned_abs();
+        let frames_per_second = match frames {
+            29 => 30000.0 / 1001.0,
+            frames => frames as f64,
+        };
+
+        Self::Smpte {
+            frames_per_second: frames_per_second.max(1.0),
+            ticks_per_frame: (division & 0xFF).max(1),
+        }
+    }
+
+    pub fn is_smpte(&self) -> bool {
+        matches!(self, Self::Smpte { .. })
+    }
+
+    /// The ticks in a quarter note at the default tempo. SMPTE MIDIs get the amount of
+    /// ticks that pass in the same time, so once their tempo events are cancelled with
+    /// [`TempoCanceller`] they can go through the same tempo based conversion.
+    pub fn ticks_per_quarter(&self) -> f64 {
+        match *self {
+            Self::TicksPerQuarter(ppq) => ppq.max(1) as f64,
+            Self::Smpte {
+                frames_per_second,
+                ticks_per_frame,
+            } => frames_per_second * ticks_per_frame as f64 * DEFAULT_TEMPO as f64 / 1_000_000.0,
+        }
+    }
+
+    /// The tempo that the whole MIDI plays at when its tempo events are ignored, in
+    /// microseconds per quarter note. SMPTE MIDIs always play at the default tempo,
+    /// unless the tempo is overridden with a BPM.
+    pub fn fixed_tempo(&self, tempo_override: Option<f64>) -> Option<f64> {
+        match tempo_override {
+            Some(bpm) => Some(60_000_000.0 / bpm.max(1.0)),
+            None if self.is_smpte() => Some(DEFAULT_TEMPO as f64),
+            None => None,
+        }
+    }
+}
+
+/// Scales the event deltas to undo the tempo events, which would otherwise be applied
+/// by `cancel_tempo_events`, so the MIDI plays at its [`MIDIDivision::fixed_tempo`].
+/// This must see the batches before that step does. Does nothing for ticks per quarter
+/// MIDIs without a tempo override.
+pub struct TempoCanceller {
+    fixed_tempo: Option<f64>,
+    tempo: u32,
+}
+
+impl TempoCanceller {
+    pub fn new(division: MIDIDivision, tempo_override: Option<f64>) -> Self {
+        Self {
+            fixed_tempo: division.fixed_tempo(tempo_override),
+            tempo: DEFAULT_TEMPO,
+        }
+    }
+
+    pub fn cancel<E>(
+        &mut self,
+        batch: Result<Delta<f64, Track<EventBatch<Event>>>, E>,
+    ) -> Result<Delta<f64, Track<EventBatch<Event>>>, E> {
+        let Some(fixed_tempo) = self.fixed_tempo else {
+            return batch;
+        };
+
+        batch.map(|mut batch| {
+            // The delta is converted with the tempo from before the batch
+            batch.delta *= fixed_tempo / self.tempo.max(1) as f64;
+            for event in batch.iter_events() {
+                if let Event::Tempo(e) = event.as_event() {
+                    self.tempo = e.tempo;
+                }
+            }
+            batch
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_ticks_per_quarter() {
+        let division = MIDIDivision::from_raw(480);
+        assert_eq!(division, MIDIDivision::TicksPerQuarter(480));
+        assert!(!division.is_smpte());
+        assert_eq!(division.ticks_per_quarter(), 480.0);
+        assert_eq!(division.fixed_tempo(None), None);
+    }
+
+    #[test]
+    fn reads_smpte() {
+        // -25 fps with 40 ticks per frame
+        let division = MIDIDivision::from_raw(0xE728);
+        assert_eq!(
+            division,
+            MIDIDivision::Smpte {
+                frames_per_second: 25.0,
+                ticks_per_frame: 40,
+            }
+        );
+        assert!(division.is_smpte());
+    }
+
+    #[test]
+    fn reads_smpte_drop_frame() {
+        // -29 fps with 80 ticks per frame
+        let MIDIDivision::Smpte {
+            frames_per_second,
+            ticks_per_frame,
+        } = MIDIDivision::from_raw(0xE350)
+        else {
+            panic!("expected a SMPTE division");
+        };
+        assert!((frames_per_second - 29.97).abs() < 0.001);
+        assert_eq!(ticks_per_frame, 80);
+    }
+
+    #[test]
+    fn smpte_plays_at_the_default_tempo() {
+        // 25 fps with 40 ticks per frame is 1000 ticks per second,
+        // which is 500 ticks in a quarter note at 120 BPM
+        let division = MIDIDivision::from_raw(0xE728);
+        assert_eq!(division.ticks_per_quarter(), 500.0);
+        assert_eq!(division.fixed_tempo(None), Some(DEFAULT_TEMPO as f64));
+    }
+
+    #[test]
+    fn tempo_override_fixes_the_tempo() {
+        let division = MIDIDivision::from_raw(480);
+        assert_eq!(division.fixed_tempo(Some(60.0)), Some(1_000_000.0));
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    fmt,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::midi::MIDIFileLoadError;
+
+/// The format number in the header of a standard MIDI file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MIDIFormat {
+    /// A single track with the events of every channel
+    SingleTrack,
+    /// Several tracks that play at the same time
+    #[default]
+    MultiTrack,
+    /// Several independent sequences, meant to be played one at a time
+    MultiSequence,
+    Unknown(u16),
+}
+
+impl MIDIFormat {
+    pub fn from_raw(format: u16) -> Self {
+        match format {
+            0 => MIDIFormat::SingleTrack,
+            1 => MIDIFormat::MultiTrack,
+            2 => MIDIFormat::MultiSequence,
+            other => MIDIFormat::Unknown(other),
+        }
+    }
+
+    pub fn number(self) -> u16 {
+        match self {
+            MIDIFormat::SingleTrack => 0,
+            MIDIFormat::MultiTrack => 1,
+            MIDIFormat::MultiSequence => 2,
+            MIDIFormat::Unknown(format) => format,
+        }
+    }
+
+    /// Why the MIDI might not play the way it was meant to, if it is in a format that
+    /// isn't fully supported
+    pub fn warning(self) -> Option<String> {
+        match self {
+            MIDIFormat::SingleTrack | MIDIFormat::MultiTrack => None,
+            MIDIFormat::MultiSequence => Some(
+                "This is a format 2 MIDI, its sequences are played at the same time instead of one after another"
+                    .to_string(),
+            ),
+            MIDIFormat::Unknown(format) => Some(format!(
+                "Unknown MIDI format {format}, it is played as if it was format 1"
+            )),
+        }
+    }
+
+    /// Reads the format from the header, leaving the stream at its start
+    pub fn read<T: Read + Seek>(stream: &mut T) -> Result<Self, MIDIFileLoadError> {
+        let mut header = [0u8; 10];
+        let read = stream.read_exact(&mut header);
+        stream.seek(SeekFrom::Start(0))?;
+
+        // A broken header is reported by the parser instead
+        if read.is_err() || &header[0..4] != b"MThd" {
+            return Ok(MIDIFormat::default());
+        }
+        Ok(Self::from_raw(u16::from_be_bytes([header[8], header[9]])))
+    }
+}
+
+impl fmt::Display for MIDIFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MIDIFormat::SingleTrack => write!(f, "0 (single track)"),
+            MIDIFormat::MultiTrack => write!(f, "1 (multiple tracks)"),
+            MIDIFormat::MultiSequence => write!(f, "2 (multiple sequences)"),
+            MIDIFormat::Unknown(format) => write!(f, "{format} (unknown)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A MIDI header with the format, one track and 480 ticks per quarter note
+    fn header(format: u16) -> Cursor<Vec<u8>> {
+        let mut data = b"MThd".to_vec();
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&format.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&480u16.to_be_bytes());
+        Cursor::new(data)
+    }
+
+    #[test]
+    fn reads_the_format_and_rewinds() {
+        for (number, format) in [
+            (0, MIDIFormat::SingleTrack),
+            (1, MIDIFormat::MultiTrack),
+            (2, MIDIFormat::MultiSequence),
+        ] {
+            let mut stream = header(number);
+            assert_eq!(MIDIFormat::read(&mut stream).unwrap(), format);
+            assert_eq!(stream.position(), 0);
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_formats() {
+        let mut stream = header(7);
+        assert_eq!(
+            MIDIFormat::read(&mut stream).unwrap(),
+            MIDIFormat::Unknown(7)
+        );
+        assert_eq!(stream.position(), 0);
+    }
+
+    #[test]
+    fn falls_back_without_a_header() {
+        let mut stream = Cursor::new(b"RIFF".to_vec());
+        assert_eq!(
+            MIDIFormat::read(&mut stream).unwrap(),
+            MIDIFormat::default()
+        );
+        assert_eq!(stream.position(), 0);
+    }
+}
@@ -1,3 +1,9 @@
 pub mod audio;
+pub mod container;
+pub mod density;
+pub mod division;
+pub mod format;
+pub mod musical_time;
+pub mod sustain;
 pub mod timer;
 pub mod track_channel;
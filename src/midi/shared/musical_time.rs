@@ -0,0 +1,231 @@
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use midi_toolkit::{
+    events::{Event, MIDIEventEnum},
+    sequence::event::{Delta, EventBatch, Track},
+};
+
+use super::division::{MIDIDivision, DEFAULT_TEMPO};
+
+/// A position in the song, in the form of bar:beat:tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MusicalPosition {
+    pub bar: u64,
+    pub beat: u64,
+    pub tick: u64,
+}
+
+impl fmt::Display for MusicalPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{:03}", self.bar, self.beat, self.tick)
+    }
+}
+
+/// The beat that is playing at some point in the song
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Beat {
+    /// Whether this is the first beat of its bar
+    pub downbeat: bool,
+    /// The seconds that have passed since the beat started
+    pub elapsed: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TempoSegment {
+    start_ticks: u64,
+    start_seconds: f64,
+    seconds_per_tick: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TimeSignatureSegment {
+    start_ticks: u64,
+    start_bar: u64,
+    beats_per_bar: u64,
+    ticks_per_beat: u64,
+}
+
+impl TimeSignatureSegment {
+    fn ticks_per_bar(&self) -> u64 {
+        self.beats_per_bar * self.ticks_per_beat
+    }
+}
+
+/// The tempo and time signature changes of a MIDI, used to convert
+/// the playback time into a musical position.
+#[derive(Debug, Clone)]
+pub struct MusicalTimeMap {
+    tempos: Vec<TempoSegment>,
+    time_signatures: Vec<TimeSignatureSegment>,
+    /// The shortest and longest quarter note in the tempo events, in microseconds
+    tempo_range: Option<(u32, u32)>,
+}
+
+impl MusicalTimeMap {
+    fn new(ticks_per_quarter: f64, tempo: f64) -> Self {
+        Self {
+            tempos: vec![TempoSegment {
+                start_ticks: 0,
+                start_seconds: 0.0,
+                seconds_per_tick: seconds_per_tick(tempo, ticks_per_quarter),
+            }],
+            time_signatures: vec![TimeSignatureSegment {
+                start_ticks: 0,
+                start_bar: 0,
+                beats_per_bar: 4,
+                ticks_per_beat: (ticks_per_quarter.round() as u64).max(1),
+            }],
+            tempo_range: None,
+        }
+    }
+
+    /// The lowest and highest BPM in the tempo events of the MIDI, even if the
+    /// tempo was overridden. `None` if there are no tempo events.
+    pub fn bpm_range(&self) -> Option<(f64, f64)> {
+        // A longer quarter note means a lower BPM, so the range flips
+        self.tempo_range
+            .map(|(min, max)| (60_000_000.0 / max as f64, 60_000_000.0 / min as f64))
+    }
+
+    fn ticks_to_seconds(&self, ticks: u64) -> f64 {
+        let tempo = self
+            .tempos
+            .iter()
+            .rev()
+            .find(|t| t.start_ticks <= ticks)
+            .unwrap_or(&self.tempos[0]);
+        tempo.start_seconds + (ticks - tempo.start_ticks) as f64 * tempo.seconds_per_tick
+    }
+
+    fn seconds_to_ticks(&self, seconds: f64) -> u64 {
+        let tempo = self
+            .tempos
+            .iter()
+            .rev()
+            .find(|t| t.start_seconds <= seconds)
+            .unwrap_or(&self.tempos[0]);
+        let ticks = (seconds - tempo.start_seconds) / tempo.seconds_per_tick;
+        tempo.start_ticks + ticks.max(0.0) as u64
+    }
+
+    /// The bar index (0 based) and the ticks into that bar at the given tick
+    fn bar_at(&self, ticks: u64) -> (TimeSignatureSegment, u64, u64) {
+        let sig = *self
+            .time_signatures
+            .iter()
+            .rev()
+            .find(|s| s.start_ticks <= ticks)
+            .unwrap_or(&self.time_signatures[0]);
+        let ticks_into = ticks - sig.start_ticks;
+        let bar = sig.start_bar + ticks_into / sig.ticks_per_bar();
+        (sig, bar, ticks_into % sig.ticks_per_bar())
+    }
+
+    pub fn position_at(&self, seconds: f64) -> MusicalPosition {
+        let ticks = self.seconds_to_ticks(seconds);
+        let (sig, bar, ticks_into_bar) = self.bar_at(ticks);
+
+        MusicalPosition {
+            bar: bar + 1,
+            beat: ticks_into_bar / sig.ticks_per_beat + 1,
+            tick: ticks_into_bar % sig.ticks_per_beat,
+        }
+    }
+
+    pub fn beat_at(&self, seconds: f64) -> Beat {
+        let ticks = self.seconds_to_ticks(seconds);
+        let (sig, _, ticks_into_bar) = self.bar_at(ticks);
+        let beat_start = self.ticks_to_seconds(ticks - ticks_into_bar % sig.ticks_per_beat);
+
+        Beat {
+            downbeat: ticks_into_bar < sig.ticks_per_beat,
+            elapsed: (seconds - beat_start).max(0.0),
+        }
+    }
+}
+
+fn seconds_per_tick(tempo: f64, ticks_per_quarter: f64) -> f64 {
+    tempo / 1_000_000.0 / ticks_per_quarter
+}
+
+/// Builds a [`MusicalTimeMap`] from the raw (tick based) event batches,
+/// before the tempo events get cancelled out by the parsers.
+pub struct MusicalTimeMapWriter {
+    map: Arc<RwLock<MusicalTimeMap>>,
+    ticks_per_quarter: f64,
+    /// Whether the tempo events are ignored
+    fixed_tempo: bool,
+    /// SMPTE timing has no tempo, so its tempo events aren't even kept in the range
+    smpte: bool,
+    ticks: u64,
+}
+
+impl MusicalTimeMapWriter {
+    pub fn new(division: MIDIDivision, tempo_override: Option<f64>) -> Self {
+        let ticks_per_quarter = division.ticks_per_quarter();
+        let fixed_tempo = division.fixed_tempo(tempo_override);
+        let tempo = fixed_tempo.unwrap_or(DEFAULT_TEMPO as f64);
+        Self {
+            map: Arc::new(RwLock::new(MusicalTimeMap::new(ticks_per_quarter, tempo))),
+            ticks_per_quarter,
+            fixed_tempo: fixed_tempo.is_some(),
+            smpte: division.is_smpte(),
+            ticks: 0,
+        }
+    }
+
+    pub fn map(&self) -> Arc<RwLock<MusicalTimeMap>> {
+        self.map.clone()
+    }
+
+    pub fn push_batch(&mut self, batch: &Delta<f64, Track<EventBatch<Event>>>) {
+        self.ticks += batch.delta as u64;
+
+        for event in batch.iter_events() {
+            if let Event::Tempo(e) = event.as_event() {
+                if !self.smpte {
+                    let mut map = self.map.write().unwrap();
+                    let (min, max) = map.tempo_range.get_or_insert((e.tempo, e.tempo));
+                    *min = (*min).min(e.tempo);
+                    *max = (*max).max(e.tempo);
+                }
+            }
+
+            match event.as_event() {
+                // SMPTE ticks and overridden tempos have a fixed length, so the tempo
+                // events don't change the timing
+                Event::Tempo(e) if !self.fixed_tempo => {
+                    let mut map = self.map.write().unwrap();
+                    let start_seconds = map.ticks_to_seconds(self.ticks);
+                    map.tempos.retain(|t| t.start_ticks < self.ticks);
+                    map.tempos.push(TempoSegment {
+                        start_ticks: self.ticks,
+                        start_seconds,
+                        seconds_per_tick: seconds_per_tick(e.tempo as f64, self.ticks_per_quarter),
+                    });
+                }
+                Event::TimeSignature(e) => {
+                    let mut map = self.map.write().unwrap();
+
+                    // A time signature change always starts a new bar
+                    let (_, bar, ticks_into_bar) = map.bar_at(self.ticks);
+                    let start_bar = if ticks_into_bar > 0 { bar + 1 } else { bar };
+
+                    let ticks_per_beat = (self.ticks_per_quarter.round() as u64 * 4)
+                        >> (e.denominator as u32).min(6);
+                    map.time_signatures.retain(|s| s.start_ticks < self.ticks);
+                    map.time_signatures.push(TimeSignatureSegment {
+                        start_ticks: self.ticks,
+                        start_bar,
+                        beats_per_bar: (e.numerator as u64).max(1),
+                        ticks_per_beat: ticks_per_beat.max(1),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+/// The times that the sustain pedal (CC64) is held down on each channel
+#[derive(Debug, Clone, Default)]
+pub struct SustainMap {
+    /// Sorted, non overlapping (pressed, released) times for each channel
+    channels: [Vec<(f64, f64)>; 16],
+}
+
+impl SustainMap {
+    /// The time that a note ending at `time` is released by the pedal,
+    /// if the pedal is held down at that point
+    pub fn release_after(&self, channel: u8, time: f64) -> Option<f64> {
+        let ranges = &self.channels[channel as usize & 0xF];
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let index = ranges.partition_point(|(pressed, _)| *pressed <= time);
+        let (_, released) = ranges[index.checked_sub(1)?];
+        (released > time).then_some(released)
+    }
+}
+
+/// Collects the sustain pedal changes while a MIDI is being parsed
+pub struct SustainMapWriter {
+    map: SustainMap,
+    pressed: [Option<f64>; 16],
+}
+
+impl SustainMapWriter {
+    pub fn new() -> Self {
+        Self {
+            map: SustainMap::default(),
+            pressed: [None; 16],
+        }
+    }
+
+    pub fn push(&mut self, channel: u8, time: f64, value: u8) {
+        let channel = channel as usize & 0xF;
+        let pressed = &mut self.pressed[channel];
+
+        if value >= 64 {
+            pressed.get_or_insert(time);
+        } else if let Some(start) = pressed.take() {
+            self.map.channels[channel].push((start, time));
+        }
+    }
+
+    /// Releases any pedals that are still held at the end of the MIDI
+    pub fn finish(mut self, end: f64) -> SustainMap {
+        for (channel, pressed) in self.pressed.iter().enumerate() {
+            if let Some(start) = pressed {
+                self.map.channels[channel].push((*start, end));
+            }
+        }
+        self.map
+    }
+}
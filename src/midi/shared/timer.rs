@@ -2,6 +2,9 @@
 
 use std::time::{Duration, Instant};
 
+/// How far a following timer can drift before it gets seeked back in place
+const MAX_SYNC_DRIFT: f64 = 0.05;
+
 struct NotifySignal {
     new_state: TimerState,
     has_seeked: bool,
@@ -12,6 +15,7 @@ enum TimerState {
     Running {
         continue_time: Instant,
         time_offset: Duration,
+        speed: f64,
     },
     Paused {
         time_offset: Duration,
@@ -24,11 +28,19 @@ impl TimerState {
             TimerState::Running {
                 continue_time,
                 time_offset,
-            } => continue_time.elapsed() + *time_offset,
+                speed,
+            } => continue_time.elapsed().mul_f64(*speed) + *time_offset,
             TimerState::Paused { time_offset } => *time_offset,
         }
     }
 
+    fn speed(&self) -> f64 {
+        match self {
+            TimerState::Running { speed, .. } => *speed,
+            TimerState::Paused { .. } => 1.0,
+        }
+    }
+
     fn is_paused(&self) -> bool {
         matches!(self, TimerState::Paused { .. })
     }
@@ -37,6 +49,7 @@ impl TimerState {
 #[derive(Debug)]
 pub struct TimeKeeper {
     current_state: TimerState,
+    speed: f64,
     listeners: Vec<crossbeam_channel::Sender<NotifySignal>>,
 }
 
@@ -46,6 +59,7 @@ impl TimeKeeper {
             current_state: TimerState::Paused {
                 time_offset: Duration::new(0, 0),
             },
+            speed: 1.0,
             listeners: Vec::new(),
         }
     }
@@ -94,6 +108,7 @@ impl TimeKeeper {
                 self.current_state = TimerState::Running {
                     continue_time: Instant::now(),
                     time_offset: now,
+                    speed: self.speed,
                 };
             }
             TimerState::Running { .. } => {
@@ -115,10 +130,29 @@ impl TimeKeeper {
         self.current_state = TimerState::Running {
             continue_time: Instant::now(),
             time_offset: now,
+            speed: self.speed,
         };
         self.notify_listeners(false);
     }
 
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Changes how fast the time passes, 1.0 being real time
+    pub fn set_speed(&mut self, speed: f64) {
+        let now = self.get_time();
+        self.speed = speed.max(0.01);
+        if !self.current_state.is_paused() {
+            self.current_state = TimerState::Running {
+                continue_time: Instant::now(),
+                time_offset: now,
+                speed: self.speed,
+            };
+        }
+        self.notify_listeners(false);
+    }
+
     pub fn seek(&mut self, time: Duration) {
         if self.current_state.is_paused() {
             self.current_state = TimerState::Paused { time_offset: time };
@@ -126,10 +160,36 @@ impl TimeKeeper {
             self.current_state = TimerState::Running {
                 continue_time: Instant::now(),
                 time_offset: time,
+                speed: self.speed,
             };
         }
         self.notify_listeners(true);
     }
+
+    /// Moves a paused timer to the time without it counting as a seek, so the synth
+    /// isn't reset and nothing catches up on the events in between. Used to step
+    /// through the MIDI one frame at a time. Does nothing while the timer runs.
+    pub fn set_time(&mut self, time: Duration) {
+        if !self.current_state.is_paused() {
+            return;
+        }
+        self.current_state = TimerState::Paused { time_offset: time };
+        self.notify_listeners(false);
+    }
+
+    /// Follows another timer, used to keep MIDI layers playing along with the main MIDI
+    pub fn sync_to(&mut self, other: &TimeKeeper) {
+        self.speed = other.speed;
+
+        let drift = self.get_time().as_secs_f64() - other.get_time().as_secs_f64();
+        let seeked = drift.abs() > MAX_SYNC_DRIFT;
+        let speed_changed = self.current_state.speed() != other.current_state.speed();
+        if seeked || speed_changed || self.is_paused() != other.is_paused() {
+            // Copying the state keeps both timers on the exact same clock
+            self.current_state = other.current_state.clone();
+            self.notify_listeners(seeked);
+        }
+    }
 }
 
 pub struct TimeListener {
@@ -164,29 +224,32 @@ impl TimeListener {
     }
 
     pub fn wait_until(&mut self, time: Duration) -> WaitResult {
-        let curr_time = self.current.get_time();
-        if curr_time >= time {
-            return WaitResult::Ok;
-        }
+        loop {
+            let curr_time = self.current.get_time();
+            if curr_time >= time {
+                return WaitResult::Ok;
+            }
+
+            // TODO: Maybe find a more reliable way to wait while still reading?
+            let result = self
+                .reciever
+                .recv_timeout((time - curr_time).div_f64(self.current.speed()));
 
-        // TODO: Maybe find a more reliable way to wait while still reading?
-        let result = self.reciever.recv_timeout(time - curr_time);
-
-        match result {
-            Ok(signal) => {
-                self.current = signal.new_state;
-                if signal.has_seeked {
-                    WaitResult::Seeked(self.current.get_time())
-                } else if self.current.is_paused() {
-                    WaitResult::Paused
-                } else {
-                    WaitResult::Ok
+            match result {
+                Ok(signal) => {
+                    self.current = signal.new_state;
+                    if signal.has_seeked {
+                        return WaitResult::Seeked(self.current.get_time());
+                    } else if self.current.is_paused() {
+                        return WaitResult::Paused;
+                    }
+                    // Otherwise the speed has changed, so keep waiting with the new one
                 }
+                Err(error) => match error {
+                    crossbeam_channel::RecvTimeoutError::Timeout => return WaitResult::Ok,
+                    crossbeam_channel::RecvTimeoutError::Disconnected => return WaitResult::Killed,
+                },
             }
-            Err(error) => match error {
-                crossbeam_channel::RecvTimeoutError::Timeout => WaitResult::Ok,
-                crossbeam_channel::RecvTimeoutError::Disconnected => WaitResult::Killed,
-            },
         }
     }
 
@@ -0,0 +1,208 @@
+use std::{collections::VecDeque, fmt::Write, fs, path::Path};
+
+use midi_toolkit::{
+    events::{Event, MIDIEventEnum},
+    pipe,
+    sequence::{
+        event::{cancel_tempo_events, scale_event_time},
+        unwrap_items, TimeCaster,
+    },
+};
+use serde_derive::Serialize;
+
+use super::{
+    open_midi_and_signature,
+    shared::{
+        division::{MIDIDivision, TempoCanceller, DEFAULT_TEMPO},
+        musical_time::{MusicalTimeMap, MusicalTimeMapWriter},
+    },
+    MIDIFileLoadError, MIDIFormat,
+};
+
+/// Statistics about the notes and tempos of a whole MIDI file, which
+/// can be exported to check a file without playing through it
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MIDISummary {
+    /// The format number from the header
+    pub format: u16,
+    pub total_notes: u64,
+    pub track_notes: Vec<u64>,
+    pub channel_notes: [u64; 16],
+    pub lowest_key: Option<u8>,
+    pub highest_key: Option<u8>,
+    /// The length of the MIDI in seconds
+    pub duration: f64,
+    /// When the last note ends, in seconds
+    pub last_note_off: Option<f64>,
+    /// The BPM range, 120 if there are no tempo events. SMPTE timing has no BPM.
+    pub min_bpm: Option<f64>,
+    pub max_bpm: Option<f64>,
+    /// The most notes that start within any one second long stretch
+    pub peak_nps: u64,
+}
+
+/// Collects a [`MIDISummary`] from the notes while a MIDI is being parsed,
+/// so that the loaders can keep one around without reading the file again
+pub struct MIDISummaryWriter {
+    summary: MIDISummary,
+    /// The start times of the notes in the last second
+    recent_notes: VecDeque<f64>,
+}
+
+impl MIDISummaryWriter {
+    pub fn new(format: MIDIFormat, track_count: usize) -> Self {
+        Self {
+            summary: MIDISummary {
+                format: format.number(),
+                track_notes: vec![0; track_count],
+                ..Default::default()
+            },
+            recent_notes: VecDeque::new(),
+        }
+    }
+
+    /// The notes have to be pushed in the order they start in
+    pub fn push_note(&mut self, time: f64, track: usize, channel: u8, key: u8) {
+        let summary = &mut self.summary;
+        summary.total_notes += 1;
+        summary.track_notes[track] += 1;
+        summary.channel_notes[channel as usize] += 1;
+        summary.lowest_key = Some(summary.lowest_key.map_or(key, |k| k.min(key)));
+        summary.highest_key = Some(summary.highest_key.map_or(key, |k| k.max(key)));
+
+        while self
+            .recent_notes
+            .front()
+            .map_or(false, |&start| start <= time - 1.0)
+        {
+            self.recent_notes.pop_front();
+        }
+        self.recent_notes.push_back(time);
+        summary.peak_nps = summary.peak_nps.max(self.recent_notes.len() as u64);
+    }
+
+    /// The note offs have to be pushed in the order they come in
+    pub fn push_note_off(&mut self, time: f64) {
+        self.summary.last_note_off = Some(time);
+    }
+
+    pub fn finish(
+        self,
+        duration: f64,
+        division: MIDIDivision,
+        time_map: &MusicalTimeMap,
+    ) -> MIDISummary {
+        let mut summary = self.summary;
+        summary.duration = duration;
+
+        if !division.is_smpte() {
+            let default_bpm = 60_000_000.0 / DEFAULT_TEMPO as f64;
+            let (min, max) = time_map.bpm_range().unwrap_or((default_bpm, default_bpm));
+            summary.min_bpm = Some(min);
+            summary.max_bpm = Some(max);
+        }
+
+        summary
+    }
+}
+
+impl MIDISummary {
+    pub fn from_file(path: &str) -> Result<Self, MIDIFileLoadError> {
+        let (midi, _, format) = open_midi_and_signature(path)?;
+
+        let division = MIDIDivision::from_raw(midi.ppq());
+        let mut writer = MIDISummaryWriter::new(format, midi.track_count());
+        let mut time_map = MusicalTimeMapWriter::new(division, None);
+        let mut tempo_canceller = TempoCanceller::new(division, None);
+        let musical_time = time_map.map();
+
+        // The tempo events get cancelled out below, so the time map reads them beforehand
+        let batches = pipe!(
+            midi.iter_all_track_events_merged_batches()
+            |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(move |batch| {
+            if let Ok(batch) = batch {
+                time_map.push_batch(batch);
+            }
+        })
+        .map(move |batch| tempo_canceller.cancel(batch));
+        let merged = pipe!(
+            batches
+            |>cancel_tempo_events(250000)
+            |>scale_event_time(1.0 / division.ticks_per_quarter())
+            |>unwrap_items()
+        );
+
+        let mut time = 0.0;
+        for batch in merged {
+            time += batch.delta;
+
+            for event in batch.iter_events() {
+                match event.as_event() {
+                    Event::NoteOn(e) => {
+                        writer.push_note(time, event.track as usize, e.channel, e.key)
+                    }
+                    Event::NoteOff(_) => writer.push_note_off(time),
+                    _ => {}
+                }
+            }
+        }
+
+        let time_map = musical_time.read().unwrap();
+        Ok(writer.finish(time, division, &time_map))
+    }
+
+    /// Writes the summary as JSON if the path ends with `.json`, otherwise as CSV
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let is_json = path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("json"));
+
+        let contents = if is_json {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        } else {
+            self.to_csv()
+        };
+
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+
+    fn to_csv(&self) -> String {
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        // Numbered from 1 like in the tracks and channels windows
+        let mut csv = String::from("stat (tracks and channels count from 1),value\n");
+        let rows = [
+            ("format", self.format.to_string()),
+            ("total_notes", self.total_notes.to_string()),
+            ("lowest_key", opt(self.lowest_key)),
+            ("highest_key", opt(self.highest_key)),
+            ("duration", self.duration.to_string()),
+            ("last_note_off", opt(self.last_note_off)),
+            ("min_bpm", opt(self.min_bpm)),
+            ("max_bpm", opt(self.max_bpm)),
+            ("peak_nps", self.peak_nps.to_string()),
+        ];
+        for (name, value) in rows {
+            writeln!(csv, "{name},{value}").ok();
+        }
+        for (i, notes) in self.channel_notes.iter().enumerate() {
+            writeln!(csv, "channel_{}_notes,{notes}", i + 1).ok();
+        }
+        for (i, notes) in self.track_notes.iter().enumerate() {
+            writeln!(csv, "track_{}_notes,{notes}", i + 1).ok();
+        }
+        csv
+    }
+}
+
+/// Reads the MIDI and saves its summary, used by `--export-stats` and for MIDIs
+/// that don't keep a summary from loading
+pub fn export_midi_summary(midi_path: &str, path: &Path) -> Result<(), String> {
+    MIDISummary::from_file(midi_path)
+        .map_err(|e| e.to_string())?
+        .save(path)
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use midi_toolkit::events::{Event, MIDIEventEnum};
+use rustc_hash::FxHashMap;
+
+use super::{open_midi_and_signature, MIDIFileLoadError, MIDIFormat};
+
+/// The common problems of a MIDI file, found by reading through all of its
+/// events without loading the notes
+#[derive(Debug, Default)]
+pub struct MIDIValidation {
+    pub format: MIDIFormat,
+    pub track_count: usize,
+    pub total_events: u64,
+    /// Note ons that are never followed by a note off for the same key,
+    /// the loaders end them when the MIDI ends
+    pub unended_notes: u64,
+    /// The tracks with at least one unended note
+    pub unended_note_tracks: Vec<usize>,
+    /// Note offs without a note on playing in their track, channel and key
+    pub stray_note_offs: u64,
+    /// Data values above 127, or pitch bends outside of the 14 bit range
+    pub out_of_range_values: u64,
+    /// The events after this error can't be read. Broken running status ends
+    /// up here, since the parser can't tell which event the data belongs to.
+    pub parse_error: Option<String>,
+}
+
+impl MIDIValidation {
+    pub fn from_file(path: &str) -> Result<Self, MIDIFileLoadError> {
+        let (midi, _, format) = open_midi_and_signature(path)?;
+
+        let mut validation = MIDIValidation {
+            format,
+            track_count: midi.track_count(),
+            ..Default::default()
+        };
+
+        // How many notes are playing on each track, channel and key
+        let mut playing: FxHashMap<(u32, u8, u8), u64> = FxHashMap::default();
+
+        for batch in midi.iter_all_track_events_merged_batches() {
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(err) => {
+                    validation.parse_error = Some(format!("{err:?}"));
+                    break;
+                }
+            };
+
+            for event in batch.iter_events() {
+                validation.total_events += 1;
+                let track = event.track;
+                let in_range = match event.as_event() {
+                    Event::NoteOn(e) => {
+                        *playing.entry((track, e.channel, e.key)).or_default() += 1;
+                        e.key <= 127 && e.velocity <= 127
+                    }
+                    Event::NoteOff(e) => {
+                        match playing.get_mut(&(track, e.channel, e.key)) {
+                            Some(count) if *count > 0 => *count -= 1,
+                            _ => validation.stray_note_offs += 1,
+                        }
+                        e.key <= 127
+                    }
+                    Event::PolyphonicKeyPressure(e) => e.key <= 127 && e.velocity <= 127,
+                    Event::ControlChange(e) => e.controller <= 127 && e.value <= 127,
+                    Event::ProgramChange(e) => e.program <= 127,
+                    Event::ChannelPressure(e) => e.pressure <= 127,
+                    Event::PitchWheelChange(e) => (-8192..=8191).contains(&e.pitch),
+                    _ => true,
+                };
+                if !in_range {
+                    validation.out_of_range_values += 1;
+                }
+            }
+        }
+
+        for ((track, _, _), count) in playing {
+            if count > 0 {
+                validation.unended_notes += count;
+                validation.unended_note_tracks.push(track as usize);
+            }
+        }
+        validation.unended_note_tracks.sort_unstable();
+        validation.unended_note_tracks.dedup();
+
+        Ok(validation)
+    }
+
+    /// A line describing each problem that was found
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if let Some(err) = &self.parse_error {
+            issues.push(format!(
+                "The MIDI could only be read up to an error, the rest is skipped: {err}"
+            ));
+        }
+        if let Some(warning) = self.format.warning() {
+            issues.push(warning);
+        }
+        if self.unended_notes > 0 {
+            let tracks = self
+                .unended_note_tracks
+                .iter()
+                .map(|track| (track + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            issues.push(format!(
+                "{} notes are never ended, in tracks {tracks}. They are held until the MIDI ends",
+                self.unended_notes
+            ));
+        }
+        if self.stray_note_offs > 0 {
+            issues.push(format!(
+                "{} note offs don't end a playing note",
+                self.stray_note_offs
+            ));
+        }
+        if self.out_of_range_values > 0 {
+            issues.push(format!(
+                "{} events have values outside of the MIDI range",
+                self.out_of_range_values
+            ));
+        }
+        issues
+    }
+}
+
+impl fmt::Display for MIDIValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Format: {}", self.format)?;
+        writeln!(f, "Tracks: {}", self.track_count)?;
+        writeln!(f, "Events: {}", self.total_events)?;
+
+        let issues = self.issues();
+        if issues.is_empty() {
+            write!(f, "No problems found")
+        } else {
+            write!(f, "Problems:")?;
+            for issue in issues {
+                write!(f, "\n  - {issue}")?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -1,15 +1,17 @@
+pub mod frame_dump;
+pub mod screenshot;
 pub mod swapchain;
 
 use std::sync::Arc;
 
 use vulkano::{
     device::{
-        physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, Features, Queue,
-        QueueCreateInfo, QueueFlags,
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags,
     },
     format::Format,
     instance::{Instance, InstanceCreateInfo, InstanceExtensions},
-    swapchain::Surface,
+    swapchain::{PresentMode, Surface},
     sync::GpuFuture,
     Version, VulkanLibrary,
 };
@@ -30,6 +32,37 @@ use self::swapchain::{ManagedSwapchain, SwapchainFrame};
 
 const ICON: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/icon.bitmap"));
 
+/// With vsync off, frames are presented as soon as they're ready. If the GPU
+/// can't do that, vsync is used anyway since FIFO is always supported.
+fn choose_present_mode(
+    physical: &PhysicalDevice,
+    surface: &Surface,
+    vsync: bool,
+    wayland: bool,
+) -> PresentMode {
+    if vsync {
+        return PresentMode::Fifo;
+    }
+
+    let supported: Vec<_> = physical
+        .surface_present_modes(surface)
+        .map(|modes| modes.collect())
+        .unwrap_or_default();
+
+    // Wayland never tears, so mailbox is the lowest latency it allows
+    let preferred: &[PresentMode] = if wayland {
+        &[PresentMode::Mailbox]
+    } else {
+        &[PresentMode::Immediate, PresentMode::Mailbox]
+    };
+
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
 pub struct Renderer {
     _instance: Arc<Instance>,
     device: Arc<Device>,
@@ -40,9 +73,19 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(event_loop: &EventLoop<()>, name: &str, fullscreen: bool, mode: VideoMode) -> Self {
+    pub fn new(
+        event_loop: &EventLoop<()>,
+        name: &str,
+        fullscreen: bool,
+        vsync: bool,
+        mode: VideoMode,
+    ) -> Self {
         // Why
-        let library = VulkanLibrary::new().unwrap();
+        let library = VulkanLibrary::new().unwrap_or_else(|err| {
+            log::error!("Failed to load Vulkan: {err}");
+            log::error!("Make sure that your graphics drivers are installed and support Vulkan");
+            std::process::exit(1);
+        });
 
         // Add instance extensions based on needs
         let instance_extensions = InstanceExtensions {
@@ -58,7 +101,11 @@ impl Renderer {
                 ..Default::default()
             },
         )
-        .expect("Failed to create instance");
+        .unwrap_or_else(|err| {
+            log::error!("Failed to create the Vulkan instance: {err}");
+            log::error!("Try updating your graphics drivers");
+            std::process::exit(1);
+        });
 
         // Create rendering surface along with window
         let window = WindowBuilder::new()
@@ -87,7 +134,8 @@ impl Renderer {
         let surface = create_surface_from_winit(window.clone(), instance.clone())
             .expect("Failed to create surface");
 
-        // Get most performant physical device (device with most memory)
+        // Get most performant physical device (device with most memory). The notes are drawn
+        // with geometry shaders, so the devices that support them come first.
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
             ..DeviceExtensions::empty()
@@ -111,22 +159,40 @@ impl Renderer {
                     })
                     .map(|i| (p, i as u32))
             })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-                _ => 5,
+            .min_by_key(|(p, _)| {
+                let missing_features = !p.supported_features().contains(&features);
+                let device_type = match p.properties().device_type {
+                    PhysicalDeviceType::DiscreteGpu => 0,
+                    PhysicalDeviceType::IntegratedGpu => 1,
+                    PhysicalDeviceType::VirtualGpu => 2,
+                    PhysicalDeviceType::Cpu => 3,
+                    PhysicalDeviceType::Other => 4,
+                    _ => 5,
+                };
+                (missing_features, device_type)
             })
-            .unwrap();
+            .unwrap_or_else(|| {
+                log::error!("No GPU that can draw to the window was found");
+                log::error!("Try updating your graphics drivers");
+                std::process::exit(1);
+            });
 
-        println!(
+        log::info!(
             "Using device: {} (type: {:?})",
             physical_device.properties().device_name,
             physical_device.properties().device_type,
         );
 
+        if !physical_device.supported_features().contains(&features) {
+            log::error!(
+                "The GPU {} lacks the Vulkan features {:?}, which are needed to draw the notes",
+                physical_device.properties().device_name,
+                features.difference(physical_device.supported_features()),
+            );
+            log::error!("Try updating your graphics drivers");
+            std::process::exit(1);
+        }
+
         // Create device
         let (device, mut queues) = Device::new(
             physical_device.clone(),
@@ -140,7 +206,22 @@ impl Renderer {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .unwrap_or_else(|err| {
+            log::error!(
+                "Failed to set up the GPU {}: {err}",
+                physical_device.properties().device_name,
+            );
+            log::error!("Try updating your graphics drivers");
+            std::process::exit(1);
+        });
+
+        #[cfg(target_os = "linux")]
+        let wayland = event_loop.is_wayland();
+        #[cfg(not(target_os = "linux"))]
+        let wayland = false;
+
+        let present_mode = choose_present_mode(&physical_device, &surface, vsync, wayland);
+        log::debug!("Present Mode: {:?}", present_mode);
 
         // Create swap chain & frame(s) to which we'll render
         let swap_chain = ManagedSwapchain::create(
@@ -148,16 +229,7 @@ impl Renderer {
             window.clone(),
             physical_device,
             device.clone(),
-            #[cfg(target_os = "linux")]
-            if event_loop.is_wayland() {
-                println!("Present Mode: {:?}", crate::WAYLAND_PRESENT_MODE);
-                crate::WAYLAND_PRESENT_MODE
-            } else {
-                println!("Present Mode: {:?}", crate::PRESENT_MODE);
-                crate::PRESENT_MODE
-            },
-            #[cfg(not(target_os = "linux"))]
-            crate::PRESENT_MODE,
+            present_mode,
         );
 
         let queue = queues.next().unwrap();
@@ -217,17 +289,13 @@ impl Renderer {
         &mut self,
         draw: impl FnOnce(&SwapchainFrame, Box<dyn GpuFuture>) -> Box<dyn GpuFuture>,
     ) {
-        // Get the previous frame before starting a new one
-        let previous_frame_future = self.swap_chain.take_previous_frame_end().unwrap();
-
-        // Start a new frame
-        let (frame, acquire_future) = self.swap_chain.acquire_frame();
-
-        // Join the futures
-        let future = previous_frame_future.join(acquire_future);
+        // Start a new frame, skipping it while the swapchain can't be used
+        let Some((frame, future)) = self.swap_chain.acquire_frame() else {
+            return;
+        };
 
         // Call the passed-in renderer
-        let after_future = draw(&frame, Box::new(future));
+        let after_future = draw(&frame, future);
 
         // Finish render
         frame.present(&self.queue, after_future);
@@ -0,0 +1,109 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use vulkano::{
+    device::Queue,
+    format::Format,
+    image::{view::ImageView, AttachmentImage, ImageUsage, ImageViewAbstract},
+    memory::allocator::StandardMemoryAllocator,
+    sync::{self, GpuFuture},
+};
+
+use super::screenshot::FrameCapturer;
+
+/// Writes every frame as raw pixels to a file or named pipe, so it can be piped into
+/// a video encoder. The MIDI is moved forward by a fixed step for each frame, so the
+/// output doesn't depend on how fast the frames get rendered.
+///
+/// The notes are drawn into an image of their own rather than the window, so the
+/// frames have a fixed size and none of the UI. Each frame is `width * height * 4`
+/// bytes of 8 bit RGBA, with the rows going from the top of the image to the bottom
+/// and no padding between them. Where there are no notes the frames are black.
+///
+/// No audio is written, there is no offline synth in the tree to render it with.
+pub struct FrameDump {
+    out: BufWriter<File>,
+    fps: u32,
+    frames: u64,
+    image: Arc<ImageView<AttachmentImage>>,
+    capturer: FrameCapturer,
+}
+
+impl FrameDump {
+    pub fn new(
+        path: &str,
+        fps: u32,
+        size: [u32; 2],
+        queue: &Arc<Queue>,
+        format: Format,
+    ) -> Result<Self, String> {
+        let device = queue.device().clone();
+        let allocator = StandardMemoryAllocator::new_default(device.clone());
+        let image = AttachmentImage::with_usage(
+            &allocator,
+            size,
+            format,
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+        )
+        .map_err(|e| format!("{e:?}"))?;
+        let image = ImageView::new_default(image).map_err(|e| format!("{e:?}"))?;
+
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        log::info!("Writing {}x{} RGBA frames at {} fps", size[0], size[1], fps);
+
+        Ok(Self {
+            out: BufWriter::new(file),
+            fps: fps.max(1),
+            frames: 0,
+            image,
+            capturer: FrameCapturer::new(device),
+        })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The MIDI time that the next frame shows
+    pub fn time(&self) -> Duration {
+        Duration::from_secs_f64(self.frames as f64 / self.fps as f64)
+    }
+
+    /// The image that the next frame gets drawn into
+    pub fn image(&self) -> Arc<dyn ImageViewAbstract + 'static> {
+        self.image.clone()
+    }
+
+    /// Writes the frame that was drawn into the image. The notes are drawn falling
+    /// down, so the rows are flipped if they rise.
+    pub fn write(&mut self, queue: &Arc<Queue>, flipped: bool) -> Result<(), String> {
+        let now = sync::now(queue.device().clone()).boxed();
+        let (future, frame) = self
+            .capturer
+            .capture(queue, self.image.image().clone(), now);
+        let frame = frame?;
+        future
+            .then_signal_fence_and_flush()
+            .and_then(|future| future.wait(None))
+            .map_err(|e| format!("{e:?}"))?;
+
+        let mut image = frame.into_rgba()?;
+        if flipped {
+            image::imageops::flip_vertical_in_place(&mut image);
+        }
+
+        self.out
+            .write_all(image.as_raw())
+            .map_err(|e| e.to_string())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
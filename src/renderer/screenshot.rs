@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{
+        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
+        CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+    },
+    device::{Device, Queue},
+    format::Format,
+    image::ImageAccess,
+    memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator},
+    sync::{self, GpuFuture},
+};
+
+/// Copies finished frames into host memory so they can be saved as screenshots
+pub struct FrameCapturer {
+    allocator: StandardMemoryAllocator,
+    cb_allocator: StandardCommandBufferAllocator,
+}
+
+/// A frame that is being copied from the GPU. The pixels can be read
+/// once the future returned alongside it has completed.
+pub struct CapturedFrame {
+    buffer: Subbuffer<[u8]>,
+    size: [u32; 2],
+    format: Format,
+}
+
+impl FrameCapturer {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            allocator: StandardMemoryAllocator::new_default(device.clone()),
+            cb_allocator: StandardCommandBufferAllocator::new(device, Default::default()),
+        }
+    }
+
+    /// Adds a copy of the image to the end of the future
+    pub fn capture(
+        &self,
+        queue: &Arc<Queue>,
+        image: Arc<dyn ImageAccess>,
+        future: Box<dyn GpuFuture>,
+    ) -> (Box<dyn GpuFuture>, Result<CapturedFrame, String>) {
+        let (command_buffer, frame) = match self.record_copy(queue, image) {
+            Ok(copy) => copy,
+            Err(err) => return (future, Err(err)),
+        };
+
+        match future.then_execute(queue.clone(), command_buffer) {
+            Ok(future) => (future.boxed(), Ok(frame)),
+            // The frame was already lost with the future, so there's nothing left to wait on
+            Err(err) => (
+                sync::now(queue.device().clone()).boxed(),
+                Err(format!("{err:?}")),
+            ),
+        }
+    }
+
+    fn record_copy(
+        &self,
+        queue: &Arc<Queue>,
+        image: Arc<dyn ImageAccess>,
+    ) -> Result<(PrimaryAutoCommandBuffer, CapturedFrame), String> {
+        let size = image.dimensions().width_height();
+        let format = image.format();
+
+        let buffer = Buffer::new_slice::<u8>(
+            &self.allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            size[0] as u64 * size[1] as u64 * 4,
+        )
+        .map_err(|err| format!("{err:?}"))?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.cb_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(|err| format!("{err:?}"))?;
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+            .map_err(|err| format!("{err:?}"))?;
+        let command_buffer = builder.build().map_err(|err| format!("{err:?}"))?;
+
+        Ok((
+            command_buffer,
+            CapturedFrame {
+                buffer,
+                size,
+                format,
+            },
+        ))
+    }
+}
+
+impl CapturedFrame {
+    /// Reads the captured pixels as RGBA, this must only be called after the frame was presented
+    pub fn into_rgba(self) -> Result<image::RgbaImage, String> {
+        let swap_red_blue = match self.format {
+            Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => true,
+            Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => false,
+            format => return Err(format!("Unsupported image format {format:?}")),
+        };
+
+        let mut pixels = self.buffer.read().map_err(|e| format!("{e:?}"))?.to_vec();
+
+        for pixel in pixels.chunks_exact_mut(4) {
+            if swap_red_blue {
+                pixel.swap(0, 2);
+            }
+            pixel[3] = 255;
+        }
+
+        image::RgbaImage::from_raw(self.size[0], self.size[1], pixels)
+            .ok_or_else(|| "Captured frame has the wrong size".to_string())
+    }
+}
@@ -5,8 +5,8 @@ use vulkano::{
     format::Format,
     image::{view::ImageView, ImageUsage, SwapchainImage},
     swapchain::{
-        AcquireError, PresentMode, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo,
-        SwapchainCreationError, SwapchainPresentInfo,
+        AcquireError, PresentMode, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+        SwapchainPresentInfo,
     },
     sync::{self, FlushError, GpuFuture},
 };
@@ -56,7 +56,9 @@ impl ManagedSwapchain {
                 min_image_count: surface_capabilities.min_image_count,
                 image_format: Some(image_format),
                 image_extent,
-                image_usage: ImageUsage::COLOR_ATTACHMENT,
+                // Transfer source lets frames be copied out for screenshots
+                image_usage: ImageUsage::COLOR_ATTACHMENT
+                    | (surface_capabilities.supported_usage_flags & ImageUsage::TRANSFER_SRC),
                 composite_alpha: surface_capabilities
                     .supported_composite_alpha
                     .into_iter()
@@ -100,14 +102,23 @@ impl ManagedSwapchain {
         }
     }
 
-    pub fn recreate(&mut self) {
+    /// Rebuilds the swapchain for the current window size. Returns false if it
+    /// can't be built right now, e.g. while the window is minimized.
+    pub fn recreate(&mut self) -> bool {
+        if self.state.size.contains(&0) {
+            return false;
+        }
+
         let (new_swapchain, new_images) = match self.swap_chain.recreate(SwapchainCreateInfo {
             image_extent: self.state.size,
             ..self.swap_chain.create_info()
         }) {
             Ok(r) => r,
-            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-            Err(e) => panic!("Failed to recreate swapchain: {e:?}"),
+            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return false,
+            Err(e) => {
+                log::error!("Failed to recreate swapchain: {e:?}");
+                return false;
+            }
         };
         self.swap_chain = new_swapchain;
         let new_images = new_images
@@ -116,51 +127,54 @@ impl ManagedSwapchain {
             .collect::<Vec<_>>();
 
         self.image_views = new_images;
+        true
     }
 
-    pub fn take_previous_frame_end(&mut self) -> Option<Box<dyn GpuFuture>> {
-        self.previous_frame_end.take()
-    }
-
-    pub fn acquire_frame(&mut self) -> (SwapchainFrame, SwapchainAcquireFuture) {
+    /// Acquires the next image, along with a future that waits for both the image and
+    /// the previous frame. Returns `None` if the frame has to be skipped because the
+    /// swapchain is out of date and can't be rebuilt yet.
+    pub fn acquire_frame(&mut self) -> Option<(SwapchainFrame, Box<dyn GpuFuture>)> {
         if self.recreate_on_next_frame {
-            self.recreate();
+            if !self.recreate() {
+                return None;
+            }
             self.recreate_on_next_frame = false;
         }
 
-        let mut tries = 0;
-        loop {
-            tries += 1;
-            if tries > 10 {
-                panic!("Failed to acquire next image after 10 tries");
-            }
+        let next = vulkano::swapchain::acquire_next_image(self.swap_chain.clone(), None);
 
-            let next = vulkano::swapchain::acquire_next_image(self.swap_chain.clone(), None);
-
-            let (image_num, suboptimal, acquire_future) = match next {
-                Ok(r) => r,
-                // TODO: Handle more errors, e.g. DeviceLost, by re-creating the entire graphics chain
-                Err(AcquireError::OutOfDate) => {
-                    self.recreate();
-                    continue;
-                }
-                Err(e) => panic!("Failed to acquire next image: {e:?}"),
-            };
-
-            if suboptimal {
-                self.recreate();
-                continue;
+        let (image_num, suboptimal, acquire_future) = match next {
+            Ok(r) => r,
+            // TODO: Handle more errors, e.g. DeviceLost, by re-creating the entire graphics chain
+            Err(AcquireError::OutOfDate) => {
+                self.recreate_on_next_frame = true;
+                return None;
             }
+            Err(e) => {
+                log::error!("Failed to acquire next image: {e:?}");
+                return None;
+            }
+        };
 
-            let frame = SwapchainFrame {
-                presented: false,
-                image_num,
-                image: self.image_views[image_num as usize].clone(),
-                managed_swap_chain: self,
-            };
-
-            return (frame, acquire_future);
+        // A suboptimal image can still be presented, so only rebuild for the next frame
+        if suboptimal {
+            self.recreate_on_next_frame = true;
         }
+
+        let previous_frame_end = self
+            .previous_frame_end
+            .take()
+            .unwrap_or_else(|| sync::now(self.device.clone()).boxed());
+        let future = previous_frame_end.join(acquire_future).boxed();
+
+        let frame = SwapchainFrame {
+            presented: false,
+            image_num,
+            image: self.image_views[image_num as usize].clone(),
+            managed_swap_chain: self,
+        };
+
+        Some((frame, future))
     }
 }
 
@@ -192,7 +206,7 @@ impl<'a> SwapchainFrame<'a> {
                 // https://github.com/vulkano-rs/vulkano/issues/627
                 match future.wait(None) {
                     Ok(x) => x,
-                    Err(err) => println!("err: {err:?}"),
+                    Err(err) => log::error!("Failed to present the frame: {err:?}"),
                 }
                 sc.previous_frame_end = Some(future.boxed());
             }
@@ -201,7 +215,7 @@ impl<'a> SwapchainFrame<'a> {
                 sc.previous_frame_end = Some(sync::now(sc.device.clone()).boxed());
             }
             Err(e) => {
-                println!("Failed to flush future: {e:?}");
+                log::error!("Failed to flush future: {e:?}");
                 sc.previous_frame_end = Some(sync::now(sc.device.clone()).boxed());
             }
         }
@@ -215,7 +229,12 @@ impl<'a> SwapchainFrame<'a> {
 impl<'a> std::ops::Drop for SwapchainFrame<'a> {
     fn drop(&mut self) {
         if !self.presented {
-            panic!("SwapchainFrame not presented.")
+            // An acquired image can only be given back by presenting it, so rebuild
+            // the swapchain instead of leaving the image acquired forever
+            log::warn!("SwapchainFrame dropped without being presented");
+            let sc = &mut self.managed_swap_chain;
+            sc.previous_frame_end = Some(sync::now(sc.device.clone()).boxed());
+            sc.recreate_on_next_frame = true;
         }
     }
 }
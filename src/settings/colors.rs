@@ -0,0 +1,84 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use egui::Color32;
+use serde_derive::Deserialize;
+
+#[derive(Deserialize)]
+struct HexColor(#[serde(with = "super::color32_serde")] Color32);
+
+/// The layout of a color file, the tables map indices to hex colors
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ColorFile {
+    tracks: BTreeMap<String, HexColor>,
+    channels: BTreeMap<String, HexColor>,
+}
+
+/// Colors for specific tracks and channels, loaded from a JSON or TOML file
+/// such as:
+///
+/// ```toml
+/// [tracks]
+/// 0 = "#ff0000"
+///
+/// [channels]
+/// 9 = "#ffffff"
+/// ```
+///
+/// Tracks and channels are counted from 0. A track color takes priority over a
+/// channel color, and anything that isn't in the file keeps its palette color.
+#[derive(Debug, Clone, Default)]
+pub struct ColorAssignments {
+    tracks: BTreeMap<usize, Color32>,
+    channels: BTreeMap<usize, Color32>,
+}
+
+impl ColorAssignments {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+        let is_json = path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("json"));
+        let file: ColorFile = if is_json {
+            serde_json::from_str(&text).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())?
+        };
+
+        fn indices(
+            table: BTreeMap<String, HexColor>,
+            name: &str,
+        ) -> Result<BTreeMap<usize, Color32>, String> {
+            table
+                .into_iter()
+                .map(|(index, color)| match index.trim().parse() {
+                    Ok(index) => Ok((index, color.0)),
+                    Err(_) => Err(format!("{index} is not a valid {name} number")),
+                })
+                .collect()
+        }
+
+        let channels = indices(file.channels, "channel")?;
+        if let Some(channel) = channels.keys().find(|channel| **channel > 15) {
+            return Err(format!("{channel} is not a valid channel number"));
+        }
+
+        Ok(ColorAssignments {
+            tracks: indices(file.tracks, "track")?,
+            channels,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty() && self.channels.is_empty()
+    }
+
+    /// The color assigned to a channel of a track, if there is one
+    pub fn color(&self, track: usize, channel: usize) -> Option<Color32> {
+        self.tracks
+            .get(&track)
+            .or_else(|| self.channels.get(&channel))
+            .copied()
+    }
+}
@@ -0,0 +1,334 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use egui::{Key, Modifiers};
+use serde::{de::IntoDeserializer, Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
+
+/// Everything that can be done with a keyboard shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShortcutAction {
+    TogglePause,
+    Stop,
+    SkipForward,
+    SkipBackward,
+    JumpToStart,
+    JumpToEnd,
+    FastForward,
+    Rewind,
+    SlowerNotes,
+    FasterNotes,
+    ToggleFullscreen,
+    ToggleTopPanel,
+    ToggleStatistics,
+    ToggleFps,
+    ToggleChannels,
+    ToggleCompactMode,
+    Panic,
+    Screenshot,
+    CycleSoundfont,
+    ToggleShortcuts,
+}
+
+/// Every key that can be bound, used to parse the key names in the config
+#[rustfmt::skip]
+const ALL_KEYS: &[Key] = &[
+    Key::ArrowDown, Key::ArrowLeft, Key::ArrowRight, Key::ArrowUp,
+    Key::Escape, Key::Tab, Key::Backspace, Key::Enter, Key::Space,
+    Key::Insert, Key::Delete, Key::Home, Key::End, Key::PageUp, Key::PageDown,
+    Key::Minus, Key::PlusEquals,
+    Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4,
+    Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+    Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10,
+    Key::F11, Key::F12, Key::F13, Key::F14, Key::F15, Key::F16, Key::F17, Key::F18, Key::F19, Key::F20,
+];
+
+/// A key combination, stored in the config as e.g. "Ctrl+G"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: Key,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    const fn new(key: Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+        }
+    }
+
+    const fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    const fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    pub fn from_key(key: Key, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+        }
+    }
+
+    /// The name of the key combination as shown to the user, e.g. "Ctrl+Page Up"
+    pub fn display_name(&self) -> String {
+        let key = match self.key {
+            Key::ArrowRight => "Right Arrow".to_string(),
+            Key::ArrowLeft => "Left Arrow".to_string(),
+            Key::ArrowUp => "Up Arrow".to_string(),
+            Key::ArrowDown => "Down Arrow".to_string(),
+            Key::PageUp => "Page Up".to_string(),
+            Key::PageDown => "Page Down".to_string(),
+            key => format!("{key:?}"),
+        };
+
+        let mut name = String::new();
+        if self.ctrl {
+            name.push_str("Ctrl+");
+        }
+        if self.alt {
+            name.push_str("Alt+");
+        }
+        name.push_str(&key);
+        name
+    }
+
+    pub fn matches(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.key == key && self.ctrl == modifiers.ctrl && self.alt == modifiers.alt
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let key_name = parts.pop().unwrap_or_default();
+
+        let key = ALL_KEYS
+            .iter()
+            .find(|key| format!("{key:?}").eq_ignore_ascii_case(key_name))
+            .ok_or_else(|| format!("Unknown key \"{key_name}\""))?;
+
+        let mut binding = KeyBinding::new(*key);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown modifier \"{modifier}\", expected Ctrl or Alt"
+                    ))
+                }
+            }
+        }
+        Ok(binding)
+    }
+}
+
+impl serde::Serialize for KeyBinding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for KeyBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+pub struct Shortcut {
+    pub action: ShortcutAction,
+    /// The key combination used when the config doesn't rebind the action
+    pub default: KeyBinding,
+    /// The action repeats for as long as the key is held down
+    pub held: bool,
+    pub description: &'static str,
+}
+
+impl Shortcut {
+    const fn new(action: ShortcutAction, default: KeyBinding, description: &'static str) -> Self {
+        Self {
+            action,
+            default,
+            held: false,
+            description,
+        }
+    }
+
+    const fn held(mut self) -> Self {
+        self.held = true;
+        self
+    }
+}
+
+/// The key combination of every shortcut action, as saved in the config
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Keybindings(BTreeMap<ShortcutAction, KeyBinding>);
+
+impl<'de> serde::Deserialize<'de> for Keybindings {
+    /// Actions that don't exist are skipped, so a config written by
+    /// another version of Wasabi still loads
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = <BTreeMap<String, KeyBinding> as serde::Deserialize>::deserialize(deserializer)?;
+        let bindings = raw
+            .into_iter()
+            .filter_map(|(name, binding)| {
+                let action = <ShortcutAction as serde::Deserialize>::deserialize(
+                    name.as_str().into_deserializer(),
+                )
+                .map_err(|_: serde::de::value::Error| {
+                    log::warn!("Ignoring the keybinding of unknown action \"{name}\"")
+                })
+                .ok()?;
+                Some((action, binding))
+            })
+            .collect();
+        Ok(Self(bindings))
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self(shortcuts().iter().map(|s| (s.action, s.default)).collect())
+    }
+}
+
+impl Keybindings {
+    /// The binding of the action, falling back to the default if the config doesn't have one
+    pub fn get(&self, action: ShortcutAction) -> KeyBinding {
+        self.0.get(&action).copied().unwrap_or_else(|| {
+            shortcuts()
+                .iter()
+                .find(|s| s.action == action)
+                .map(|s| s.default)
+                .unwrap()
+        })
+    }
+
+    pub fn set(&mut self, action: ShortcutAction, binding: KeyBinding) {
+        self.0.insert(action, binding);
+    }
+
+    /// The other actions that are bound to the same key combination
+    pub fn conflicts(&self, action: ShortcutAction) -> Vec<ShortcutAction> {
+        let binding = self.get(action);
+        shortcuts()
+            .iter()
+            .map(|s| s.action)
+            .filter(|other| *other != action && self.get(*other) == binding)
+            .collect()
+    }
+}
+
+/// All the keyboard shortcuts, used both for handling the keys and for the help overlay
+pub fn shortcuts() -> &'static [Shortcut] {
+    use ShortcutAction::*;
+
+    const SHORTCUTS: &[Shortcut] = &[
+        Shortcut::new(TogglePause, KeyBinding::new(Key::Space), "Play/Pause MIDI"),
+        Shortcut::new(
+            Stop,
+            KeyBinding::new(Key::S),
+            "Stop and go back to the start",
+        ),
+        Shortcut::new(
+            SkipForward,
+            KeyBinding::new(Key::ArrowRight),
+            "Skip 1 second",
+        ),
+        Shortcut::new(
+            SkipBackward,
+            KeyBinding::new(Key::ArrowLeft),
+            "Go back 1 second",
+        ),
+        Shortcut::new(JumpToStart, KeyBinding::new(Key::Home), "Jump to start"),
+        Shortcut::new(JumpToEnd, KeyBinding::new(Key::End), "Jump to the ending"),
+        Shortcut::new(FastForward, KeyBinding::new(Key::PageUp), "Fast forward").held(),
+        Shortcut::new(Rewind, KeyBinding::new(Key::PageDown), "Rewind").held(),
+        Shortcut::new(
+            SlowerNotes,
+            KeyBinding::new(Key::ArrowUp),
+            "Slower note speed",
+        ),
+        Shortcut::new(
+            FasterNotes,
+            KeyBinding::new(Key::ArrowDown),
+            "Faster note speed",
+        ),
+        Shortcut::new(
+            ToggleFullscreen,
+            KeyBinding::new(Key::Enter).alt(),
+            "Toggle fullscreen",
+        ),
+        Shortcut::new(
+            ToggleTopPanel,
+            KeyBinding::new(Key::F).ctrl(),
+            "Show/Hide top panel",
+        ),
+        Shortcut::new(
+            ToggleStatistics,
+            KeyBinding::new(Key::G).ctrl(),
+            "Show/Hide statistics",
+        ),
+        Shortcut::new(
+            ToggleFps,
+            KeyBinding::new(Key::P).ctrl(),
+            "Show/Hide FPS counter",
+        ),
+        Shortcut::new(
+            ToggleChannels,
+            KeyBinding::new(Key::I).ctrl(),
+            "Show/Hide channel list",
+        ),
+        Shortcut::new(
+            ToggleCompactMode,
+            KeyBinding::new(Key::H).ctrl(),
+            "Hide/Show everything but the notes",
+        ),
+        Shortcut::new(
+            Panic,
+            KeyBinding::new(Key::Escape),
+            "Stop all playing notes",
+        ),
+        Shortcut::new(Screenshot, KeyBinding::new(Key::F12), "Save a screenshot"),
+        Shortcut::new(
+            CycleSoundfont,
+            KeyBinding::new(Key::F8),
+            "Switch to the next soundfont",
+        ),
+        Shortcut::new(
+            ToggleShortcuts,
+            KeyBinding::new(Key::F1),
+            "Show this list (also ?)",
+        ),
+    ];
+
+    SHORTCUTS
+}
@@ -3,7 +3,10 @@ use egui::Color32;
 use serde_derive::Deserialize;
 use std::fs;
 
-use super::{MidiLoading, MidiSettings, Synth, SynthSettings, VisualSettings, WasabiSettings};
+use super::{
+    ColorBy, ColorPalette, KeyColorBlend, MidiLoading, MidiSettings, NoteSpeedUnit, OverlayCorner,
+    Synth, SynthSettings, VisualSettings, WasabiSettings,
+};
 
 #[derive(Deserialize)]
 pub struct WasabiConfigFileV0 {
@@ -41,19 +44,40 @@ impl WasabiConfigFileV0 {
                     synth: Synth::from(cfg.synth),
                     buffer_ms: cfg.buffer_ms,
                     use_threadpool: cfg.use_threadpool,
+                    render_threads: None,
                     limit_layers: cfg.limit_layers,
                     layer_count: cfg.layer_count,
                     fade_out_kill: cfg.fade_out_kill,
                     linear_envelope: cfg.linear_envelope,
                     use_effects: cfg.use_effects,
+                    fade_ms: 5.0,
+                    force_program: None,
                     sfz_path: cfg.sfz_path,
+                    soundfont_cycle: Vec::new(),
                     vel_ignore: cfg.vel_ignore_lo..=cfg.vel_ignore_hi,
+                    output_device: None,
+                    sample_rate: None,
+                    max_voices: None,
                 },
                 midi: MidiSettings {
                     note_speed: cfg.note_speed,
+                    note_speed_unit: NoteSpeedUnit::Factor,
+                    lookahead_seconds: None,
                     random_colors: cfg.random_colors,
+                    color_palette: ColorPalette::Default,
+                    color_by: ColorBy::Both,
+                    color_file: None,
                     key_range: cfg.first_key..=cfg.last_key,
+                    follow_active_keys: false,
                     midi_loading: MidiLoading::from(cfg.midi_loading),
+                    fast_forward_speed: 5.0,
+                    seek_on_release: false,
+                    transpose: 0,
+                    tempo_override: None,
+                    layer_channel_offset: 8,
+                    remember_playlist: false,
+                    playlist: Vec::new(),
+                    resume_last: false,
                 },
                 visual: VisualSettings {
                     bg_color: Color32::from_rgb(
@@ -61,16 +85,70 @@ impl WasabiConfigFileV0 {
                         bg.get_green() as u8,
                         bg.get_blue() as u8,
                     ),
+                    bg_color_bottom: None,
                     bar_color: Color32::from_rgb(
                         bar.get_red() as u8,
                         bar.get_green() as u8,
                         bar.get_blue() as u8,
                     ),
+                    bar_height_px: None,
+                    bar_gradient: true,
+                    bar_border: false,
+                    bg_image: None,
+                    note_outline: true,
+                    note_rounding: 0.0,
+                    rounding_min_length_px: 4.0,
+                    key_color_blend: KeyColorBlend::Off,
+                    visualize_pitch_bend: false,
+                    pitch_bend_range: 2,
+                    notes_fall_down: true,
+                    key_press_animation: false,
+                    velocity_key_colors: false,
+                    keyboard_height_ratio: 11.6,
+                    max_keyboard_height: None,
+                    beat_flash: false,
+                    ui_scale: 1.0,
+                    theme: Default::default(),
+                    brightness: 1.0,
+                    saturation: 1.0,
+                    min_note_height_px: 1.0,
+                    note_gradient: false,
+                    show_sustain: false,
+                    pan_notes: false,
+                    separate_drums: false,
+                    note_tooltips: true,
+                    gradient_strength: 0.5,
+                    note_opacity: 1.0,
+                    keyboard_note_opacity: false,
+                    attack_glow: 0.0,
+                    edge_fade_px: 12.0,
+                    cc_mappings: Vec::new(),
+                    max_rendered_notes: None,
                     show_top_pannel: true,
                     show_statistics: true,
+                    stats_number_format: Default::default(),
+                    stats_corner: OverlayCorner::TopLeft,
+                    show_fps: false,
+                    fps_corner: Default::default(),
+                    show_frame_graph: false,
+                    show_channels: false,
+                    show_channel_leds: false,
                     fullscreen: false,
+                    vsync: true,
+                    inhibit_sleep: true,
                 },
+                keybindings: Default::default(),
+                autosave: false,
                 load_midi_file: None,
+                start_paused: false,
+                audio_only: false,
+                loop_count: None,
+                midi_input: None,
+                export_stats: None,
+                validate: false,
+                frames_out: None,
+                frames_fps: 0,
+                frames_size: [0, 0],
             })
         } else {
             Ok(WasabiSettings::default())
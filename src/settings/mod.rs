@@ -1,7 +1,7 @@
 use clap::{parser::ValueSource, value_parser, Arg, ArgAction, Command, ValueHint};
 use colors_transform::{Color, Rgb};
 use directories::BaseDirs;
-use egui::Color32;
+use egui::{Align2, Color32};
 use num_enum::FromPrimitive;
 use serde_derive::{Deserialize, Serialize};
 use std::{
@@ -11,11 +11,39 @@ use std::{
     ops::RangeInclusive,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::OnceLock,
 };
 use xsynth_core::{channel::ChannelInitOptions, soundfont::SoundfontInitOptions};
 use xsynth_realtime::config::XSynthRealtimeConfig;
 
+use crate::audio_playback::{midi_input::list_midi_inputs, xsynth::list_output_devices};
+
+use self::keybindings::Keybindings;
+
+pub mod colors;
+pub mod keybindings;
 mod migrations;
+pub mod recent;
+
+/// The smallest and largest allowed GUI scale
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.0;
+
+/// The note speed factors that can be picked, higher is faster
+pub const NOTE_SPEED_FACTOR_RANGE: RangeInclusive<f64> = 0.0001..=2.0;
+
+/// The BPMs that the tempo of a MIDI can be overridden with
+pub const TEMPO_OVERRIDE_RANGE: RangeInclusive<f64> = 10.0..=1000.0;
+
+/// Turns a note speed factor, as taken by `--note-speed`, into the seconds
+/// that the notes take to cross the screen, which is what gets stored
+pub fn note_speed_from_factor(factor: f64) -> f64 {
+    2.0001 - factor
+}
+
+/// Turns the seconds that the notes take to cross the screen into a note speed factor
+pub fn note_speed_to_factor(seconds: f64) -> f64 {
+    2.0001 - seconds
+}
 
 #[inline(always)]
 fn f64_parser(s: &str) -> Result<f64, String> {
@@ -25,13 +53,27 @@ fn f64_parser(s: &str) -> Result<f64, String> {
 #[inline(always)]
 fn note_speed(s: &str) -> Result<f64, String> {
     let num: f64 = f64_parser(s)?;
-    if (0.0001..=2.0).contains(&num) {
-        Ok(2.0001 - num)
+    if NOTE_SPEED_FACTOR_RANGE.contains(&num) {
+        Ok(note_speed_from_factor(num))
     } else {
         Err(String::from("Number must be between >0 and 2.0"))
     }
 }
 
+#[inline(always)]
+fn tempo_override(s: &str) -> Result<f64, String> {
+    let num: f64 = f64_parser(s)?;
+    if TEMPO_OVERRIDE_RANGE.contains(&num) {
+        Ok(num)
+    } else {
+        Err(format!(
+            "Number must be between {} and {}",
+            TEMPO_OVERRIDE_RANGE.start(),
+            TEMPO_OVERRIDE_RANGE.end()
+        ))
+    }
+}
+
 #[inline(always)]
 fn color_parser(s: &str) -> Result<Color32, String> {
     let rgb = Rgb::from_hex_str(s).map_err(|e| e.message)?;
@@ -44,12 +86,23 @@ fn color_parser(s: &str) -> Result<Color32, String> {
 
 #[inline(always)]
 fn range_parser(s: &str) -> Result<RangeInclusive<u8>, String> {
+    if let Ok(preset) = KeyRangePreset::from_str(s) {
+        return Ok(preset.range());
+    }
+
     let range = s
         .split_once(',')
         .ok_or_else(|| String::from("This argument requires 2 numbers, comma seperated"))?;
 
-    Ok(range.0.parse().map_err(|e| format!("{}", e))?
-        ..=range.1.parse().map_err(|e| format!("{}", e))?)
+    let first: u8 = range.0.parse().map_err(|e| format!("{}", e))?;
+    let last: u8 = range.1.parse().map_err(|e| format!("{}", e))?;
+    if first > last {
+        return Err(String::from(
+            "The first key of the range must not be above the last key",
+        ));
+    }
+
+    Ok(first..=last)
 }
 
 mod color32_serde {
@@ -93,6 +146,29 @@ mod color32_serde {
     {
         de.deserialize_str(ColorVisitor)
     }
+
+    /// For optional colors, which must be skipped when they aren't set
+    pub mod option {
+        use egui::Color32;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S>(color: &Option<Color32>, ser: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match color {
+                Some(color) => super::serialize(color, ser),
+                None => ser.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(de: D) -> Result<Option<Color32>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(de).map(Some)
+        }
+    }
 }
 
 mod range_serde {
@@ -240,26 +316,481 @@ impl FromStr for Synth {
     }
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorPalette {
+    #[default]
+    Default,
+    Colorblind,
+    Grayscale,
+    Pastel,
+}
+
+impl ColorPalette {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ColorPalette::Default => "Default",
+            ColorPalette::Colorblind => "Colorblind Safe",
+            ColorPalette::Grayscale => "Grayscale",
+            ColorPalette::Pastel => "Pastel",
+        }
+    }
+}
+
+impl FromStr for ColorPalette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(ColorPalette::Default),
+            "colorblind" => Ok(ColorPalette::Colorblind),
+            "grayscale" => Ok(ColorPalette::Grayscale),
+            "pastel" => Ok(ColorPalette::Pastel),
+            s => Err(format!(
+                "{} was not expected. Expected one of `default`, `colorblind`, `grayscale` or `pastel`",
+                s
+            )),
+        }
+    }
+}
+
+/// What decides the color of a note
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorBy {
+    #[default]
+    Both,
+    Track,
+    Channel,
+}
+
+impl ColorBy {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ColorBy::Both => "Track and Channel",
+            ColorBy::Track => "Track",
+            ColorBy::Channel => "Channel",
+        }
+    }
+}
+
+impl FromStr for ColorBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "both" => Ok(ColorBy::Both),
+            "track" => Ok(ColorBy::Track),
+            "channel" => Ok(ColorBy::Channel),
+            s => Err(format!(
+                "{} was not expected. Expected one of `both`, `track` or `channel`",
+                s
+            )),
+        }
+    }
+}
+
+/// Common keyboard sizes, which can be picked instead of typing in the key range
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeyRangePreset {
+    Piano88,
+    Keys61,
+    Full,
+}
+
+impl KeyRangePreset {
+    pub const ALL: [KeyRangePreset; 3] = [
+        KeyRangePreset::Piano88,
+        KeyRangePreset::Keys61,
+        KeyRangePreset::Full,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            KeyRangePreset::Piano88 => "88 Keys",
+            KeyRangePreset::Keys61 => "61 Keys",
+            KeyRangePreset::Full => "Full MIDI",
+        }
+    }
+
+    pub const fn range(self) -> RangeInclusive<u8> {
+        match self {
+            KeyRangePreset::Piano88 => 21..=108,
+            KeyRangePreset::Keys61 => 36..=96,
+            KeyRangePreset::Full => 0..=127,
+        }
+    }
+
+    /// The preset with exactly this range, if there is one
+    pub fn find(range: &RangeInclusive<u8>) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.range() == *range)
+    }
+}
+
+impl FromStr for KeyRangePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "88" | "piano" => Ok(KeyRangePreset::Piano88),
+            "61" => Ok(KeyRangePreset::Keys61),
+            "full" => Ok(KeyRangePreset::Full),
+            s => Err(format!(
+                "{} was not expected. Expected one of `88`, `piano`, `61` or `full`",
+                s
+            )),
+        }
+    }
+}
+
+/// How the note speed is shown in the GUI
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteSpeedUnit {
+    /// The same factor that `--note-speed` takes
+    #[default]
+    Factor,
+    /// The seconds that the notes take to cross the screen
+    Seconds,
+}
+
+impl NoteSpeedUnit {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            NoteSpeedUnit::Factor => "Speed Factor",
+            NoteSpeedUnit::Seconds => "Seconds on Screen",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+impl UiTheme {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            UiTheme::Dark => "Dark",
+            UiTheme::Light => "Light",
+            UiTheme::System => "System",
+        }
+    }
+}
+
+impl FromStr for UiTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dark" => Ok(UiTheme::Dark),
+            "light" => Ok(UiTheme::Light),
+            "system" => Ok(UiTheme::System),
+            s => Err(format!(
+                "{} was not expected. Expected one of `dark`, `light` or `system`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayCorner {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            OverlayCorner::TopLeft => "Top Left",
+            OverlayCorner::TopRight => "Top Right",
+            OverlayCorner::BottomLeft => "Bottom Left",
+            OverlayCorner::BottomRight => "Bottom Right",
+        }
+    }
+
+    pub const fn align(self) -> Align2 {
+        match self {
+            OverlayCorner::TopLeft => Align2::LEFT_TOP,
+            OverlayCorner::TopRight => Align2::RIGHT_TOP,
+            OverlayCorner::BottomLeft => Align2::LEFT_BOTTOM,
+            OverlayCorner::BottomRight => Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+impl FromStr for OverlayCorner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "top-left" => Ok(OverlayCorner::TopLeft),
+            "top-right" => Ok(OverlayCorner::TopRight),
+            "bottom-left" => Ok(OverlayCorner::BottomLeft),
+            "bottom-right" => Ok(OverlayCorner::BottomRight),
+            s => Err(format!(
+                "{} was not expected. Expected one of `top-left`, `top-right`, `bottom-left` or `bottom-right`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsNumberFormat {
+    /// 1234567
+    Raw,
+    /// 1,234,567
+    #[default]
+    Grouped,
+    /// 1.2M
+    Compact,
+}
+
+impl StatsNumberFormat {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            StatsNumberFormat::Raw => "Raw",
+            StatsNumberFormat::Grouped => "Grouped",
+            StatsNumberFormat::Compact => "Compact",
+        }
+    }
+}
+
+impl FromStr for StatsNumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(StatsNumberFormat::Raw),
+            "grouped" => Ok(StatsNumberFormat::Grouped),
+            "compact" => Ok(StatsNumberFormat::Compact),
+            s => Err(format!(
+                "{} was not expected. Expected one of `raw`, `grouped` or `compact`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyColorBlend {
+    #[default]
+    Off,
+    Average,
+    Additive,
+}
+
+impl KeyColorBlend {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            KeyColorBlend::Off => "Off",
+            KeyColorBlend::Average => "Average",
+            KeyColorBlend::Additive => "Additive",
+        }
+    }
+}
+
+impl FromStr for KeyColorBlend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(KeyColorBlend::Off),
+            "average" => Ok(KeyColorBlend::Average),
+            "additive" => Ok(KeyColorBlend::Additive),
+            s => Err(format!(
+                "{} was not expected. Expected one of `off`, `average` or `additive`",
+                s
+            )),
+        }
+    }
+}
+
+/// A visual setting that can be driven by a MIDI controller
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum CCTarget {
+    #[default]
+    BackgroundBrightness,
+    NoteBrightness,
+    NoteSaturation,
+    NoteOpacity,
+}
+
+impl CCTarget {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            CCTarget::BackgroundBrightness => "Background Brightness",
+            CCTarget::NoteBrightness => "Note Brightness",
+            CCTarget::NoteSaturation => "Note Saturation",
+            CCTarget::NoteOpacity => "Note Opacity",
+        }
+    }
+}
+
+/// Scales a visual setting by the last value of a MIDI controller, from 0 at
+/// value 0 to unchanged at value 127. The setting is unchanged until the
+/// controller is sent.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct CCMapping {
+    pub controller: u8,
+    /// The channel to follow, counted from 0. Any channel is followed when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    pub target: CCTarget,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(default)]
 pub struct VisualSettings {
     #[serde(with = "color32_serde")]
     pub bg_color: Color32,
+    /// The color at the bottom of the note area, blending from `bg_color` at the top
+    #[serde(
+        with = "color32_serde::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bg_color_bottom: Option<Color32>,
     #[serde(with = "color32_serde")]
     pub bar_color: Color32,
+    /// The height of the bar above the keyboard, it scales with the keyboard when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bar_height_px: Option<f32>,
+    /// Fade the bar to a darker color at its top, instead of a flat color
+    pub bar_gradient: bool,
+    /// Draw dark lines along the edges of the bar
+    pub bar_border: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg_image: Option<String>,
+    pub note_outline: bool,
+    pub note_rounding: f32,
+    /// Notes shorter than this aren't rounded, so that short notes don't turn into circles
+    pub rounding_min_length_px: f32,
+    pub key_color_blend: KeyColorBlend,
+    pub visualize_pitch_bend: bool,
+    pub pitch_bend_range: u8,
+    pub notes_fall_down: bool,
+    /// Make the pressed keys sink in, instead of only changing their color
+    pub key_press_animation: bool,
+    /// Make the pressed keys brighter the harder their notes were hit
+    pub velocity_key_colors: bool,
+    /// The height of the keyboard relative to the width of a single visible key
+    pub keyboard_height_ratio: f32,
+    /// The tallest that the keyboard can get, it can always take up to half of the window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_keyboard_height: Option<f32>,
+    pub beat_flash: bool,
+    pub ui_scale: f32,
+    pub theme: UiTheme,
+    pub brightness: f32,
+    pub saturation: f32,
+    pub min_note_height_px: f32,
+    pub note_gradient: bool,
+    pub show_sustain: bool,
+    /// Nudge the notes of panned channels towards their side
+    pub pan_notes: bool,
+    /// Show the percussion channel as drum names instead of notes
+    pub separate_drums: bool,
+    /// Show the details of the note under the cursor while the MIDI is paused
+    pub note_tooltips: bool,
+    pub gradient_strength: f32,
+    /// How opaque the notes are, lower values let the overlapping notes and the
+    /// background show through
+    pub note_opacity: f32,
+    /// Draw the pressed key colors with the note opacity as well
+    pub keyboard_note_opacity: bool,
+    /// How much the notes light up for a moment as they reach the keyboard, 0 being off
+    pub attack_glow: f32,
+    /// How many pixels the notes cut off by the sides of the view fade out over
+    pub edge_fade_px: f32,
+    /// MIDI controllers that drive visual settings, e.g. the mod wheel for the brightness
+    pub cc_mappings: Vec<CCMapping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rendered_notes: Option<usize>,
     pub show_top_pannel: bool,
     pub show_statistics: bool,
+    /// How the note counts and peaks in the statistics are written
+    pub stats_number_format: StatsNumberFormat,
+    /// The corner of the note area that the statistics are shown in
+    pub stats_corner: OverlayCorner,
+    pub show_fps: bool,
+    pub fps_corner: OverlayCorner,
+    /// Show a graph of the recent frame times in the statistics
+    pub show_frame_graph: bool,
+    pub show_channels: bool,
+    /// Show a light for each channel that is lit while the channel is playing
+    pub show_channel_leds: bool,
     pub fullscreen: bool,
+    pub vsync: bool,
+    /// Keep the screen from going to sleep while a MIDI is playing
+    pub inhibit_sleep: bool,
 }
 
 impl Default for VisualSettings {
     fn default() -> Self {
         VisualSettings {
             bg_color: Color32::from_rgb(30, 30, 30),
+            bg_color_bottom: None,
             bar_color: Color32::from_rgb(145, 0, 0),
+            bar_height_px: None,
+            bar_gradient: true,
+            bar_border: false,
+            bg_image: None,
+            note_outline: true,
+            note_rounding: 0.0,
+            rounding_min_length_px: 4.0,
+            key_color_blend: KeyColorBlend::Off,
+            visualize_pitch_bend: false,
+            pitch_bend_range: 2,
+            notes_fall_down: true,
+            key_press_animation: false,
+            velocity_key_colors: false,
+            keyboard_height_ratio: 11.6,
+            max_keyboard_height: None,
+            beat_flash: false,
+            ui_scale: 1.0,
+            theme: UiTheme::Dark,
+            brightness: 1.0,
+            saturation: 1.0,
+            min_note_height_px: 1.0,
+            note_gradient: false,
+            show_sustain: false,
+            pan_notes: false,
+            separate_drums: false,
+            note_tooltips: true,
+            gradient_strength: 0.5,
+            note_opacity: 1.0,
+            keyboard_note_opacity: false,
+            attack_glow: 0.0,
+            edge_fade_px: 12.0,
+            cc_mappings: Vec::new(),
+            max_rendered_notes: None,
             show_top_pannel: true,
             show_statistics: true,
+            stats_number_format: StatsNumberFormat::Grouped,
+            stats_corner: OverlayCorner::TopLeft,
+            show_fps: false,
+            fps_corner: OverlayCorner::TopRight,
+            show_frame_graph: false,
+            show_channels: false,
+            show_channel_leds: false,
             fullscreen: false,
+            vsync: true,
+            inhibit_sleep: true,
         }
     }
 }
@@ -267,20 +798,62 @@ impl Default for VisualSettings {
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(default)]
 pub struct MidiSettings {
+    /// The seconds that the notes take to cross the screen
     pub note_speed: f64,
+    pub note_speed_unit: NoteSpeedUnit,
+    /// How many seconds before reaching the keyboard the notes appear, when it is less
+    /// than the note speed. The notes keep the length that the note speed gives them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookahead_seconds: Option<f64>,
     pub random_colors: bool,
+    pub color_palette: ColorPalette,
+    pub color_by: ColorBy,
+    /// A JSON or TOML file with colors for specific tracks and channels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_file: Option<String>,
     #[serde(with = "range_serde")]
     pub key_range: RangeInclusive<u8>,
+    /// Narrow the shown keys down to the ones with notes on screen
+    pub follow_active_keys: bool,
     pub midi_loading: MidiLoading,
+    pub fast_forward_speed: f64,
+    /// Only seek once the seek bar is let go, instead of on every step while dragging it
+    pub seek_on_release: bool,
+    pub transpose: i32,
+    /// Play the MIDI at this BPM, ignoring its tempo events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tempo_override: Option<f64>,
+    /// How many channels the events of each MIDI layer are moved over by, skipping the
+    /// drum channel
+    pub layer_channel_offset: u8,
+    pub remember_playlist: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub playlist: Vec<String>,
+    /// Open the last opened MIDI on launch, when no MIDI is given on the command line
+    pub resume_last: bool,
 }
 
 impl Default for MidiSettings {
     fn default() -> Self {
         MidiSettings {
             note_speed: 0.25,
+            note_speed_unit: NoteSpeedUnit::Factor,
+            lookahead_seconds: None,
             random_colors: false,
+            color_palette: ColorPalette::Default,
+            color_by: ColorBy::Both,
+            color_file: None,
             key_range: 0..=127,
+            follow_active_keys: false,
             midi_loading: MidiLoading::Cake,
+            fast_forward_speed: 5.0,
+            seek_on_release: false,
+            transpose: 0,
+            tempo_override: None,
+            layer_channel_offset: 8,
+            remember_playlist: false,
+            playlist: Vec::new(),
+            resume_last: false,
         }
     }
 }
@@ -291,7 +864,14 @@ pub struct SynthSettings {
     pub synth: Synth,
     pub buffer_ms: f64,
     pub use_threadpool: bool,
+    /// How many threads XSynth's threadpool renders with, one per CPU core if not set.
+    /// Only read when Wasabi starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_threads: Option<usize>,
     pub sfz_path: String,
+    /// The soundfonts that the cycle soundfont shortcut switches between, kept loaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub soundfont_cycle: Vec<String>,
     pub limit_layers: bool,
     pub layer_count: usize,
     #[serde(with = "range_serde")]
@@ -299,6 +879,21 @@ pub struct SynthSettings {
     pub fade_out_kill: bool,
     pub linear_envelope: bool,
     pub use_effects: bool,
+    /// How long the voices fade out for when pausing or seeking, 0 to cut them off.
+    /// KDMAPI is only faded while notes are held, the voices left after that are cut off.
+    pub fade_ms: f64,
+    /// The General MIDI program that every channel except the drums is played with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_program: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_device: Option<String>,
+    /// The sample rate that XSynth renders at, the device default if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// The most voices XSynth plays at once, new notes steal the quietest and oldest
+    /// held notes while it's at the limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_voices: Option<u64>,
 }
 
 impl Default for SynthSettings {
@@ -307,13 +902,20 @@ impl Default for SynthSettings {
             synth: Synth::XSynth,
             buffer_ms: XSynthRealtimeConfig::default().render_window_ms,
             use_threadpool: false,
+            render_threads: None,
             sfz_path: String::new(),
+            soundfont_cycle: Vec::new(),
             limit_layers: true,
             layer_count: 4,
             vel_ignore: 0..=0,
             fade_out_kill: ChannelInitOptions::default().fade_out_killing,
             linear_envelope: SoundfontInitOptions::default().linear_release,
             use_effects: SoundfontInitOptions::default().use_effects,
+            fade_ms: 5.0,
+            force_program: None,
+            output_device: None,
+            sample_rate: None,
+            max_voices: None,
         }
     }
 }
@@ -324,40 +926,138 @@ pub struct WasabiSettings {
     pub synth: SynthSettings,
     pub midi: MidiSettings,
     pub visual: VisualSettings,
+    pub keybindings: Keybindings,
+    /// Save the settings shortly after they change, instead of only with the Save button
+    pub autosave: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub load_midi_file: Option<String>,
+    /// Only applies to the MIDI loaded on launch
+    #[serde(skip)]
+    pub start_paused: bool,
+    #[serde(skip)]
+    pub audio_only: bool,
+    /// How many times the MIDI loaded on launch plays, 0 being forever
+    #[serde(skip)]
+    pub loop_count: Option<u32>,
+    #[serde(skip)]
+    pub midi_input: Option<String>,
+    #[serde(skip)]
+    pub export_stats: Option<String>,
+    /// Print the problems found in the MIDI loaded on launch, then quit
+    #[serde(skip)]
+    pub validate: bool,
+    /// Where the raw frames of the MIDI loaded on launch get written
+    #[serde(skip)]
+    pub frames_out: Option<String>,
+    #[serde(skip)]
+    pub frames_fps: u32,
+    #[serde(skip)]
+    pub frames_size: [u32; 2],
 }
 
 static CONFIG_PATH: &str = "wasabi-config.toml";
 
+/// The prefix of the environment variables that override settings
+static ENV_PREFIX: &str = "WASABI_";
+
+/// The environment variable that moves the config folder somewhere else
+static CONFIG_DIR_VAR: &str = "WASABI_CONFIG_DIR";
+
+/// The settings from the config file and the settings after the environment variables
+/// and the command line arguments were applied on launch, so that saving can leave out
+/// the overrides that weren't changed since
+static LAUNCH_SETTINGS: OnceLock<(toml::Table, toml::Table)> = OnceLock::new();
+
+/// Puts the values from the config file back where they were overridden on launch,
+/// unless they were changed since
+fn restore_overridden(current: &mut toml::Table, file: &toml::Table, launch: &toml::Table) {
+    for (key, launch_value) in launch {
+        let file_value = file.get(key);
+        if file_value == Some(launch_value) {
+            continue;
+        }
+
+        if let (
+            Some(toml::Value::Table(current)),
+            Some(toml::Value::Table(file)),
+            toml::Value::Table(launch),
+        ) = (current.get_mut(key), file_value, launch_value)
+        {
+            restore_overridden(current, file, launch);
+            continue;
+        }
+
+        if current.get(key) == Some(launch_value) {
+            match file_value {
+                Some(file_value) => current.insert(key.clone(), file_value.clone()),
+                None => current.remove(key),
+            };
+        }
+    }
+}
+
 impl WasabiSettings {
+    /// Resolves a soundfont or MIDI path from the settings. Relative paths are taken
+    /// from the folder of the config file, usually [`WasabiSettings::config_dir`],
+    /// instead of the working directory, so that a config keeps working when it's
+    /// shared along with the files next to it.
+    pub fn resolve_path(path: &str, config_dir: &Path) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            config_dir.join(path)
+        }
+    }
+
+    /// Loads the config file, then applies the `WASABI_` environment variables
+    /// and the command line arguments on top of it, the arguments having the last say
     pub fn new_or_load() -> Self {
         let config_path = Self::get_config_path();
-        let mut config = if !Path::new(&config_path).exists() {
+        let mut config = if !config_path.exists() {
+            Self::load_and_save_defaults()
+        } else if let Some(config) = Self::load_from_dir(Self::config_dir()) {
+            config
+        } else if fs::read_to_string(&config_path).unwrap().starts_with('#') {
             Self::load_and_save_defaults()
         } else {
-            let config = fs::read_to_string(&config_path).unwrap();
-            if config.starts_with('#') {
-                if let Ok(config) = toml::from_str(&config) {
-                    config
-                } else {
-                    Self::load_and_save_defaults()
-                }
-            } else {
-                let config = migrations::WasabiConfigFileV0::migrate().unwrap_or_default();
-                config.save_to_file();
-                config
-            }
+            let config = migrations::WasabiConfigFileV0::migrate().unwrap_or_default();
+            config.save_to_file();
+            config
         };
 
+        let file_values = toml::Value::try_from(&config);
+        config.augment_from_env();
         config.augment_from_args();
+        if let (Ok(toml::Value::Table(file)), Ok(toml::Value::Table(launch))) =
+            (file_values, toml::Value::try_from(&config))
+        {
+            let _ = LAUNCH_SETTINGS.set((file, launch));
+        }
+
+        let (first, last) = (*config.midi.key_range.start(), *config.midi.key_range.end());
+        if first > last {
+            log::warn!("The keyboard range {first}..={last} is inverted, swapping the keys");
+            config.midi.key_range = last..=first;
+        }
+        if let Some(program) = config.synth.force_program.filter(|program| *program > 127) {
+            log::warn!("The forced program {program} isn't a General MIDI program, using 127");
+            config.synth.force_program = Some(127);
+        }
+
         config
     }
 
+    /// Saves the settings to the config file. Settings that were overridden by the
+    /// environment variables or the command line on launch keep their value from the
+    /// config file, unless they were changed in Wasabi since.
     pub fn save_to_file(&self) {
         let config_path = Self::get_config_path();
-        let toml: String = toml::to_string(&self).unwrap();
-        if Path::new(&config_path).exists() {
+        let toml: String = match self.without_launch_overrides() {
+            Some(settings) => toml::to_string(&settings).unwrap(),
+            None => toml::to_string(&self).unwrap(),
+        };
+        if config_path.exists() {
             fs::remove_file(&config_path).expect("Error deleting old config");
         }
         let mut file = fs::File::create(&config_path).unwrap();
@@ -367,6 +1067,115 @@ impl WasabiSettings {
             .expect("Error creating config");
     }
 
+    /// A copy of the settings with the launch overrides that weren't changed since
+    /// put back to their values from the config file, if there were overrides
+    fn without_launch_overrides(&self) -> Option<Self> {
+        let (file, launch) = LAUNCH_SETTINGS.get()?;
+        let toml::Value::Table(mut current) = toml::Value::try_from(self).ok()? else {
+            return None;
+        };
+        restore_overridden(&mut current, file, launch);
+        toml::Value::Table(current).try_into().ok()
+    }
+
+    /// Overrides settings with environment variables named after the section and the
+    /// setting in the config file, e.g. `WASABI_SYNTH__SFZ_PATH` or
+    /// `WASABI_MIDI__NOTE_SPEED=0.5`. Variables that don't match a setting, or hold a
+    /// value that the setting can't take, are ignored with a warning. Like the command
+    /// line arguments, the overrides aren't saved to the config file unless changed.
+    fn augment_from_env(&mut self) {
+        let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(&*self) else {
+            return;
+        };
+
+        let mut changed = false;
+        for (name, raw) in std::env::vars() {
+            if !name.starts_with(ENV_PREFIX) || name == CONFIG_DIR_VAR {
+                continue;
+            }
+            let Some((section, key)) = Self::env_var_setting(&name) else {
+                log::warn!(
+                    "Ignoring {name}, settings are set with the section and the setting \
+                    in the name, e.g. {ENV_PREFIX}MIDI__NOTE_SPEED"
+                );
+                continue;
+            };
+
+            // The keybindings are kebab case, everything else is snake case
+            let new_table = [key.clone(), key.replace('_', "-")]
+                .iter()
+                .find_map(|key| Self::apply_env_var(&table, &section, key, &raw));
+            match new_table {
+                Some(new_table) => {
+                    table = new_table;
+                    changed = true;
+                }
+                None => {
+                    log::warn!("Ignoring {name}, there's no such setting or it can't be {raw:?}")
+                }
+            }
+        }
+
+        if changed {
+            match toml::Value::Table(table).try_into() {
+                Ok(config) => *self = config,
+                Err(e) => log::warn!("Failed to apply the environment variables: {e}"),
+            }
+        }
+    }
+
+    /// The section and the setting that an environment variable is named after
+    fn env_var_setting(name: &str) -> Option<(String, String)> {
+        let (section, key) = name.strip_prefix(ENV_PREFIX)?.split_once("__")?;
+        if section.is_empty() || key.is_empty() {
+            return None;
+        }
+        Some((section.to_lowercase(), key.to_lowercase()))
+    }
+
+    /// Puts the value in the setting of the section, returning the new config
+    /// if the setting took it
+    fn apply_env_var(
+        table: &toml::Table,
+        section: &str,
+        key: &str,
+        raw: &str,
+    ) -> Option<toml::Table> {
+        if !table.get(section)?.is_table() {
+            return None;
+        }
+
+        // Numbers, booleans and inline tables are read as TOML, anything else is a string
+        let typed = toml::from_str::<toml::Table>(&format!("value = {raw}"))
+            .ok()
+            .and_then(|mut value| value.remove("value"));
+        let values = typed
+            .into_iter()
+            .chain(std::iter::once(toml::Value::String(raw.to_owned())));
+
+        for value in values {
+            let mut new_table = table.clone();
+            new_table
+                .get_mut(section)
+                .and_then(|s| s.as_table_mut())?
+                .insert(key.to_owned(), value);
+
+            let Ok(config) = toml::Value::Table(new_table).try_into::<Self>() else {
+                continue;
+            };
+
+            // Unknown keys are dropped when deserializing, so they won't come back out
+            let Ok(toml::Value::Table(new_table)) = toml::Value::try_from(&config) else {
+                continue;
+            };
+            if new_table.get(section).and_then(|s| s.get(key)).is_some() {
+                return Some(new_table);
+            }
+        }
+
+        None
+    }
+
     fn augment_from_args(&mut self) {
         let matches = Command::new("wasabi")
             .version(env!("CARGO_PKG_VERSION"))
@@ -396,6 +1205,28 @@ impl WasabiSettings {
                     .long("buffer-ms")
                     .value_parser(f64_parser),
             )
+            .arg(
+                Arg::new("fade-ms")
+                    .help("How long notes fade out for when pausing or seeking")
+                    .long_help(
+                        "The amount of milliseconds that the playing notes are faded \
+                        out over when pausing or seeking, which avoids clicks when they \
+                        stop. 0 stops them instantly",
+                    )
+                    .long("fade-ms")
+                    .value_parser(f64_parser),
+            )
+            .arg(
+                Arg::new("force-program")
+                    .help("Play every channel with the same instrument")
+                    .long_help(
+                        "A General MIDI program number from 0 to 127 that every channel \
+                        except the drums is played with, replacing the program changes \
+                        in the MIDI",
+                    )
+                    .long("force-program")
+                    .value_parser(value_parser!(u8).range(0..=127)),
+            )
             .arg(
                 Arg::new("sfz-path")
                     .help("The path to an SFZ SoundFont")
@@ -476,17 +1307,96 @@ impl WasabiSettings {
                     .long("no-effects")
                     .action(ArgAction::SetFalse),
             )
+            .arg(
+                Arg::new("output-device")
+                    .help("The audio device that XSynth plays to")
+                    .long_help(
+                        "The name of the audio output device used by XSynth. Use \
+                        `--list-audio-devices` to see the available names. If the device \
+                        can't be found, the default output device is used",
+                    )
+                    .short('o')
+                    .long("output-device"),
+            )
+            .arg(
+                Arg::new("render-threads")
+                    .help("The amount of threads XSynth renders audio with")
+                    .long_help(
+                        "The amount of threads that XSynth's threadpool uses to render \
+                        audio. More threads allow playing MIDIs with higher NPS, but add \
+                        overhead for light MIDIs. Only has an effect with the threadpool \
+                        enabled. Defaults to one thread per CPU core",
+                    )
+                    .long("render-threads")
+                    .value_parser(value_parser!(usize).range(1..)),
+            )
+            .arg(
+                Arg::new("sample-rate")
+                    .help("The sample rate that XSynth renders at")
+                    .long_help(
+                        "The sample rate in Hz that XSynth renders at, e.g. 44100 or 48000. \
+                        If the audio device doesn't support it, the device default is used",
+                    )
+                    .long("sample-rate")
+                    .value_parser(value_parser!(u32)),
+            )
+            .arg(
+                Arg::new("max-voices")
+                    .help("The most voices that XSynth plays at once")
+                    .long_help(
+                        "Limits how many voices XSynth plays at once, to prevent audio \
+                        dropouts on slower computers. While the limit is reached, each new \
+                        note releases the quietest held note, the oldest one if several \
+                        are as quiet. Notes that are already releasing keep going",
+                    )
+                    .long("max-voices")
+                    .value_parser(value_parser!(u64).range(1..)),
+            )
+            .arg(
+                Arg::new("list-audio-devices")
+                    .help("List the available audio output devices and exit")
+                    .long("list-audio-devices")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("midi-input")
+                    .help("A MIDI input port to play live")
+                    .long_help(
+                        "Opens a MIDI input port, such as a connected keyboard, and plays \
+                        its notes through the synth. This can be the name or the number \
+                        of the port, use `--list-midi-inputs` to see the available ports",
+                    )
+                    .long("midi-input"),
+            )
+            .arg(
+                Arg::new("list-midi-inputs")
+                    .help("List the available MIDI input ports and exit")
+                    .long("list-midi-inputs")
+                    .action(ArgAction::SetTrue),
+            )
             .arg(
                 Arg::new("note-speed")
                     .help("The speed that the notes travel on-screen")
                     .long_help(
                         "The speed at which the notes will move across the screen. This makes \
-                        the notes physically longer, causing them to move faster on-screen",
+                        the notes physically longer, causing them to move faster on-screen. \
+                        Goes from above 0 to 2, and the notes take 2 minus this many seconds \
+                        to cross the screen",
                     )
                     .short('n')
                     .long("note-speed")
                     .value_parser(note_speed),
             )
+            .arg(
+                Arg::new("lookahead")
+                    .help("How many seconds before reaching the keyboard the notes appear")
+                    .long_help(
+                        "Only shows the notes that reach the keyboard within this many seconds, \
+                        without changing their length. Values above the note speed have no effect",
+                    )
+                    .long("lookahead")
+                    .value_parser(f64_parser),
+            )
             .arg(
                 Arg::new("random-colors")
                     .help("Make each channel a random color")
@@ -495,13 +1405,48 @@ impl WasabiSettings {
                     .long("random-colors")
                     .action(ArgAction::SetTrue),
             )
+            .arg(
+                Arg::new("color-palette")
+                    .help("The colors given to the note channels")
+                    .long_help(
+                        "The set of colors given to the note channels, one of `default`, \
+                        `colorblind`, `grayscale` or `pastel`. This is ignored when \
+                        `--random-colors` is used",
+                    )
+                    .long("color-palette")
+                    .value_parser(ColorPalette::from_str),
+            )
+            .arg(
+                Arg::new("color-by")
+                    .help("Whether the notes are colored by track, channel or both")
+                    .long_help(
+                        "What the note colors are picked from, one of `both`, `track` or \
+                        `channel`. Coloring by track helps with MIDIs that put everything \
+                        in a few channels across many tracks",
+                    )
+                    .long("color-by")
+                    .value_parser(ColorBy::from_str),
+            )
+            .arg(
+                Arg::new("colors")
+                    .value_hint(ValueHint::FilePath)
+                    .help("A JSON or TOML file with colors for specific tracks and channels")
+                    .long_help(
+                        "A JSON or TOML file with a `tracks` and a `channels` table, which \
+                        map track and channel numbers counted from 0 to hex colors. The \
+                        tracks and channels that aren't in the file keep the colors of \
+                        the palette. This is ignored when `--random-colors` is used",
+                    )
+                    .long("colors"),
+            )
             .arg(
                 Arg::new("key-range")
                     .help("The key range of the on-screen piano keyboard")
                     .long_help(
                         "Two numbers, comma seperated, that describe the range \
                         of keys to be shown on the on-screen piano keyboard, the range must \
-                        be less than 255 and more than 0",
+                        be less than 255 and more than 0. The presets `88` (or `piano`), `61` \
+                        and `full` can be given instead of the numbers",
                     )
                     .short('k')
                     .long("key-range")
@@ -521,6 +1466,15 @@ impl WasabiSettings {
                     .long("midi-loading")
                     .value_parser(MidiLoading::from_str),
             )
+            .arg(
+                Arg::new("tempo-override")
+                    .help("Play the MIDI at a fixed BPM")
+                    .long_help(
+                        "Ignores the tempo changes in the MIDI and plays all of it at the given BPM",
+                    )
+                    .long("tempo-override")
+                    .value_parser(tempo_override),
+            )
             .arg(
                 Arg::new("bg-color")
                     .help("The window background")
@@ -528,6 +1482,26 @@ impl WasabiSettings {
                     .long("bg-color")
                     .value_parser(color_parser),
             )
+            .arg(
+                Arg::new("bg-color-bottom")
+                    .help("Fade the background into this color towards the bottom")
+                    .long_help(
+                        "A hex color string that the background of the notes fades into \
+                        towards the bottom, starting from the background color at the top",
+                    )
+                    .long("bg-color-bottom")
+                    .value_parser(color_parser),
+            )
+            .arg(
+                Arg::new("bg-image")
+                    .help("An image to show behind the notes")
+                    .long_help(
+                        "The path to an image file that is shown behind the notes \
+                        instead of the background color",
+                    )
+                    .long("bg-image")
+                    .value_hint(ValueHint::FilePath),
+            )
             .arg(
                 Arg::new("bar-color")
                     .help("The color of the bar just above the piano")
@@ -538,6 +1512,17 @@ impl WasabiSettings {
                     .long("bar-color")
                     .value_parser(color_parser),
             )
+            .arg(
+                Arg::new("key-color-blend")
+                    .help("How the colors of overlapping notes are shown on the keyboard")
+                    .long_help(
+                        "How the keyboard colors a key that is pressed by notes of \
+                        different colors at once. `off` shows the color of a single \
+                        note, `average` mixes the colors and `additive` adds them together",
+                    )
+                    .long("key-color-blend")
+                    .value_parser(KeyColorBlend::from_str),
+            )
             .arg(
                 Arg::new("hide-top-pannel")
                     .long_help(
@@ -558,6 +1543,100 @@ impl WasabiSettings {
                     .long("hide-statistics")
                     .action(ArgAction::SetFalse),
             )
+            .arg(
+                Arg::new("theme")
+                    .help("The color theme of the menus")
+                    .long_help(
+                        "The color theme of the menus and windows, one of `dark`, `light` \
+                        or `system`. `system` follows the theme of the OS where it's \
+                        known, and is dark otherwise. The notes aren't affected",
+                    )
+                    .long("theme")
+                    .value_parser(UiTheme::from_str),
+            )
+            .arg(
+                Arg::new("beat-flash")
+                    .help("Flash the keyboard bar on every beat")
+                    .long_help(
+                        "Flashes the colored bar above the keyboard on every beat of \
+                        the MIDI, using its tempo and time signature. The first beat \
+                        of each bar gets a stronger flash",
+                    )
+                    .long("beat-flash")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("show-fps")
+                    .help("Show the FPS counter")
+                    .long_help(
+                        "Shows a small FPS counter in a corner of the window, without \
+                        the rest of the statistics. It can be toggled with Ctrl+P",
+                    )
+                    .long("show-fps")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("fps-corner")
+                    .help("The corner the FPS counter is shown in")
+                    .long_help(
+                        "The corner of the note area that the FPS counter is shown in. \
+                        One of `top-left`, `top-right`, `bottom-left` or `bottom-right`",
+                    )
+                    .long("fps-corner")
+                    .value_parser(OverlayCorner::from_str),
+            )
+            .arg(
+                Arg::new("show-frame-graph")
+                    .help("Show a graph of the frame times in the statistics")
+                    .long_help(
+                        "Shows how long each frame took over the last few seconds \
+                        in the statistics, with the frames that missed the refresh \
+                        of the monitor in red",
+                    )
+                    .long("show-frame-graph")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("stats-corner")
+                    .help("The corner the statistics are shown in")
+                    .long_help(
+                        "The corner of the note area that the statistics are shown in. \
+                        One of `top-left`, `top-right`, `bottom-left` or `bottom-right`",
+                    )
+                    .long("stats-corner")
+                    .value_parser(OverlayCorner::from_str),
+            )
+            .arg(
+                Arg::new("stats-number-format")
+                    .help("How the numbers in the statistics are written")
+                    .long_help(
+                        "How the note counts and peaks in the statistics are written. \
+                        `raw` writes 1234567, `grouped` writes 1,234,567 and `compact` \
+                        writes 1.2M",
+                    )
+                    .long("stats-number-format")
+                    .value_parser(StatsNumberFormat::from_str),
+            )
+            .arg(
+                Arg::new("show-channels")
+                    .help("Show the channel list")
+                    .long_help(
+                        "Shows the list of MIDI channels and their current instruments \
+                        when the app opens. It can be toggled with Ctrl+I",
+                    )
+                    .long("show-channels")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("show-channel-leds")
+                    .help("Show which channels are playing")
+                    .long_help(
+                        "Shows a row of 16 lights above the notes, which light up in the \
+                        color of their channel while it has notes playing",
+                    )
+                    .long("show-channel-leds")
+                    .action(ArgAction::SetTrue),
+            )
             .arg(
                 Arg::new("fullscreen")
                     .help("Start `wasabi` in fullscreen")
@@ -570,15 +1649,159 @@ impl WasabiSettings {
                     .long("fullscreen")
                     .action(ArgAction::SetTrue),
             )
+            .arg(
+                Arg::new("no-vsync")
+                    .help("Disable vsync")
+                    .long_help(
+                        "Presents frames as soon as they are rendered instead of waiting \
+                        for the display, which lowers the latency but can cause tearing. \
+                        Vsync is still used if the GPU doesn't support this",
+                    )
+                    .long("no-vsync")
+                    .action(ArgAction::SetFalse),
+            )
+            .arg(
+                Arg::new("audio-only")
+                    .help("Play the MIDI file without opening a window")
+                    .long_help(
+                        "Plays the MIDI file without the GUI. Instead, a CLI will open \
+                        which will allow you to control the playback",
+                    )
+                    .long("audio-only")
+                    .requires("midi-file")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("export-stats")
+                    .value_hint(ValueHint::FilePath)
+                    .help("Save statistics about the MIDI file to a CSV or JSON file")
+                    .long_help(
+                        "Saves the note counts per track and channel, the key range, \
+                        the length, the tempo range and the peak NPS of the MIDI file. \
+                        The file is written as JSON if its name ends with `.json`, \
+                        otherwise as CSV, where the tracks and channels count from 1",
+                    )
+                    .long("export-stats")
+                    .requires("midi-file"),
+            )
+            .arg(
+                Arg::new("validate")
+                    .help("Check the MIDI file for common problems, then quit")
+                    .long_help(
+                        "Reads through the MIDI file and reports notes that are never \
+                        ended, note offs without a playing note, values outside of the \
+                        MIDI range and parsing errors, then quits without opening a window",
+                    )
+                    .long("validate")
+                    .requires("midi-file")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("frames-out")
+                    .value_hint(ValueHint::FilePath)
+                    .help("Write the frames of the MIDI file as raw RGBA to a file or pipe")
+                    .long_help(
+                        "Plays the MIDI file given on the command line at a fixed timestep \
+                        and writes every frame to this file or named pipe, then quits once \
+                        the MIDI ends. Each frame is width * height * 4 bytes of 8 bit RGBA, \
+                        rows from top to bottom with no padding, at the size given by \
+                        --frames-width and --frames-height. The frames only show the notes \
+                        of the main MIDI, without the keyboard or the UI, and no audio is \
+                        written",
+                    )
+                    .long("frames-out")
+                    .requires("midi-file"),
+            )
+            .arg(
+                Arg::new("frames-fps")
+                    .help("The frame rate of the frames written by --frames-out")
+                    .long("frames-fps")
+                    .default_value("60")
+                    .value_parser(value_parser!(u32).range(1..=240)),
+            )
+            .arg(
+                Arg::new("frames-width")
+                    .help("The width in pixels of the frames written by --frames-out")
+                    .long("frames-width")
+                    .default_value("1920")
+                    .value_parser(value_parser!(u32).range(1..=8192)),
+            )
+            .arg(
+                Arg::new("frames-height")
+                    .help("The height in pixels of the frames written by --frames-out")
+                    .long("frames-height")
+                    .default_value("1080")
+                    .value_parser(value_parser!(u32).range(1..=8192)),
+            )
             .arg(
                 Arg::new("midi-file")
                     .value_hint(ValueHint::FilePath)
                     .help("The MIDI file to immediately begin playing")
                     .long_help(
                         "This MIDI file is played immediately after the app's launch. \
-                        This argument is required to use the `--audio-only` option",
+                        It can also be an HTTP(S) URL to download the MIDI from, or `-` \
+                        to read it from stdin. This argument is required to use the \
+                        `--audio-only` option",
                     ),
             )
+            .arg(
+                Arg::new("loop-count")
+                    .value_name("N")
+                    .help("Play the MIDI file N times, 0 loops it forever")
+                    .long_help(
+                        "Plays the MIDI file given on the command line N times before \
+                        stopping, restarting it from the beginning each time it ends. \
+                        A count of 0 keeps looping it until it is stopped. Also works \
+                        with `--audio-only`, which quits after the last loop",
+                    )
+                    .long("loop-count")
+                    .requires("midi-file")
+                    .value_parser(value_parser!(u32)),
+            )
+            .arg(
+                Arg::new("resume-last")
+                    .help("Open the last opened MIDI file when no file is given")
+                    .long_help(
+                        "Loads and plays the MIDI file that was opened most recently when \
+                        no MIDI file is given on the command line, if it still exists. \
+                        Works together with `--start-paused`",
+                    )
+                    .long("resume-last")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("verbose")
+                    .help("Log more details of what the app is doing")
+                    .long_help(
+                        "Logs debugging details such as the chosen devices and the \
+                        progress of loading, on top of the warnings and errors. \
+                        The `RUST_LOG` environment variable overrides this",
+                    )
+                    .long("verbose")
+                    .conflicts_with("quiet")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .help("Only log errors")
+                    .long_help(
+                        "Hides everything but errors from the log. \
+                        The `RUST_LOG` environment variable overrides this",
+                    )
+                    .short('q')
+                    .long("quiet")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("start-paused")
+                    .help("Don't start playing the MIDI file right away")
+                    .long_help(
+                        "Loads the MIDI file given on the command line but leaves \
+                        it paused, so it can be started with Space when ready",
+                    )
+                    .long("start-paused")
+                    .action(ArgAction::SetTrue),
+            )
             .get_matches();
 
         macro_rules! set {
@@ -607,31 +1830,125 @@ impl WasabiSettings {
             };
         }
 
+        if matches.get_flag("list-audio-devices") {
+            for device in list_output_devices() {
+                println!("{device}");
+            }
+            std::process::exit(0);
+        }
+
+        if matches.get_flag("list-midi-inputs") {
+            for (i, port) in list_midi_inputs().iter().enumerate() {
+                println!("{i}: {port}");
+            }
+            std::process::exit(0);
+        }
+
         self.load_midi_file = matches.get_one::<String>("midi-file").map(|f| f.to_owned());
+        self.frames_out = matches
+            .get_one::<String>("frames-out")
+            .map(|p| p.to_owned());
+        self.frames_fps = matches.get_one::<u32>("frames-fps").copied().unwrap_or(60);
+        self.frames_size = [
+            matches
+                .get_one::<u32>("frames-width")
+                .copied()
+                .unwrap_or(1920),
+            matches
+                .get_one::<u32>("frames-height")
+                .copied()
+                .unwrap_or(1080),
+        ];
+        // The frames are stepped through one at a time instead of playing
+        self.start_paused = matches.get_flag("start-paused") || self.frames_out.is_some();
+        self.audio_only = matches.get_flag("audio-only");
+        self.loop_count = matches.get_one::<u32>("loop-count").copied();
+        self.midi_input = matches
+            .get_one::<String>("midi-input")
+            .map(|p| p.to_owned());
+        self.export_stats = matches
+            .get_one::<String>("export-stats")
+            .map(|p| p.to_owned());
+        self.validate = matches.get_flag("validate");
 
         // Synth settings
         set!(synth.synth, "synth");
         set!(synth.buffer_ms, "buffer-ms");
-        set_owned!(synth.sfz_path, "sfz-path", String);
+        set!(synth.fade_ms, "fade-ms");
+        if let Some(program) = matches.get_one::<u8>("force-program") {
+            self.synth.force_program = Some(*program);
+        }
+        // Relative paths in the config are taken from its folder, but on the
+        // command line they are expected to be from the working directory
+        if let Some(path) = matches.get_one::<String>("sfz-path") {
+            self.synth.sfz_path = std::env::current_dir()
+                .map(|dir| dir.join(path).to_string_lossy().into_owned())
+                .unwrap_or_else(|_| path.to_owned());
+        }
         set_flag!(synth.limit_layers, "dont-limit-layers");
         set!(synth.layer_count, "layer-count");
         set_owned!(synth.vel_ignore, "vel-ignore", RangeInclusive<u8>);
         set_flag!(synth.fade_out_kill, "fade-out-kill");
         set_flag!(synth.linear_envelope, "linear-envelope");
         set_flag!(synth.use_effects, "no-effects");
+        if let Some(output_device) = matches.get_one::<String>("output-device") {
+            self.synth.output_device = Some(output_device.to_owned());
+        }
+        if let Some(render_threads) = matches.get_one::<usize>("render-threads") {
+            self.synth.render_threads = Some(*render_threads);
+        }
+        if let Some(sample_rate) = matches.get_one::<u32>("sample-rate") {
+            self.synth.sample_rate = Some(*sample_rate);
+        }
+        if let Some(max_voices) = matches.get_one::<u64>("max-voices") {
+            self.synth.max_voices = Some(*max_voices);
+        }
 
         // MIDI settings
         set!(midi.note_speed, "note-speed");
+        if let Some(bpm) = matches.get_one::<f64>("tempo-override") {
+            self.midi.tempo_override = Some(*bpm);
+        }
+        if let Some(lookahead) = matches.get_one::<f64>("lookahead") {
+            self.midi.lookahead_seconds = Some(*lookahead);
+        }
         set_flag!(midi.random_colors, "random-colors");
+        set!(midi.color_palette, "color-palette");
+        set!(midi.color_by, "color-by");
+        if let Some(color_file) = matches.get_one::<String>("colors") {
+            self.midi.color_file = Some(color_file.to_owned());
+        }
         set_owned!(midi.key_range, "key-range", RangeInclusive<u8>);
         set!(midi.midi_loading, "midi-loading");
 
         // Visual settings
         set!(visual.bg_color, "bg-color");
+        if let Some(color) = matches.get_one::<Color32>("bg-color-bottom") {
+            self.visual.bg_color_bottom = Some(*color);
+        }
         set!(visual.bar_color, "bar-color");
+        set!(visual.key_color_blend, "key-color-blend");
+        if let Some(bg_image) = matches.get_one::<String>("bg-image") {
+            self.visual.bg_image = Some(bg_image.to_owned());
+        }
         set_flag!(visual.show_top_pannel, "hide-top-pannel");
         set_flag!(visual.show_statistics, "hide-statistics");
+        set!(visual.theme, "theme");
+        set_flag!(visual.beat_flash, "beat-flash");
+        set_flag!(visual.show_fps, "show-fps");
+        set!(visual.fps_corner, "fps-corner");
+        set!(visual.stats_number_format, "stats-number-format");
+        set!(visual.stats_corner, "stats-corner");
+        set_flag!(visual.show_frame_graph, "show-frame-graph");
+        set_flag!(visual.show_channels, "show-channels");
+        set_flag!(visual.show_channel_leds, "show-channel-leds");
         set_flag!(visual.fullscreen, "fullscreen");
+        set_flag!(visual.vsync, "no-vsync");
+
+        set_flag!(midi.resume_last, "resume-last");
+        if self.load_midi_file.is_none() && self.midi.resume_last {
+            self.load_midi_file = recent::last_recent_midi();
+        }
     }
 
     fn load_and_save_defaults() -> Self {
@@ -641,23 +1958,144 @@ impl WasabiSettings {
         cfg
     }
 
-    fn get_config_path() -> String {
-        if let Some(base_dirs) = BaseDirs::new() {
-            let mut path: PathBuf = base_dirs.config_dir().to_path_buf();
-            path.push("wasabi");
-            path.push(CONFIG_PATH);
+    /// Reads the config file in a folder, if it's there and in the current format
+    fn load_from_dir(config_dir: &Path) -> Option<Self> {
+        let config = fs::read_to_string(config_dir.join(CONFIG_PATH)).ok()?;
+        if !config.starts_with('#') {
+            return None;
+        }
+        toml::from_str(&config).ok()
+    }
+
+    fn get_config_path() -> PathBuf {
+        Self::config_dir().join(CONFIG_PATH)
+    }
 
-            if std::fs::create_dir_all(path.parent().unwrap()).is_ok() {
-                if let Some(path) = path.to_str() {
-                    path.to_string()
-                } else {
-                    CONFIG_PATH.to_string()
-                }
-            } else {
-                CONFIG_PATH.to_string()
-            }
-        } else {
-            CONFIG_PATH.to_string()
+    /// The folder holding the config file, created the first time it's asked for.
+    /// It can be moved with the `WASABI_CONFIG_DIR` environment variable, and falls
+    /// back to the working directory when there's no config folder to use.
+    pub fn config_dir() -> &'static Path {
+        static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+        CONFIG_DIR.get_or_init(|| {
+            Self::find_config_dir(std::env::var_os(CONFIG_DIR_VAR))
+                .filter(|dir| fs::create_dir_all(dir).is_ok())
+                .unwrap_or_default()
+        })
+    }
+
+    fn find_config_dir(overridden: Option<std::ffi::OsString>) -> Option<PathBuf> {
+        match overridden.filter(|dir| !dir.is_empty()) {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => BaseDirs::new().map(|base_dirs| base_dirs.config_dir().join("wasabi")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_are_taken_from_the_config_dir() {
+        let config_dir = std::env::temp_dir().join("wasabi-shared-config");
+        assert_eq!(
+            WasabiSettings::resolve_path("soundfonts/piano.sfz", &config_dir),
+            config_dir.join("soundfonts").join("piano.sfz")
+        );
+        assert_ne!(
+            WasabiSettings::resolve_path("piano.sfz", &config_dir),
+            std::env::current_dir().unwrap().join("piano.sfz")
+        );
+    }
+
+    #[test]
+    fn absolute_paths_are_kept() {
+        let config_dir = std::env::temp_dir().join("wasabi-shared-config");
+        let soundfont = std::env::temp_dir().join("piano.sfz");
+        assert_eq!(
+            WasabiSettings::resolve_path(soundfont.to_str().unwrap(), &config_dir),
+            soundfont
+        );
+    }
+
+    #[test]
+    fn config_dir_can_be_moved() {
+        let config_dir = std::env::temp_dir().join("wasabi-moved-config");
+        assert_eq!(
+            WasabiSettings::find_config_dir(Some(config_dir.clone().into_os_string())),
+            Some(config_dir)
+        );
+        assert_ne!(
+            WasabiSettings::find_config_dir(Some(Default::default())),
+            Some(PathBuf::new())
+        );
+    }
+
+    #[test]
+    fn loaded_config_paths_are_taken_from_its_dir() {
+        let config_dir = std::env::temp_dir().join(format!("wasabi-config-{}", std::process::id()));
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join(CONFIG_PATH),
+            "# DON'T EDIT THIS LINE; Version: 1\n\n[synth]\nsfz_path = \"soundfonts/piano.sfz\"\n",
+        )
+        .unwrap();
+
+        let config = WasabiSettings::load_from_dir(&config_dir);
+        let _ = fs::remove_dir_all(&config_dir);
+
+        let config = config.unwrap();
+        assert_eq!(
+            WasabiSettings::resolve_path(&config.synth.sfz_path, &config_dir),
+            config_dir.join("soundfonts").join("piano.sfz")
+        );
+    }
+
+    #[test]
+    fn env_vars_are_named_after_the_section() {
+        assert_eq!(
+            WasabiSettings::env_var_setting("WASABI_MIDI__NOTE_SPEED"),
+            Some(("midi".to_owned(), "note_speed".to_owned()))
+        );
+        assert_eq!(WasabiSettings::env_var_setting("WASABI_NOTE_SPEED"), None);
+        assert_eq!(WasabiSettings::env_var_setting("WASABI___NOTE_SPEED"), None);
+    }
+
+    #[test]
+    fn env_vars_only_set_their_own_section() {
+        let Ok(toml::Value::Table(table)) = toml::Value::try_from(WasabiSettings::default()) else {
+            panic!("the settings should be a table");
+        };
+
+        let table = WasabiSettings::apply_env_var(&table, "midi", "note_speed", "0.5").unwrap();
+        let settings: WasabiSettings = toml::Value::Table(table.clone()).try_into().unwrap();
+        assert_eq!(settings.midi.note_speed, 0.5);
+
+        assert!(WasabiSettings::apply_env_var(&table, "visual", "note_speed", "0.5").is_none());
+        assert!(WasabiSettings::apply_env_var(&table, "midi", "note_speed", "fast").is_none());
+        assert!(WasabiSettings::apply_env_var(&table, "colors", "note_speed", "0.5").is_none());
+    }
+
+    #[test]
+    fn launch_overrides_are_not_saved_unless_changed() {
+        let file: toml::Table =
+            toml::from_str("[midi]\nnote_speed = 0.25\ntranspose = 0\n[synth]\nfade_ms = 10.0\n")
+                .unwrap();
+        let launch: toml::Table = toml::from_str(
+            "[midi]\nnote_speed = 0.5\ntranspose = 2\n[synth]\nfade_ms = 10.0\nrender_threads = 4\n",
+        )
+        .unwrap();
+        // The transpose was changed again after launch, the fade after that
+        let mut current: toml::Table = toml::from_str(
+            "[midi]\nnote_speed = 0.5\ntranspose = 5\n[synth]\nfade_ms = 20.0\nrender_threads = 4\n",
+        )
+        .unwrap();
+
+        restore_overridden(&mut current, &file, &launch);
+
+        let expected: toml::Table =
+            toml::from_str("[midi]\nnote_speed = 0.25\ntranspose = 5\n[synth]\nfade_ms = 20.0\n")
+                .unwrap();
+        assert_eq!(current, expected);
+    }
+}
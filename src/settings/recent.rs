@@ -0,0 +1,50 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::WasabiSettings;
+
+/// How many of the last opened MIDIs are remembered
+const MAX_RECENT_MIDIS: usize = 10;
+
+static RECENT_PATH: &str = "recent-midis.txt";
+
+/// The recent MIDIs are kept next to the config, so they're saved
+/// right away without saving the settings that were changed
+fn recent_path() -> PathBuf {
+    WasabiSettings::config_dir().join(RECENT_PATH)
+}
+
+/// The last opened MIDIs, newest first
+pub fn recent_midis() -> Vec<String> {
+    fs::read_to_string(recent_path())
+        .map(|recent| {
+            recent
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Moves the MIDI to the top of the recent MIDIs
+pub fn add_recent_midi(path: &str) {
+    let mut recent = recent_midis();
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_owned());
+    recent.truncate(MAX_RECENT_MIDIS);
+
+    if let Err(err) = fs::write(recent_path(), recent.join("\n")) {
+        log::warn!("Failed to save the recent MIDIs: {err}");
+    }
+}
+
+/// The most recently opened MIDI, if it still exists
+pub fn last_recent_midi() -> Option<String> {
+    recent_midis()
+        .into_iter()
+        .next()
+        .filter(|path| Path::new(path).exists())
+}
@@ -1,10 +1,47 @@
 use std::path::PathBuf;
 
+use winit::window::Theme;
+
+use crate::{audio_playback::event_log::EventKind, settings::keybindings::ShortcutAction};
+
+/// The windows that were open before compact mode closed them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HiddenWindows {
+    pub settings: bool,
+    pub xsynth_settings: bool,
+    pub tracks: bool,
+    pub playlist: bool,
+    pub event_log: bool,
+}
+
 #[derive(Clone, Default)]
 pub struct WasabiState {
     pub fullscreen: bool,
+    pub take_screenshot: bool,
     pub settings_visible: bool,
     pub xsynth_settings_visible: bool,
+    pub tracks_visible: bool,
+    pub sort_tracks_by_notes: bool,
+    pub playlist_visible: bool,
+    pub event_log_visible: bool,
+    /// The channel that the event log is narrowed down to
+    pub event_log_channel: Option<u8>,
+    /// The event types that the event log leaves out
+    pub event_log_hidden_kinds: Vec<EventKind>,
+    pub shortcuts_visible: bool,
+    pub loop_playback: bool,
+    pub show_musical_time: bool,
     pub last_midi_file: Option<PathBuf>,
+    /// The theme of the OS, if the platform reports it
+    pub system_theme: Option<Theme>,
+    /// The refresh rate of the monitor in millihertz, if the platform reports it
+    pub refresh_rate_mhz: Option<u32>,
+    /// The shortcut that the next key press gets bound to
+    pub rebinding_shortcut: Option<ShortcutAction>,
+    /// Set while compact mode hides everything but the notes and the keyboard. The
+    /// panels are only left out while drawing, so their settings stay as they are.
+    pub compact_mode: bool,
+    /// The windows to open again once compact mode is turned off
+    pub hidden_windows: HiddenWindows,
     // pub last_sfz_file: Option<PathBuf>,
 }